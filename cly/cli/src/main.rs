@@ -1,26 +1,174 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 use anyhow::{anyhow, Context, Result};
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use cly_impl::converter::ConversionResult;
+use cly_impl::{DocFormat, Project};
+use repc_impl::builder::Options;
 use repc_impl::target::{Target, TARGETS};
+use std::env;
+use std::fs;
 use std::fs::File;
-use std::io::{stdin, Read};
+use std::io::{stdin, stdout, Read, Write};
+use std::path::Path;
 use std::process;
 
-fn args() -> (Target, Option<String>) {
-    let matches = App::new("c-layout")
-        .arg(
-            Arg::with_name("print-targets")
-                .long("print-targets")
-                .help("Prints all available targets"),
-        )
-        .arg(
-            Arg::with_name("target")
-                .long("target")
-                .takes_value(true)
-                .help("Sets the target"),
-        )
-        .arg(Arg::with_name("input").required(false))
-        .get_matches();
+enum Command {
+    Print,
+    Decode {
+        name: String,
+        data: Option<String>,
+    },
+    Encode {
+        name: String,
+        values: Option<String>,
+        fill: u8,
+    },
+    Doc {
+        format: DocFormat,
+    },
+    Lsp,
+    Repl,
+    Fmt {
+        files: Vec<String>,
+        check: bool,
+    },
+    Strip {
+        files: Vec<String>,
+    },
+    Annotate {
+        files: Vec<String>,
+        update: bool,
+    },
+    Check {
+        files: Vec<String>,
+    },
+}
+
+fn args() -> (Target, Options, Option<String>, Command) {
+    let matches =
+        App::new("c-layout")
+            .arg(
+                Arg::with_name("print-targets")
+                    .long("print-targets")
+                    .help("Prints all available targets"),
+            )
+            .arg(
+                Arg::with_name("target")
+                    .long("target")
+                    .takes_value(true)
+                    .global(true)
+                    .help("Sets the target"),
+            )
+            .arg(
+                Arg::with_name("short-enums")
+                    .long("short-enums")
+                    .global(true)
+                    .help("Uses the smallest possible type for enums like -fshort-enums"),
+            )
+            .arg(Arg::with_name("input").required(false))
+            .subcommand(
+                SubCommand::with_name("decode")
+                    .about("Decodes a binary object through the layout of a type")
+                    .arg(
+                        Arg::with_name("input")
+                            .required(true)
+                            .help("The file containing the type declarations"),
+                    )
+                    .arg(
+                        Arg::with_name("type")
+                            .required(true)
+                            .help("The name of the type of the object"),
+                    )
+                    .arg(
+                        Arg::with_name("data")
+                            .required(false)
+                            .help("The file containing the object. Defaults to stdin"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("encode")
+                    .about("Writes the bytes of an object with the given field values to stdout")
+                    .arg(
+                        Arg::with_name("fill")
+                            .long("fill")
+                            .takes_value(true)
+                            .help("Sets the value of padding bytes. Defaults to 0"),
+                    )
+                    .arg(
+                        Arg::with_name("input")
+                            .required(true)
+                            .help("The file containing the type declarations"),
+                    )
+                    .arg(
+                        Arg::with_name("type")
+                            .required(true)
+                            .help("The name of the type of the object"),
+                    )
+                    .arg(Arg::with_name("values").required(false).help(
+                        "The TOML or JSON file containing the field values. Defaults to stdin",
+                    )),
+            )
+            .subcommand(
+                SubCommand::with_name("doc")
+                    .about("Prints the declarations with their doc comments and layouts")
+                    .arg(
+                        Arg::with_name("format")
+                            .long("format")
+                            .takes_value(true)
+                            .possible_values(&["markdown", "json"])
+                            .help("Sets the output format. Defaults to markdown"),
+                    )
+                    .arg(
+                        Arg::with_name("input")
+                            .required(false)
+                            .help("The file containing the declarations. Defaults to stdin"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("fmt")
+                    .about("Formats files in the canonical form")
+                    .arg(
+                        Arg::with_name("check")
+                            .long("check")
+                            .help("Checks that the files are formatted without modifying them"),
+                    )
+                    .arg(files_arg(
+                        "The files to format. Defaults to formatting stdin to stdout",
+                    )),
+            )
+            .subcommand(
+                SubCommand::with_name("strip")
+                    .about("Removes the computed layouts from files")
+                    .arg(files_arg(
+                        "The files to strip. Defaults to stripping stdin to stdout",
+                    )),
+            )
+            .subcommand(
+                SubCommand::with_name("annotate")
+                    .about("Annotates files with the computed layouts")
+                    .arg(
+                        Arg::with_name("update")
+                            .long("update")
+                            .help("Updates the files in place instead of printing them"),
+                    )
+                    .arg(files_arg(
+                        "The files to annotate. Defaults to annotating stdin to stdout",
+                    )),
+            )
+            .subcommand(
+                SubCommand::with_name("check")
+                    .about("Checks that the layouts in annotated files match the computed layouts")
+                    .arg(files_arg("The files to check. Defaults to stdin")),
+            )
+            .subcommand(
+                SubCommand::with_name("lsp")
+                    .about("Runs a language server that communicates via stdin and stdout"),
+            )
+            .subcommand(
+                SubCommand::with_name("repl")
+                    .about("Runs an interactive session that prints layouts as they are declared"),
+            )
+            .get_matches();
     if matches.is_present("print-targets") {
         for t in TARGETS {
             println!("{}", t.name());
@@ -37,15 +185,90 @@ fn args() -> (Target, Option<String>) {
                 process::exit(1);
             }
         },
-        Some(target) => match TARGETS.iter().copied().find(|t| t.name() == target) {
-            Some(t) => t,
-            _ => {
-                eprintln!("Invalid target {}.", target);
+        Some(target) => match target.parse() {
+            Ok(t) => t,
+            Err(e) => {
+                eprintln!("{}", e);
+                eprintln!("Print all available targets with the --print-targets flag.");
                 process::exit(1);
             }
         },
     };
-    (target, matches.value_of("input").map(|s| s.to_owned()))
+    let options = Options {
+        short_enums: matches.is_present("short-enums"),
+    };
+    let (input, command) = match matches.subcommand() {
+        ("decode", Some(m)) => (
+            m.value_of("input"),
+            Command::Decode {
+                name: m.value_of("type").unwrap().to_owned(),
+                data: m.value_of("data").map(|s| s.to_owned()),
+            },
+        ),
+        ("encode", Some(m)) => (
+            m.value_of("input"),
+            Command::Encode {
+                name: m.value_of("type").unwrap().to_owned(),
+                values: m.value_of("values").map(|s| s.to_owned()),
+                fill: match m.value_of("fill").map(parse_byte) {
+                    None => 0,
+                    Some(Some(b)) => b,
+                    Some(None) => {
+                        eprintln!("The argument of --fill must be a byte value");
+                        process::exit(1);
+                    }
+                },
+            },
+        ),
+        ("doc", Some(m)) => (
+            m.value_of("input"),
+            Command::Doc {
+                format: match m.value_of("format") {
+                    Some("json") => DocFormat::Json,
+                    _ => DocFormat::Markdown,
+                },
+            },
+        ),
+        ("lsp", Some(_)) => (None, Command::Lsp),
+        ("repl", Some(_)) => (None, Command::Repl),
+        ("fmt", Some(m)) => (
+            None,
+            Command::Fmt {
+                files: files(m),
+                check: m.is_present("check"),
+            },
+        ),
+        ("strip", Some(m)) => (None, Command::Strip { files: files(m) }),
+        ("annotate", Some(m)) => (
+            None,
+            Command::Annotate {
+                files: files(m),
+                update: m.is_present("update"),
+            },
+        ),
+        ("check", Some(m)) => (None, Command::Check { files: files(m) }),
+        _ => (matches.value_of("input"), Command::Print),
+    };
+    (target, options, input.map(|s| s.to_owned()), command)
+}
+
+fn files_arg(help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name("files").multiple(true).help(help)
+}
+
+fn files(m: &ArgMatches) -> Vec<String> {
+    m.values_of("files")
+        .into_iter()
+        .flatten()
+        .map(|s| s.to_owned())
+        .collect()
+}
+
+fn parse_byte(s: &str) -> Option<u8> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
 }
 
 fn main() {
@@ -56,21 +279,204 @@ fn main() {
 }
 
 fn main_() -> Result<()> {
-    let (target, file) = args();
+    let (target, options, file, command) = args();
+    match command {
+        Command::Lsp => return cly_impl::lsp::serve(stdin().lock(), stdout(), target, options),
+        Command::Repl => {
+            let history = env::var_os("HOME").map(|h| Path::new(&h).join(".cly_history"));
+            return cly_impl::repl::run(
+                stdin().lock(),
+                stdout(),
+                target,
+                options,
+                history.as_deref(),
+            );
+        }
+        Command::Fmt { files, check } => return format(files, check),
+        Command::Strip { files } => return strip(files),
+        Command::Annotate { files, update } => return annotate(files, update, target, options),
+        Command::Check { files } => return check(files, target, options),
+        _ => {}
+    }
+    let (project, layouts) = load(file.as_deref(), target, options)?;
+    let res = &project.declarations;
+    match command {
+        Command::Print => print!("{}", annotated(&project, &layouts)),
+        Command::Doc { format } => print!(
+            "{}",
+            cly_impl::document(
+                project.root_input(),
+                project.root_declarations(),
+                &layouts,
+                format
+            )
+        ),
+        Command::Decode { name, data } => {
+            let mut bytes = vec![];
+            match data {
+                Some(p) => File::open(&p)
+                    .with_context(|| anyhow!("cannot open {}", p))?
+                    .read_to_end(&mut bytes)
+                    .with_context(|| anyhow!("cannot read from {}", p))?,
+                _ => stdin()
+                    .read_to_end(&mut bytes)
+                    .context("cannot read from stdin")?,
+            };
+            for field in cly_impl::decode(res, &layouts, target, &name, &bytes)? {
+                println!("{}", field);
+            }
+        }
+        Command::Encode { name, values, fill } => {
+            let mut text = String::new();
+            match values {
+                Some(p) => File::open(&p)
+                    .with_context(|| anyhow!("cannot open {}", p))?
+                    .read_to_string(&mut text)
+                    .with_context(|| anyhow!("cannot read from {}", p))?,
+                _ => stdin()
+                    .read_to_string(&mut text)
+                    .context("cannot read from stdin")?,
+            };
+            let values =
+                cly_impl::parse_values(&text).context("Parsing the field values failed")?;
+            let bytes = cly_impl::encode(res, &layouts, target, &name, &values, fill)?;
+            stdout()
+                .write_all(&bytes)
+                .context("cannot write to stdout")?;
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+/// Loads a file or stdin and computes the layouts of its declarations.
+fn load(
+    file: Option<&str>,
+    target: Target,
+    options: Options,
+) -> Result<(Project, ConversionResult)> {
+    let mut project = match file {
+        Some(p) => cly_impl::load(Path::new(p)),
+        _ => {
+            let input = read_stdin()?;
+            let dir = env::current_dir().context("cannot determine the current directory")?;
+            cly_impl::load_str(&input, &dir)
+        }
+    }
+    .context("Parsing failed")?;
+    project
+        .evaluate_conditions(target, options)
+        .context("Evaluating conditions failed")?;
+    let (layouts, warnings) = cly_impl::compute_project_layouts(&project, target, options)
+        .context("Layout computation failed")?;
+    for w in &warnings {
+        eprintln!("Warning: {}", w.describe_in_project(&project));
+    }
+    Ok((project, layouts))
+}
+
+/// Returns the root file of `project` annotated with `layouts`.
+fn annotated(project: &Project, layouts: &ConversionResult) -> String {
+    let res = cly_impl::enhance_declarations(project.root_declarations(), layouts);
+    cly_impl::printer(project.root_input(), &res).to_string()
+}
+
+fn read_stdin() -> Result<String> {
     let mut input = String::new();
-    match file {
-        Some(p) => File::open(&p)
-            .with_context(|| anyhow!("cannot open {}", p))?
-            .read_to_string(&mut input)
-            .with_context(|| anyhow!("cannot read from {}", p))?,
-        _ => stdin()
-            .read_to_string(&mut input)
-            .context("cannot read from stdin")?,
+    stdin()
+        .read_to_string(&mut input)
+        .context("cannot read from stdin")?;
+    Ok(input)
+}
+
+/// Replaces the contents of `files` with the result of `f` or, if there are no files,
+/// writes the result of `f` for stdin to stdout.
+fn rewrite(files: &[String], f: impl Fn(&str) -> Result<String>) -> Result<()> {
+    if files.is_empty() {
+        print!("{}", f(&read_stdin()?)?);
+        return Ok(());
+    }
+    for p in files {
+        let input = fs::read_to_string(p).with_context(|| anyhow!("cannot read {}", p))?;
+        let output = f(&input).with_context(|| anyhow!("Processing {} failed", p))?;
+        if output != input {
+            fs::write(p, output).with_context(|| anyhow!("cannot write {}", p))?;
+        }
+    }
+    Ok(())
+}
+
+fn format(files: Vec<String>, check: bool) -> Result<()> {
+    if !check {
+        return rewrite(&files, cly_impl::format);
+    }
+    let inputs = match files.is_empty() {
+        true => vec![("stdin".to_string(), read_stdin()?)],
+        false => files
+            .into_iter()
+            .map(|p| match fs::read_to_string(&p) {
+                Ok(input) => Ok((p, input)),
+                Err(e) => Err(anyhow!(e).context(anyhow!("cannot read {}", p))),
+            })
+            .collect::<Result<_>>()?,
     };
-    let res = cly_impl::parse(&input).context("Parsing failed")?;
-    let layouts =
-        cly_impl::compute_layouts(&input, &res, target).context("Layout computation failed")?;
-    let res = cly_impl::enhance_declarations(&res, &layouts);
-    print!("{}", cly_impl::printer(&input, &res));
+    let mut unformatted = false;
+    for (p, input) in inputs {
+        let output =
+            cly_impl::format(&input).with_context(|| anyhow!("Processing {} failed", p))?;
+        if output != input {
+            eprintln!("{} is not formatted", p);
+            unformatted = true;
+        }
+    }
+    if unformatted {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn strip(files: Vec<String>) -> Result<()> {
+    rewrite(&files, cly_impl::strip)
+}
+
+fn annotate(files: Vec<String>, update: bool, target: Target, options: Options) -> Result<()> {
+    if files.is_empty() {
+        let (project, layouts) = load(None, target, options)?;
+        print!("{}", annotated(&project, &layouts));
+        return Ok(());
+    }
+    for p in &files {
+        let (project, layouts) =
+            load(Some(p), target, options).with_context(|| anyhow!("Processing {} failed", p))?;
+        let output = annotated(&project, &layouts);
+        if !update {
+            print!("{}", output);
+        } else if output != project.root_input() {
+            fs::write(p, output).with_context(|| anyhow!("cannot write {}", p))?;
+        }
+    }
+    Ok(())
+}
+
+fn check(files: Vec<String>, target: Target, options: Options) -> Result<()> {
+    let files = match files.is_empty() {
+        true => vec![None],
+        false => files.iter().map(|p| Some(&**p)).collect(),
+    };
+    let mut stale = false;
+    for p in files {
+        let name = p.unwrap_or("stdin");
+        let (project, layouts) =
+            load(p, target, options).with_context(|| anyhow!("Processing {} failed", name))?;
+        for line in cly_impl::find_stale_layouts(&project, &layouts) {
+            eprintln!("{}:{}: The layouts are stale", name, line.line);
+            eprintln!("    expected: {}", line.expected.trim());
+            eprintln!("       found: {}", line.actual.trim());
+            stale = true;
+        }
+    }
+    if stale {
+        process::exit(1);
+    }
     Ok(())
 }
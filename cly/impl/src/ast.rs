@@ -1,12 +1,15 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
-use repc_impl::layout::{BuiltinType, FieldLayout, RecordKind, TypeLayout};
+use repc_impl::layout::{
+    BuiltinType, EnumUnderlyingType, FieldLayout, RecordKind, RustEnumRepr, RustStructRepr,
+    TypeLayout,
+};
 
 pub struct State {
     pub next_id: usize,
 }
 
 /// A type declaration.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub struct Span(pub usize, pub usize);
 
 /// A type declaration.
@@ -14,13 +17,44 @@ pub struct Span(pub usize, pub usize);
 pub struct Declaration {
     pub name: String,
     pub span: Span,
+    /// The doc comments before the declaration, `/// text`, joined by newlines.
+    pub doc: Option<String>,
+    /// The start of the declaration including its condition.
+    pub lo: usize,
+    /// The parameters of a generic type declaration, `Name(T, N) = ...`.
+    pub params: Vec<String>,
+    /// The condition under which the declaration exists, `@if(condition)`.
+    pub condition: Option<Box<Expr>>,
     pub ty: DeclarationType,
 }
 
+/// An import of the declarations of another file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Import {
+    /// The path of the file relative to the directory of the importing file.
+    pub path: String,
+    /// The namespace of the imported declarations.
+    ///
+    /// If this is `None`, the declarations can be referenced without a prefix.
+    pub namespace: Option<String>,
+    pub span: Span,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum DeclarationType {
     Type(Type),
     Const(Expr),
+    /// An assertion, `assert expr, "message"`.
+    ///
+    /// The name of an assertion is empty.
+    Assert(Assertion),
+}
+
+/// An assertion that is checked after the layouts have been computed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Assertion {
+    pub expr: Expr,
+    pub message: Option<String>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -28,6 +62,7 @@ pub enum Annotation {
     PragmaPack(Box<Expr>),
     AttrPacked,
     Aligned(Option<Box<Expr>>),
+    Alignas(Box<Expr>),
 }
 
 /// A type.
@@ -35,6 +70,7 @@ pub enum Annotation {
 pub struct Type {
     pub id: usize,
     pub lo: usize,
+    pub hi: usize,
     pub layout: Option<TypeLayout>,
     pub layout_hi: usize,
     pub annotations: Vec<Annotation>,
@@ -55,7 +91,13 @@ pub struct RecordField {
     pub pos: Option<usize>,
     pub lo: usize,
     pub layout: Option<FieldLayout>,
+    /// The signedness of a bit-field, `signedness: signed` in the layout.
+    pub signed: Option<bool>,
     pub layout_hi: usize,
+    /// The doc comments before the field, `/// text`, joined by newlines.
+    pub doc: Option<String>,
+    /// The condition under which the field exists, `@if(condition)`.
+    pub condition: Option<Box<Expr>>,
     pub annotations: Vec<Annotation>,
     pub name: Option<String>,
     pub bit_width: Option<Box<Expr>>,
@@ -70,8 +112,53 @@ pub enum TypeVariant {
     Typedef(Box<Type>),
     Array(Array),
     Opaque(OpaqueTypeLayout),
-    Name(String, Span),
-    Enum(Vec<Expr>),
+    /// A reference to a declared type, `Name`, or to an instantiation of a generic type,
+    /// `Name(int, 16)`. The span covers the arguments.
+    Name(String, Vec<TypeArgument>, Span),
+    Enum(Enum),
+    RustStruct(RustStruct),
+    RustEnum(RustEnum),
+}
+
+/// An argument of an instantiation of a generic type.
+///
+/// A plain name is always parsed as a type. Whether it refers to a type or to a constant
+/// is decided when the instantiation is evaluated.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TypeArgument {
+    Type(Type),
+    Expr(Expr),
+}
+
+/// An enum.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Enum {
+    pub fixed_underlying_type: Option<BuiltinType>,
+    pub variants: Vec<Expr>,
+    pub underlying_type: Option<EnumUnderlyingType>,
+}
+
+/// A Rust struct.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RustStruct {
+    pub repr: RustStructRepr,
+    pub fields: Vec<RecordField>,
+}
+
+/// A Rust enum.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RustEnum {
+    pub repr: RustEnumRepr,
+    pub variants: Vec<RustEnumVariant>,
+}
+
+/// A variant of a Rust enum.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RustEnumVariant {
+    pub name: String,
+    pub span: Span,
+    pub fields: Vec<RecordField>,
+    pub discriminant: Option<Box<Expr>>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -106,23 +193,48 @@ pub enum ExprType {
     Builtin(BuiltinExpr),
     Name(String),
     Offsetof(OffsetofType, Type, Vec<Index>),
+    /// Whether a property of the target matches a string, e.g. `target_os("linux")`.
+    Target(TargetProperty, String),
+    /// `cond ? then : else`
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum BuiltinExpr {
     BitsPerByte,
+    TargetPointerWidth,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TargetProperty {
+    /// The name of the target matched against a glob pattern.
+    Name,
+    /// The system compiler of the target.
+    Compiler,
+    /// The operating system of the target.
+    Os,
+    /// The byte order of the target.
+    Endian,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum UnaryExprType {
     Neg,
     Not,
+    BitNot,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum TypeExprType {
     Sizeof,
     SizeofBits,
+    /// The field alignment in bytes.
+    Alignof,
+    AlignofBits,
+    PointerAlignof,
+    PointerAlignofBits,
+    RequiredAlignof,
+    RequiredAlignofBits,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -140,6 +252,17 @@ pub enum BinaryExprType {
     Le,
     Gt,
     Ge,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    /// `min(l, r)`
+    Min,
+    /// `max(l, r)`
+    Max,
+    /// `align_up(l, r)`, the smallest multiple of `r` that is not less than `l`
+    AlignUp,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -1,18 +1,29 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 use crate::ast::{
     BinaryExprType, BuiltinExpr, DeclarationType, ExprType, Index, IndexType, OffsetofType, Span,
-    TypeExprType, UnaryExprType,
+    TargetProperty, TypeExprType, UnaryExprType,
 };
-use crate::{ast, to_span, S};
-use anyhow::{anyhow, Result};
+use crate::parser::{set_field_positions, set_variant_field_positions};
+use crate::result::{Label, SourceError};
+use crate::{ast, label, source_error, to_span, Project, Source, S};
+use anyhow::Result;
+use repc_impl::builder::common::bit_field_is_signed;
+use repc_impl::builder::{collect_warnings, compute_layout, compute_layout_with_options, Options};
+use repc_impl::flatten::{resolve_field_path, FieldPathElement};
 use repc_impl::layout::{
-    Annotation, Array, FieldLayout, Layout, Record, RecordField, Type, TypeLayout, TypeVariant,
+    Annotation, Array, Enum, FieldLayout, Layout, Record, RecordField, RustEnum, RustEnumVariant,
+    RustStruct, Type, TypeLayout, TypeVariant,
 };
-use repc_impl::target::Target;
+use repc_impl::result::ErrorType;
+use repc_impl::target::{system_compiler, Compiler, Endianness, Target, TARGETS};
+use repc_impl::typedefs::{c_typedef, C_TYPEDEFS};
 use repc_impl::util::BITS_PER_BYTE;
+use repc_impl::warning::{PathElement, WarningType};
 use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
+use std::fmt::Display;
 use std::ops::Not;
+use std::rc::Rc;
 
 #[derive(Eq, PartialEq)]
 pub struct ConversionResult {
@@ -20,6 +31,28 @@ pub struct ConversionResult {
     pub consts: HashMap<String, i128>,
 }
 
+/// A warning produced while computing the layout of a type.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Warning {
+    /// The location of the type the warning applies to.
+    pub span: Span,
+    /// The type of the warning.
+    pub kind: WarningType,
+}
+
+impl Warning {
+    /// Returns a human-readable description of the warning including its location in `input`.
+    pub fn describe(&self, input: &str) -> String {
+        format!("At {}: {}", to_span(input, self.span), self.kind)
+    }
+
+    /// Returns a human-readable description of the warning including its location in the
+    /// files of `project`.
+    pub fn describe_in_project(&self, project: &Project) -> String {
+        format!("At {}: {}", to_span(project, self.span), self.kind)
+    }
+}
+
 pub fn extract_layouts(input: &str, d: &[ast::Declaration]) -> Result<ConversionResult> {
     struct Converter<'a>(&'a str);
     impl<'a> Convert for Converter<'a> {
@@ -32,9 +65,10 @@ pub fn extract_layouts(input: &str, d: &[ast::Declaration]) -> Result<Conversion
 
         fn extract_type(&self, t: &ast::Type) -> Result<Self::Src> {
             match t.layout {
-                None => Err(anyhow!(
-                    "At {}: Missing type layout",
-                    to_span(self.0, Span(t.lo, t.lo))
+                None => Err(source_error(
+                    self.0,
+                    Span(t.lo, t.lo),
+                    "Missing type layout",
                 )),
                 Some(l) => Ok(l),
             }
@@ -42,13 +76,21 @@ pub fn extract_layouts(input: &str, d: &[ast::Declaration]) -> Result<Conversion
 
         fn extract_field(&self, t: &ast::RecordField, _pos: usize) -> Result<FieldLayout> {
             match t.layout {
-                None => Err(anyhow!(
-                    "At {}: Missing field layout",
-                    to_span(self.0, Span(t.lo, t.lo))
+                None => Err(source_error(
+                    self.0,
+                    Span(t.lo, t.lo),
+                    "Missing field layout",
                 )),
                 Some(l) => Ok(l),
             }
         }
+
+        fn predefined_type(&self, name: &str, t: &ast::Type) -> Result<Option<TypeLayout>> {
+            match C_TYPEDEFS.contains(&name) {
+                true => Ok(Some(self.extract_type(t)?)),
+                false => Ok(None),
+            }
+        }
     }
     Computer::new(input, d, Converter(input))?.compute_layouts()
 }
@@ -58,23 +100,267 @@ pub fn compute_layouts(
     d: &[ast::Declaration],
     target: Target,
 ) -> Result<ConversionResult> {
-    struct Converter(Target);
-    impl Convert for Converter {
-        type Src = ();
+    Ok(compute_layouts_with_warnings(input, d, target, Options::default())?.0)
+}
 
-        fn convert(&self, ty: Type<Self::Src>) -> Result<Type<TypeLayout>> {
-            Ok(repc_impl::builder::compute_layout(self.0, &ty)?)
+pub fn compute_layouts_with_warnings(
+    input: &str,
+    d: &[ast::Declaration],
+    target: Target,
+    options: Options,
+) -> Result<(ConversionResult, Vec<Warning>)> {
+    compute_layouts_in_source(input.into(), d, target, options)
+}
+
+/// Computes the layouts of the declarations of all files in `project`.
+pub fn compute_project_layouts(
+    project: &Project,
+    target: Target,
+    options: Options,
+) -> Result<(ConversionResult, Vec<Warning>)> {
+    compute_layouts_in_source(project.into(), &project.declarations, target, options)
+}
+
+fn compute_layouts_in_source(
+    input: Source,
+    d: &[ast::Declaration],
+    target: Target,
+    options: Options,
+) -> Result<(ConversionResult, Vec<Warning>)> {
+    Computer::with_source(input, d, TargetConverter(target, options))?
+        .compute_layouts_with_warnings()
+}
+
+struct TargetConverter(Target, Options);
+
+impl Convert for TargetConverter {
+    type Src = ();
+
+    fn convert(&self, ty: Type<Self::Src>) -> Result<Type<TypeLayout>> {
+        Ok(compute_layout_with_options(self.0, &ty, self.1)?)
+    }
+
+    fn convert_with_warnings(
+        &self,
+        ty: Type<Self::Src>,
+    ) -> Result<(Type<TypeLayout>, Vec<repc_impl::warning::Warning>)> {
+        let ty = self.convert(ty)?;
+        let warnings = collect_warnings(self.0, &ty);
+        Ok((ty, warnings))
+    }
+
+    fn extract_type(&self, _: &ast::Type) -> Result<Self::Src> {
+        Ok(())
+    }
+
+    fn extract_field(&self, _: &ast::RecordField, _: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn predefined_type(&self, name: &str, _: &ast::Type) -> Result<Option<TypeLayout>> {
+        match c_typedef(self.0, name) {
+            Some(ty) => Ok(Some(compute_layout(self.0, &ty)?.layout)),
+            None => Ok(None),
+        }
+    }
+
+    fn target(&self) -> Option<Target> {
+        Some(self.0)
+    }
+}
+
+/// Evaluates the `@if` conditions of the declarations and fields in `d` for `target`.
+///
+/// Declarations and fields whose conditions are false are removed. The conditions of the
+/// remaining declarations and fields are removed. The layouts of declarations with
+/// conditions cannot be computed before their conditions have been evaluated.
+///
+/// The layouts used in the conditions are computed with `options`.
+pub fn evaluate_conditions(
+    input: &str,
+    d: &[ast::Declaration],
+    target: Target,
+    options: Options,
+) -> Result<Vec<ast::Declaration>> {
+    evaluate_conditions_in_source(input.into(), d, target, options)
+}
+
+pub(crate) fn evaluate_conditions_in_source(
+    input: Source,
+    d: &[ast::Declaration],
+    target: Target,
+    options: Options,
+) -> Result<Vec<ast::Declaration>> {
+    // Conditions can refer to declarations that do not have conditions themselves.
+    let unconditional: Vec<_> = d
+        .iter()
+        .filter(|d| d.condition.is_none())
+        .cloned()
+        .collect();
+    let converter = TargetConverter(target, options);
+    let mut ce = ConditionEvaluator {
+        computer: Computer::with_source(input, &unconditional, converter)?,
+        false_conditions: HashSet::new(),
+    };
+    for d in d {
+        ce.evaluate(&d.condition)?;
+        match &d.ty {
+            DeclarationType::Type(t) => ce.evaluate_type(t)?,
+            DeclarationType::Const(e) => ce.evaluate_expr(e)?,
+            DeclarationType::Assert(a) => ce.evaluate_expr(&a.expr)?,
+        }
+    }
+    let mut res = vec![];
+    for d in d {
+        if ce.holds(&d.condition) {
+            let mut d = d.clone();
+            d.condition = None;
+            match &mut d.ty {
+                DeclarationType::Type(t) => ce.retain_type(t),
+                DeclarationType::Const(e) => ce.retain_expr(e),
+                DeclarationType::Assert(a) => ce.retain_expr(&mut a.expr),
+            }
+            res.push(d);
+        }
+    }
+    Ok(res)
+}
+
+struct ConditionEvaluator<'a> {
+    computer: Computer<'a, TargetConverter>,
+    /// The spans of the conditions that are false.
+    false_conditions: HashSet<Span>,
+}
+
+impl<'a> ConditionEvaluator<'a> {
+    fn evaluate(&mut self, condition: &'a Option<Box<ast::Expr>>) -> Result<()> {
+        if let Some(e) = condition {
+            if self.computer.eval_expr(e)? == 0 {
+                self.false_conditions.insert(e.span);
+            }
+        }
+        Ok(())
+    }
+
+    fn evaluate_type(&mut self, t: &'a ast::Type) -> Result<()> {
+        match &t.variant {
+            ast::TypeVariant::Record(r) => self.evaluate_fields(&r.fields),
+            ast::TypeVariant::RustStruct(s) => self.evaluate_fields(&s.fields),
+            ast::TypeVariant::RustEnum(e) => {
+                for v in &e.variants {
+                    self.evaluate_fields(&v.fields)?;
+                }
+                Ok(())
+            }
+            ast::TypeVariant::Typedef(t) => self.evaluate_type(t),
+            ast::TypeVariant::Array(a) => self.evaluate_type(&a.element_type),
+            ast::TypeVariant::Name(_, args, _) => {
+                for a in args {
+                    match a {
+                        ast::TypeArgument::Type(t) => self.evaluate_type(t)?,
+                        ast::TypeArgument::Expr(e) => self.evaluate_expr(e)?,
+                    }
+                }
+                Ok(())
+            }
+            ast::TypeVariant::Builtin(_)
+            | ast::TypeVariant::Opaque(_)
+            | ast::TypeVariant::Enum(_) => Ok(()),
+        }
+    }
+
+    fn evaluate_fields(&mut self, fields: &'a [ast::RecordField]) -> Result<()> {
+        for f in fields {
+            self.evaluate(&f.condition)?;
+            self.evaluate_type(&f.ty)?;
+        }
+        Ok(())
+    }
+
+    fn evaluate_expr(&mut self, e: &'a ast::Expr) -> Result<()> {
+        match &e.ty {
+            ExprType::Unary(_, e) => self.evaluate_expr(e),
+            ExprType::Binary(_, l, r) => {
+                self.evaluate_expr(l)?;
+                self.evaluate_expr(r)
+            }
+            ExprType::Conditional(c, t, e) => {
+                self.evaluate_expr(c)?;
+                self.evaluate_expr(t)?;
+                self.evaluate_expr(e)
+            }
+            ExprType::TypeExpr(_, t) => self.evaluate_type(t),
+            ExprType::Offsetof(_, t, _) => self.evaluate_type(t),
+            ExprType::Lit(_) | ExprType::Builtin(_) | ExprType::Name(_) | ExprType::Target(..) => {
+                Ok(())
+            }
+        }
+    }
+
+    fn holds(&self, condition: &Option<Box<ast::Expr>>) -> bool {
+        match condition {
+            Some(e) => !self.false_conditions.contains(&e.span),
+            None => true,
+        }
+    }
+
+    fn retain_type(&self, t: &mut ast::Type) {
+        match &mut t.variant {
+            ast::TypeVariant::Record(r) => {
+                self.retain_fields(&mut r.fields);
+                set_field_positions(&mut r.fields);
+            }
+            ast::TypeVariant::RustStruct(s) => {
+                self.retain_fields(&mut s.fields);
+                set_field_positions(&mut s.fields);
+            }
+            ast::TypeVariant::RustEnum(e) => {
+                for v in &mut e.variants {
+                    self.retain_fields(&mut v.fields);
+                }
+                set_variant_field_positions(&mut e.variants);
+            }
+            ast::TypeVariant::Typedef(t) => self.retain_type(t),
+            ast::TypeVariant::Array(a) => self.retain_type(&mut a.element_type),
+            ast::TypeVariant::Name(_, args, _) => {
+                for a in args {
+                    match a {
+                        ast::TypeArgument::Type(t) => self.retain_type(t),
+                        ast::TypeArgument::Expr(e) => self.retain_expr(e),
+                    }
+                }
+            }
+            ast::TypeVariant::Builtin(_)
+            | ast::TypeVariant::Opaque(_)
+            | ast::TypeVariant::Enum(_) => {}
         }
+    }
 
-        fn extract_type(&self, _: &ast::Type) -> Result<Self::Src> {
-            Ok(())
+    fn retain_fields(&self, fields: &mut Vec<ast::RecordField>) {
+        fields.retain(|f| self.holds(&f.condition));
+        for f in fields {
+            f.condition = None;
+            self.retain_type(&mut f.ty);
         }
+    }
 
-        fn extract_field(&self, _: &ast::RecordField, _: usize) -> Result<()> {
-            Ok(())
+    fn retain_expr(&self, e: &mut ast::Expr) {
+        match &mut e.ty {
+            ExprType::Unary(_, e) => self.retain_expr(e),
+            ExprType::Binary(_, l, r) => {
+                self.retain_expr(l);
+                self.retain_expr(r);
+            }
+            ExprType::Conditional(c, t, e) => {
+                self.retain_expr(c);
+                self.retain_expr(t);
+                self.retain_expr(e);
+            }
+            ExprType::TypeExpr(_, t) => self.retain_type(t),
+            ExprType::Offsetof(_, t, _) => self.retain_type(t),
+            ExprType::Lit(_) | ExprType::Builtin(_) | ExprType::Name(_) | ExprType::Target(..) => {}
         }
     }
-    Computer::new(input, d, Converter(target))?.compute_layouts()
 }
 
 pub trait Convert {
@@ -82,34 +368,101 @@ pub trait Convert {
     const USE_EVALUATED_EXPR: bool = false;
 
     fn convert(&self, ty: Type<Self::Src>) -> Result<Type<TypeLayout>>;
+    fn convert_with_warnings(
+        &self,
+        ty: Type<Self::Src>,
+    ) -> Result<(Type<TypeLayout>, Vec<repc_impl::warning::Warning>)> {
+        Ok((self.convert(ty)?, vec![]))
+    }
     fn extract_type(&self, ty: &ast::Type) -> Result<<Self::Src as Layout>::TypeLayout>;
     fn extract_field(
         &self,
         field: &ast::RecordField,
         pos: usize,
     ) -> Result<<Self::Src as Layout>::FieldLayout>;
+    /// Returns the signedness of the bit-field `field`, if it is known.
+    fn extract_signedness(&self, field: &ast::RecordField, pos: usize) -> Result<Option<bool>> {
+        let _ = pos;
+        Ok(field.signed)
+    }
+    /// Returns the layout of a predefined C typedef that is referenced by `ty`.
+    fn predefined_type(&self, name: &str, ty: &ast::Type) -> Result<Option<TypeLayout>> {
+        let _ = (name, ty);
+        Ok(None)
+    }
+    /// Returns the target whose properties are used in expressions.
+    fn target(&self) -> Option<Target> {
+        None
+    }
 }
 
+/// The value of a parameter of a generic declaration.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+enum Argument {
+    Type(TypeLayout),
+    Const(i128),
+}
+
+type Arguments<'a> = HashMap<&'a str, Argument>;
+
+/// The scope in which the names of the type of an `offsetof` expression are resolved.
+#[derive(Clone, Default)]
+struct OffsetofScope<'a> {
+    arguments: Arguments<'a>,
+    /// The types passed for type parameters and the scopes in which they were written.
+    types: HashMap<&'a str, (&'a ast::Type, Rc<OffsetofScope<'a>>)>,
+}
+
+/// The maximum number of nested instantiations of generic declarations.
+const MAX_INSTANTIATION_DEPTH: usize = 256;
+
+/// The maximum number of distinct instantiations listed in the trace of an error.
+const MAX_INSTANTIATION_TRACE: usize = 10;
+
 pub struct Computer<'a, C> {
-    input: &'a str,
+    input: Source<'a>,
     d: &'a [ast::Declaration],
     declarations: HashMap<&'a str, &'a ast::Declaration>,
     type_layouts: HashMap<String, Type<TypeLayout>>,
     constants: HashMap<String, i128>,
     converting: HashSet<&'a str>,
+    /// The arguments of the declarations that are currently being computed. Names are
+    /// resolved with the last element.
+    arguments: Vec<Arguments<'a>>,
+    /// The layouts of all instantiations of generic declarations.
+    instances: HashMap<(&'a str, Vec<Argument>), Type<TypeLayout>>,
+    /// The number of instantiations that are currently being computed.
+    instantiation_depth: usize,
+    /// The sites of the instantiations an error is propagated through, innermost first.
+    instantiation_trace: Vec<Span>,
+    warnings: Vec<Warning>,
     converter: C,
 }
 
 impl<'a, C: Convert> Computer<'a, C> {
     pub fn new(input: &'a str, d: &'a [ast::Declaration], converter: C) -> Result<Self> {
+        Self::with_source(input.into(), d, converter)
+    }
+
+    fn with_source(input: Source<'a>, d: &'a [ast::Declaration], converter: C) -> Result<Self> {
         let mut declarations = HashMap::new();
         for d in d {
+            if let Some(c) = &d.condition {
+                return Err(unevaluated_condition(input, c));
+            }
+            // Assertions cannot be referenced.
+            if let DeclarationType::Assert(_) = d.ty {
+                continue;
+            }
             if let Some(old) = declarations.insert(&*d.name, d) {
-                return Err(anyhow!(
-                    "At {}: Type {} is declared multiple times. Previous declaration at {}",
-                    to_span(input, d.span),
-                    d.name,
-                    to_span(input, old.span)
+                return Err(source_error(
+                    input,
+                    d.span,
+                    format!(
+                        "Type {} is declared multiple times. Previous declaration at {}",
+                        d.name,
+                        to_span(input, old.span)
+                    ),
                 ));
             }
         }
@@ -120,54 +473,125 @@ impl<'a, C: Convert> Computer<'a, C> {
             converter,
             type_layouts: Default::default(),
             converting: Default::default(),
+            arguments: Default::default(),
+            instances: Default::default(),
+            instantiation_depth: 0,
+            instantiation_trace: Default::default(),
             constants: Default::default(),
+            warnings: Default::default(),
         })
     }
 
-    pub fn compute_layouts(mut self) -> Result<ConversionResult> {
+    pub fn compute_layouts(self) -> Result<ConversionResult> {
+        Ok(self.compute_layouts_with_warnings()?.0)
+    }
+
+    pub fn compute_layouts_with_warnings(mut self) -> Result<(ConversionResult, Vec<Warning>)> {
         for d in self.d {
             match d.ty {
+                // Generic declarations only have a layout when they are instantiated.
+                DeclarationType::Type(_) if !d.params.is_empty() => {}
                 DeclarationType::Type(_) => {
                     self.compute_decl_ty_layout(d, d.span)?;
                 }
                 DeclarationType::Const(_) => {
                     self.compute_decl_const(d, d.span)?;
                 }
+                DeclarationType::Assert(_) => {}
             }
         }
-        Ok(ConversionResult {
-            types: self.type_layouts,
-            consts: self.constants,
-        })
+        let mut failures = vec![];
+        for d in self.d {
+            if let DeclarationType::Assert(a) = &d.ty {
+                if self.eval_expr(&a.expr)? == 0 {
+                    failures.push(self.describe_failed_assertion(a)?);
+                }
+            }
+        }
+        if !failures.is_empty() {
+            let labels = failures;
+            return Err(SourceError { msg: None, labels }.into());
+        }
+        Ok((
+            ConversionResult {
+                types: self.type_layouts,
+                consts: self.constants,
+            },
+            self.warnings,
+        ))
     }
 
-    fn span(&self, span: Span) -> S {
+    fn span(&self, span: Span) -> S<'a> {
         to_span(self.input, span)
     }
 
+    fn error(&self, span: Span, msg: impl Display) -> anyhow::Error {
+        source_error(self.input, span, msg)
+    }
+
+    /// Describes a failed assertion together with the values of its sub-expressions.
+    fn describe_failed_assertion(&mut self, a: &'a ast::Assertion) -> Result<Label> {
+        let mut res = "Assertion failed".to_string();
+        if let Some(msg) = &a.message {
+            res.push_str(": ");
+            res.push_str(msg);
+        }
+        let mut values = vec![];
+        self.collect_sub_values(&a.expr, &mut values)?;
+        for (span, value) in values {
+            let text = &self.input.text()[span.0..span.1];
+            res.push_str(&format!("\n    {} = {}", text, value));
+        }
+        Ok(label(self.input, a.expr.span, res))
+    }
+
+    fn collect_sub_values(&mut self, e: &'a ast::Expr, res: &mut Vec<(Span, i128)>) -> Result<()> {
+        let children: Vec<&ast::Expr> = match &e.ty {
+            ExprType::Unary(_, v) => vec![v],
+            ExprType::Binary(_, l, r) => vec![l, r],
+            // Only the branch that was taken is evaluated.
+            ExprType::Conditional(c, t, e) => match self.eval_expr(c)? {
+                0 => vec![c, e],
+                _ => vec![c, t],
+            },
+            _ => vec![],
+        };
+        for c in children {
+            if let ExprType::Lit(_) = c.ty {
+                continue;
+            }
+            let value = self.eval_expr(c)?;
+            if res.iter().all(|v| v.0 != c.span) {
+                res.push((c.span, value));
+            }
+            self.collect_sub_values(c, res)?;
+        }
+        Ok(())
+    }
+
     fn compute_decl_const(&mut self, d: &'a ast::Declaration, site: Span) -> Result<i128> {
         if let Some(value) = self.constants.get(&d.name) {
             return Ok(*value);
         }
         if self.converting.insert(&d.name).not() {
-            return Err(anyhow!(
-                "At {}: The value of {} depends on itself",
-                self.span(d.span),
-                d.name
-            ));
+            return Err(self.error(d.span, format!("The value of {} depends on itself", d.name)));
         }
         let e = match &d.ty {
-            DeclarationType::Type(_) => {
-                return Err(anyhow!(
-                    "At {}: {} is declared as a type but must be a constant at {}",
-                    self.span(d.span),
-                    d.name,
-                    self.span(site),
+            DeclarationType::Type(_) | DeclarationType::Assert(_) => {
+                return Err(self.error(
+                    d.span,
+                    format!(
+                        "{} is declared as a type but must be a constant at {}",
+                        d.name,
+                        self.span(site)
+                    ),
                 ));
             }
             DeclarationType::Const(e) => e,
         };
+        self.arguments.push(Arguments::new());
         let res = self.eval_expr(e);
+        self.arguments.pop();
         self.converting.remove(&*d.name);
         let res = res?;
         self.constants.insert(d.name.clone(), res);
@@ -184,23 +608,26 @@ impl<'a, C: Convert> Computer<'a, C> {
         }
         let ty = match &d.ty {
             DeclarationType::Type(ty) => ty,
-            DeclarationType::Const(_) => {
-                return Err(anyhow!(
-                    "At {}: {} is declared as a constant but must be a type at {}",
-                    self.span(d.span),
-                    d.name,
-                    self.span(site),
+            DeclarationType::Const(_) | DeclarationType::Assert(_) => {
+                return Err(self.error(
+                    d.span,
+                    format!(
+                        "{} is declared as a constant but must be a type at {}",
+                        d.name,
+                        self.span(site)
+                    ),
                 ));
             }
         };
         if self.converting.insert(&d.name).not() {
-            return Err(anyhow!(
-                "At {}: The layout of {} depends on itself",
-                self.span(d.span),
-                d.name
+            return Err(self.error(
+                d.span,
+                format!("The layout of {} depends on itself", d.name),
             ));
         }
+        self.arguments.push(Arguments::new());
         let res = self.compute_type_layout(ty);
+        self.arguments.pop();
         self.converting.remove(&*d.name);
         let res = res?;
         let layout = res.layout;
@@ -208,9 +635,210 @@ impl<'a, C: Convert> Computer<'a, C> {
         Ok(layout)
     }
 
+    /// Returns the argument bound to the parameter `name`, if any.
+    fn argument(&self, name: &str) -> Option<Argument> {
+        self.arguments.last().and_then(|a| a.get(name)).copied()
+    }
+
+    /// Computes the layout of the instantiation of the generic declaration `d` with `args`.
+    ///
+    /// Returns the layout and the arguments bound to the parameters of `d`.
+    fn instantiate(
+        &mut self,
+        d: &'a ast::Declaration,
+        args: &'a [ast::TypeArgument],
+        site: Span,
+    ) -> Result<(Type<TypeLayout>, Arguments<'a>)> {
+        let ty = match &d.ty {
+            DeclarationType::Type(ty) => ty,
+            DeclarationType::Const(_) | DeclarationType::Assert(_) => {
+                return Err(self.error(
+                    d.span,
+                    format!(
+                        "{} is declared as a constant but must be a type at {}",
+                        d.name,
+                        self.span(site)
+                    ),
+                ));
+            }
+        };
+        if d.params.len() != args.len() {
+            return Err(self.error(
+                site,
+                format!(
+                    "{} takes {} arguments but {} were given",
+                    d.name,
+                    d.params.len(),
+                    args.len()
+                ),
+            ));
+        }
+        let mut values = vec![];
+        for a in args {
+            values.push(self.eval_type_argument(a)?);
+        }
+        let bound: Arguments = d.params.iter().map(|p| &**p).zip(values.clone()).collect();
+        let key = (&*d.name, values);
+        if let Some(ty) = self.instances.get(&key) {
+            return Ok((ty.clone(), bound));
+        }
+        if self.arguments.len() >= MAX_INSTANTIATION_DEPTH {
+            return Err(self.error(
+                site,
+                format!("The instantiations of {} are nested too deeply", d.name),
+            ));
+        }
+        // An error that is being propagated does not start new instantiations. Any
+        // remaining sites belong to an error that was handled.
+        self.instantiation_trace.clear();
+        self.arguments.push(bound.clone());
+        self.instantiation_depth += 1;
+        let res = self.compute_type_layout(ty);
+        self.instantiation_depth -= 1;
+        self.arguments.pop();
+        let res = res.map_err(|e| self.instantiation_error(e, site))?;
+        self.instances.insert(key, res.clone());
+        Ok((res, bound))
+    }
+
+    /// Adds the instantiation at `site` to the trace of `e`.
+    ///
+    /// The trace is added to the labels of the error once the error leaves the outermost
+    /// instantiation. Repeated instantiations at the same site, e.g. in runaway recursion,
+    /// are listed once.
+    fn instantiation_error(&mut self, e: anyhow::Error, site: Span) -> anyhow::Error {
+        self.instantiation_trace.push(site);
+        if self.instantiation_depth > 0 {
+            return e;
+        }
+        let mut frames: Vec<(Span, usize)> = vec![];
+        for site in self.instantiation_trace.drain(..) {
+            match frames.last_mut() {
+                Some((s, n)) if *s == site => *n += 1,
+                _ => frames.push((site, 1)),
+            }
+        }
+        let frame = |&(site, n): &(Span, usize)| {
+            let mut msg = format!(
+                "In the instantiation {}",
+                &self.input.text()[site.0..site.1]
+            );
+            if n > 1 {
+                msg.push_str(&format!(" ({} times)", n));
+            }
+            label(self.input, site, msg)
+        };
+        let mut labels: Vec<_> = frames.iter().map(frame).collect();
+        if labels.len() > MAX_INSTANTIATION_TRACE {
+            // Show the innermost instantiations and the outermost one.
+            let omitted = labels.len() - MAX_INSTANTIATION_TRACE;
+            labels.drain(MAX_INSTANTIATION_TRACE - 1..labels.len() - 1);
+            let last_inner = &mut labels[MAX_INSTANTIATION_TRACE - 2];
+            last_inner
+                .msg
+                .push_str(&format!("\n({} more instantiations)", omitted));
+        }
+        let mut e = match e.downcast::<SourceError>() {
+            Ok(e) => e,
+            Err(e) => SourceError {
+                msg: Some(e.to_string()),
+                labels: vec![],
+            },
+        };
+        e.labels.extend(labels);
+        e.into()
+    }
+
+    fn eval_type_argument(&mut self, a: &'a ast::TypeArgument) -> Result<Argument> {
+        let t = match a {
+            ast::TypeArgument::Type(t) => t,
+            ast::TypeArgument::Expr(e) => return Ok(Argument::Const(self.eval_expr(e)?)),
+        };
+        // Plain names are parsed as types but can also refer to constants.
+        if let ast::TypeVariant::Name(n, args, span) = &t.variant {
+            if args.is_empty() && t.annotations.is_empty() {
+                if let Some(a) = self.argument(n) {
+                    return Ok(a);
+                }
+                if let Some(&d) = self.declarations.get(&**n) {
+                    if let DeclarationType::Const(_) = d.ty {
+                        return Ok(Argument::Const(self.compute_decl_const(d, *span)?));
+                    }
+                }
+            }
+        }
+        Ok(Argument::Type(self.compute_type_layout(t)?.layout))
+    }
+
     fn compute_type_layout(&mut self, t: &'a ast::Type) -> Result<Type<TypeLayout>> {
-        let t = self.convert_type(t)?;
-        Ok(self.converter.convert(t)?)
+        let ty = self.convert_type(t)?;
+        let (mut ty, warnings) = self.converter.convert_with_warnings(ty)?;
+        for w in warnings {
+            self.warnings.push(Warning {
+                span: warning_span(t, w.path()),
+                kind: w.kind(),
+            });
+        }
+        if let Some(target) = self.converter.target() {
+            self.set_named_signedness(target, t, &mut ty);
+        }
+        Ok(ty)
+    }
+
+    /// Sets the signedness of bit-fields whose type is a declared type.
+    ///
+    /// Declared types are converted to opaque types whose signedness is unknown.
+    fn set_named_signedness(&self, target: Target, t: &ast::Type, ty: &mut Type<TypeLayout>) {
+        match (&t.variant, &mut ty.variant) {
+            (ast::TypeVariant::Record(r), TypeVariant::Record(rc)) => {
+                for (f, fc) in r.fields.iter().zip(rc.fields.iter_mut()) {
+                    if fc.bit_width.is_some() && fc.signed.is_none() {
+                        fc.signed = match &f.ty.variant {
+                            ast::TypeVariant::Name(n, args, _)
+                                if args.is_empty() && self.argument(n).is_none() =>
+                            {
+                                self.declared_signedness(target, n)
+                            }
+                            _ => None,
+                        };
+                    }
+                    self.set_named_signedness(target, &f.ty, &mut fc.ty);
+                }
+            }
+            (ast::TypeVariant::Typedef(dst), TypeVariant::Typedef(dstc)) => {
+                self.set_named_signedness(target, dst, dstc)
+            }
+            (ast::TypeVariant::Array(a), TypeVariant::Array(ac)) => {
+                self.set_named_signedness(target, &a.element_type, &mut ac.element_type)
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the signedness of a bit-field whose type is the declared type `name`.
+    fn declared_signedness(&self, target: Target, name: &str) -> Option<bool> {
+        let d = match self.declarations.get(name) {
+            Some(d) if d.params.is_empty() => d,
+            Some(_) => return None,
+            None => return bit_field_is_signed(target, &c_typedef(target, name)?),
+        };
+        let mut ty = match &d.ty {
+            DeclarationType::Type(ty) => ty,
+            _ => return None,
+        };
+        if let Some(s) = bit_field_is_signed(target, self.type_layouts.get(name)?) {
+            return Some(s);
+        }
+        // Typedefs of declared types are computed as typedefs of opaque types.
+        while let ast::TypeVariant::Typedef(dst) = &ty.variant {
+            ty = dst;
+        }
+        match &ty.variant {
+            ast::TypeVariant::Name(n, args, _) if args.is_empty() => {
+                self.declared_signedness(target, n)
+            }
+            _ => None,
+        }
     }
 
     fn convert_type(&mut self, t: &'a ast::Type) -> Result<Type<C::Src>> {
@@ -231,23 +859,77 @@ impl<'a, C: Convert> Computer<'a, C> {
             ast::TypeVariant::Builtin(bi) => TypeVariant::Builtin(*bi),
             ast::TypeVariant::Record(r) => TypeVariant::Record(self.convert_record(r)?),
             ast::TypeVariant::Array(a) => TypeVariant::Array(self.convert_array(a)?),
-            ast::TypeVariant::Name(n, span) => match self.declarations.get(&**n) {
-                None => {
-                    return Err(anyhow!(
-                        "At {}: The referenced type {} is not declared",
-                        self.span(*span),
-                        n
+            ast::TypeVariant::Name(n, args, span) => match self.argument(n) {
+                Some(_) if !args.is_empty() => {
+                    return Err(self.error(
+                        *span,
+                        format!("{} takes 0 arguments but {} were given", n, args.len()),
                     ))
                 }
-                Some(&d) => TypeVariant::Opaque(self.compute_decl_ty_layout(d, *span)?),
+                Some(Argument::Type(layout)) => TypeVariant::Opaque(layout),
+                Some(Argument::Const(_)) => {
+                    return Err(self.error(*span, format!("{} is a constant but must be a type", n)))
+                }
+                None => match self.declarations.get(&**n) {
+                    None if args.is_empty() => match self.converter.predefined_type(n, t)? {
+                        Some(layout) => TypeVariant::Opaque(layout),
+                        None => {
+                            return Err(self.error(
+                                *span,
+                                format!("The referenced type {} is not declared", n),
+                            ))
+                        }
+                    },
+                    None => {
+                        return Err(
+                            self.error(*span, format!("The referenced type {} is not declared", n))
+                        )
+                    }
+                    Some(&d) if d.params.is_empty() && args.is_empty() => {
+                        TypeVariant::Opaque(self.compute_decl_ty_layout(d, *span)?)
+                    }
+                    Some(_) if C::USE_EVALUATED_EXPR && t.layout.is_some() => {
+                        TypeVariant::Opaque(t.layout.unwrap())
+                    }
+                    Some(&d) => TypeVariant::Opaque(self.instantiate(d, args, *span)?.0.layout),
+                },
             },
             ast::TypeVariant::Typedef(td) => TypeVariant::Typedef(Box::new(self.convert_type(td)?)),
             ast::TypeVariant::Enum(e) => {
-                let mut res = vec![];
-                for e in e {
-                    res.push(self.eval_expr(e)?);
+                let mut variants = vec![];
+                for e in &e.variants {
+                    variants.push(self.eval_expr(e)?);
                 }
-                TypeVariant::Enum(res)
+                TypeVariant::Enum(Enum {
+                    fixed_underlying_type: e.fixed_underlying_type,
+                    variants,
+                    underlying_type: e.underlying_type,
+                })
+            }
+            ast::TypeVariant::RustStruct(s) => TypeVariant::RustStruct(RustStruct {
+                repr: s.repr,
+                fields: self.convert_record_fields(&s.fields)?,
+            }),
+            ast::TypeVariant::RustEnum(e) => {
+                let mut variants = vec![];
+                // Like in Rust, a variant without explicit discriminant has the discriminant
+                // of the previous variant plus one.
+                let mut discriminant = 0;
+                for v in &e.variants {
+                    if let Some(d) = &v.discriminant {
+                        discriminant = self.eval_expr(d)?;
+                    }
+                    variants.push(RustEnumVariant {
+                        discriminant,
+                        fields: self.convert_record_fields(&v.fields)?,
+                    });
+                    discriminant = discriminant.wrapping_add(1);
+                }
+                TypeVariant::RustEnum(RustEnum {
+                    repr: e.repr,
+                    tag_layout: None,
+                    variants,
+                })
             }
         };
         Ok(Type {
@@ -258,17 +940,27 @@ impl<'a, C: Convert> Computer<'a, C> {
     }
 
     fn convert_record(&mut self, r: &'a ast::Record) -> Result<Record<C::Src>> {
-        let mut fields = vec![];
-        for f in &r.fields {
-            fields.push(self.convert_record_field(f)?);
-        }
         Ok(Record {
             kind: r.kind,
-            fields,
+            fields: self.convert_record_fields(&r.fields)?,
         })
     }
 
+    fn convert_record_fields(
+        &mut self,
+        fields: &'a [ast::RecordField],
+    ) -> Result<Vec<RecordField<C::Src>>> {
+        let mut res = vec![];
+        for f in fields {
+            res.push(self.convert_record_field(f)?);
+        }
+        Ok(res)
+    }
+
     fn convert_record_field(&mut self, f: &'a ast::RecordField) -> Result<RecordField<C::Src>> {
+        if let Some(c) = &f.condition {
+            return Err(unevaluated_condition(self.input, c));
+        }
         Ok(RecordField {
             layout: match f.pos {
                 Some(p) => Some(self.converter.extract_field(f, p)?),
@@ -281,6 +973,10 @@ impl<'a, C: Convert> Computer<'a, C> {
                 .as_ref()
                 .map(|w| self.eval_u64_expr(w))
                 .transpose()?,
+            signed: match f.pos {
+                Some(p) if f.bit_width.is_some() => self.converter.extract_signedness(f, p)?,
+                _ => None,
+            },
             ty: self.convert_type(&f.ty)?,
         })
     }
@@ -297,18 +993,19 @@ impl<'a, C: Convert> Computer<'a, C> {
                 ast::Annotation::Aligned(Some(e)) => {
                     Annotation::Align(Some(BITS_PER_BYTE * self.eval_u64_expr(e)?))
                 }
+                ast::Annotation::Alignas(e) => {
+                    Annotation::Alignas(BITS_PER_BYTE * self.eval_u64_expr(e)?)
+                }
             });
         }
         Ok(res)
     }
 
     fn eval_u64_expr(&mut self, e: &'a ast::Expr) -> Result<u64> {
-        let v = self.eval_expr(e)?.try_into().map_err(|_| {
-            anyhow!(
-                "At {}: Expression value does not fit into u64",
-                self.span(e.span)
-            )
-        })?;
+        let v = self
+            .eval_expr(e)?
+            .try_into()
+            .map_err(|_| self.error(e.span, "Expression value does not fit into u64"))?;
         Ok(v)
     }
 
@@ -322,16 +1019,25 @@ impl<'a, C: Convert> Computer<'a, C> {
             ExprType::Lit(n) => Ok(*n),
             ExprType::Builtin(b) => match b {
                 BuiltinExpr::BitsPerByte => Ok(BITS_PER_BYTE as i128),
+                BuiltinExpr::TargetPointerWidth => {
+                    Ok(self.target(e.span)?.pointer_width_bits() as i128)
+                }
             },
+            ExprType::Target(p, s) => self.eval_target_property(e.span, *p, s),
             ExprType::Unary(k, v) => {
                 let v = self.eval_expr(v)?;
                 match *k {
                     UnaryExprType::Neg => v
                         .checked_neg()
-                        .ok_or_else(|| anyhow!("At {}: Expression overflow", self.span(e.span))),
+                        .ok_or_else(|| self.error(e.span, "Expression overflow")),
                     UnaryExprType::Not => Ok(if v != 0 { 0 } else { 1 }),
+                    UnaryExprType::BitNot => Ok(!v),
                 }
             }
+            ExprType::Conditional(c, t, el) => match self.eval_expr(c)? {
+                0 => self.eval_expr(el),
+                _ => self.eval_expr(t),
+            },
             ExprType::Binary(k, le, re) => {
                 use BinaryExprType::*;
                 let l = self.eval_expr(le)?;
@@ -343,10 +1049,10 @@ impl<'a, C: Convert> Computer<'a, C> {
                         Mul => l.checked_mul(r),
                         _ => unreachable!(),
                     }
-                    .ok_or_else(|| anyhow!("At {}: Expression overflow", self.span(e.span))),
+                    .ok_or_else(|| self.error(e.span, "Expression overflow")),
                     Div | Mod => {
                         if r == 0 {
-                            return Err(anyhow!("At {}: Division by zero", self.span(re.span)));
+                            return Err(self.error(re.span, "Division by zero"));
                         }
                         Ok(match *k {
                             Div => l / r,
@@ -354,6 +1060,29 @@ impl<'a, C: Convert> Computer<'a, C> {
                             _ => unreachable!(),
                         })
                     }
+                    BitAnd => Ok(l & r),
+                    BitOr => Ok(l | r),
+                    BitXor => Ok(l ^ r),
+                    Min => Ok(l.min(r)),
+                    Max => Ok(l.max(r)),
+                    Shl | Shr => {
+                        if !(0..128).contains(&r) {
+                            return Err(self.error(re.span, format!("Invalid shift amount {}", r)));
+                        }
+                        let r = r as u32;
+                        match *k {
+                            Shl if (l << r) >> r == l => Ok(l << r),
+                            Shl => Err(self.error(e.span, "Expression overflow")),
+                            _ => Ok(l >> r),
+                        }
+                    }
+                    AlignUp => {
+                        if r <= 0 {
+                            return Err(self.error(re.span, "Alignment must be positive"));
+                        }
+                        l.checked_add((r - l.rem_euclid(r)) % r)
+                            .ok_or_else(|| self.error(e.span, "Expression overflow"))
+                    }
                     LogicalAnd | LogicalOr | Eq | NotEq | Lt | Le | Gt | Ge => {
                         let ll = l != 0;
                         let rr = r != 0;
@@ -376,19 +1105,38 @@ impl<'a, C: Convert> Computer<'a, C> {
                 Ok(match k {
                     TypeExprType::Sizeof => (layout.size_bits / BITS_PER_BYTE) as i128,
                     TypeExprType::SizeofBits => layout.size_bits as i128,
+                    TypeExprType::Alignof => (layout.field_alignment_bits / BITS_PER_BYTE) as i128,
+                    TypeExprType::AlignofBits => layout.field_alignment_bits as i128,
+                    TypeExprType::PointerAlignof => {
+                        (layout.pointer_alignment_bits / BITS_PER_BYTE) as i128
+                    }
+                    TypeExprType::PointerAlignofBits => layout.pointer_alignment_bits as i128,
+                    TypeExprType::RequiredAlignof => {
+                        (layout.required_alignment_bits / BITS_PER_BYTE) as i128
+                    }
+                    TypeExprType::RequiredAlignofBits => layout.required_alignment_bits as i128,
                 })
             }
-            ExprType::Name(n) => match self.declarations.get(&**n) {
-                None => Err(anyhow!(
-                    "At {}: The referenced constant {} is not declared",
-                    self.span(e.span),
-                    n
-                )),
-                Some(&d) => self.compute_decl_const(d, e.span),
+            ExprType::Name(n) => match self.argument(n) {
+                Some(Argument::Const(v)) => Ok(v),
+                Some(Argument::Type(_)) => {
+                    Err(self.error(e.span, format!("{} is a type but must be a constant", n)))
+                }
+                None => match self.declarations.get(&**n) {
+                    None => Err(self.error(
+                        e.span,
+                        format!("The referenced constant {} is not declared", n),
+                    )),
+                    Some(&d) => self.compute_decl_const(d, e.span),
+                },
             },
             ExprType::Offsetof(k, aty, p) => {
                 let ty = self.compute_type_layout(aty)?;
-                let val = self.eval_offsetof(*k, aty, &ty, &p[0], &p[1..])?;
+                let scope = OffsetofScope {
+                    arguments: self.arguments.last().cloned().unwrap_or_default(),
+                    types: HashMap::new(),
+                };
+                let val = self.eval_offsetof(*k, aty, &ty, &p[0], &p[1..], &scope)?;
                 match k {
                     OffsetofType::Bytes => Ok((val / BITS_PER_BYTE) as i128),
                     OffsetofType::Bits => Ok(val as i128),
@@ -397,6 +1145,57 @@ impl<'a, C: Convert> Computer<'a, C> {
         }
     }
 
+    fn target(&self, span: Span) -> Result<Target> {
+        self.converter
+            .target()
+            .ok_or_else(|| self.error(span, "The target is not known in this context"))
+    }
+
+    fn eval_target_property(&self, span: Span, p: TargetProperty, s: &str) -> Result<i128> {
+        let target = self.target(span)?;
+        let matches = match p {
+            TargetProperty::Name => glob_matches(s.as_bytes(), target.name().as_bytes()),
+            TargetProperty::Compiler => {
+                let compiler = match s {
+                    "msvc" => Compiler::Msvc,
+                    "gcc" => Compiler::Gcc,
+                    "clang" => Compiler::Clang,
+                    _ => {
+                        return Err(self.error(
+                            span,
+                            format!("Unknown compiler {}. Expected `msvc`, `gcc`, or `clang`", s),
+                        ))
+                    }
+                };
+                system_compiler(target) == compiler
+            }
+            TargetProperty::Os => {
+                if TARGETS.iter().all(|t| t.os() != s) {
+                    return Err(self.error(span, format!("Unknown operating system {}", s)));
+                }
+                target.os() == s
+            }
+            TargetProperty::Endian => {
+                let endianness = match s {
+                    "little" => Endianness::Little,
+                    "big" => Endianness::Big,
+                    _ => {
+                        return Err(self.error(
+                            span,
+                            format!("Unknown byte order {}. Expected `little` or `big`", s),
+                        ))
+                    }
+                };
+                target.endianness() == endianness
+            }
+        };
+        Ok(matches as i128)
+    }
+
+    /// Computes the offset of the field `head.rest` in `aty`.
+    ///
+    /// The names in `aty` are resolved with the arguments in `scope`. The array indices
+    /// are evaluated with the current arguments.
     fn eval_offsetof(
         &mut self,
         k: OffsetofType,
@@ -404,81 +1203,99 @@ impl<'a, C: Convert> Computer<'a, C> {
         ty: &Type<TypeLayout>,
         head: &'a Index,
         rest: &'a [Index],
+        scope: &OffsetofScope<'a>,
     ) -> Result<u64> {
-        let (aty, ty, base) = match (&aty.variant, &ty.variant, &head.ty) {
-            (ast::TypeVariant::Record(ar), TypeVariant::Record(r), IndexType::Field(name)) => {
-                let af = match ar.fields.iter().find(|f| f.name.as_ref() == Some(name)) {
-                    Some(f) => f,
-                    None => {
-                        return Err(anyhow!(
-                            "At {}: Type has no field {}",
-                            self.span(head.span),
-                            name
-                        ))
-                    }
-                };
-                let pos = ar
-                    .fields
-                    .iter()
-                    .position(|f| f.name.as_ref() == Some(name))
-                    .unwrap();
-                let f = &r.fields[pos];
-                if f.bit_width.is_some() && k == OffsetofType::Bytes {
-                    return Err(anyhow!(
-                        "At {}: Cannot compute bytewise offset of bit field",
-                        self.span(head.span)
-                    ));
-                }
-                (&af.ty, &f.ty, f.layout.unwrap().offset_bits)
+        let (aty, pe) = match (&aty.variant, &head.ty) {
+            (ast::TypeVariant::Record(ar), IndexType::Field(name)) => {
+                self.field_index(&ar.fields, head, name)?
+            }
+            (ast::TypeVariant::RustStruct(ar), IndexType::Field(name)) => {
+                self.field_index(&ar.fields, head, name)?
             }
-            (ast::TypeVariant::Array(aa), TypeVariant::Array(a), IndexType::Array(pos)) => {
+            (ast::TypeVariant::Array(aa), IndexType::Array(pos)) => {
                 let pos = self.eval_u64_expr(pos)?;
-                match a.num_elements {
-                    Some(n) if pos > n => {
-                        return Err(anyhow!("At {}: Out of bounds", self.span(head.span)));
-                    }
-                    _ => {}
-                }
-                match a.element_type.layout.size_bits.checked_mul(pos) {
-                    None => {
-                        return Err(anyhow!("At {}: Offset overflow", self.span(head.span)));
-                    }
-                    Some(b) => (&*aa.element_type, &*a.element_type, b),
-                }
+                (&*aa.element_type, FieldPathElement::Index(pos))
             }
-            (ast::TypeVariant::Name(n, span), _, _) => {
+            (ast::TypeVariant::Name(n, args, span), _) if !scope.arguments.contains_key(&**n) => {
                 let d = match self.declarations.get(&**n) {
                     None => {
-                        return Err(anyhow!(
-                            "At {}: The referenced type {} is not declared",
-                            self.span(*span),
-                            n
-                        ))
+                        return Err(
+                            self.error(*span, format!("The referenced type {} is not declared", n))
+                        )
                     }
                     Some(d) => *d,
                 };
-                self.compute_decl_ty_layout(d, head.span)?;
+                let (ty, inner) = if d.params.is_empty() && args.is_empty() {
+                    self.compute_decl_ty_layout(d, head.span)?;
+                    let ty = self.type_layouts.get(n).unwrap().clone();
+                    (ty, OffsetofScope::default())
+                } else {
+                    self.arguments.push(scope.arguments.clone());
+                    let res = self.instantiate(d, args, *span);
+                    self.arguments.pop();
+                    let (ty, arguments) = res?;
+                    let outer = Rc::new(scope.clone());
+                    let mut types = HashMap::new();
+                    for (p, a) in d.params.iter().zip(args) {
+                        if let ast::TypeArgument::Type(t) = a {
+                            types.insert(&**p, (t, outer.clone()));
+                        }
+                    }
+                    (ty, OffsetofScope { arguments, types })
+                };
                 let aty = match &d.ty {
                     DeclarationType::Type(aty) => aty,
-                    DeclarationType::Const(_) => unreachable!(),
+                    DeclarationType::Const(_) | DeclarationType::Assert(_) => unreachable!(),
                 };
-                let ty = self.type_layouts.get(n).unwrap().clone();
-                return self.eval_offsetof(k, aty, &ty, head, rest);
+                return self.eval_offsetof(k, aty, &ty, head, rest, &inner);
+            }
+            (ast::TypeVariant::Name(n, ..), _) if scope.types.contains_key(&**n) => {
+                let (aty, outer) = scope.types[&**n].clone();
+                self.arguments.push(outer.arguments.clone());
+                let ty = self.compute_type_layout(aty);
+                self.arguments.pop();
+                return self.eval_offsetof(k, aty, &ty?, head, rest, &outer);
+            }
+            (_, IndexType::Field(_)) => {
+                return Err(self.error(head.span, "Type is not a record"));
             }
-            (_, _, IndexType::Field(_)) => {
-                return Err(anyhow!("At {}: Type is not a record", self.span(head.span)));
+            (_, IndexType::Array(_)) => {
+                return Err(self.error(head.span, "Type is not an array"));
             }
-            (_, _, IndexType::Array(_)) => {
-                return Err(anyhow!("At {}: Type is not an array", self.span(head.span)));
+        };
+        let field = match resolve_field_path(ty, &[pe]) {
+            Ok(f) => f,
+            Err(e) => {
+                let msg = match e.kind() {
+                    ErrorType::ArrayIndexOutOfBounds => "Out of bounds".to_string(),
+                    ErrorType::OffsetOverflow => "Offset overflow".to_string(),
+                    _ => e.to_string(),
+                };
+                return Err(self.error(head.span, msg));
             }
         };
-        Ok(base
+        if field.bit_field && k == OffsetofType::Bytes {
+            return Err(self.error(head.span, "Cannot compute bytewise offset of bit field"));
+        }
+        Ok(field.offset_bits
             + match rest {
-                [head, rest @ ..] => self.eval_offsetof(k, aty, ty, head, rest)?,
+                [head, rest @ ..] => self.eval_offsetof(k, aty, field.ty, head, rest, scope)?,
                 _ => 0,
             })
     }
 
+    fn field_index(
+        &self,
+        afields: &'a [ast::RecordField],
+        head: &'a Index,
+        name: &str,
+    ) -> Result<(&'a ast::Type, FieldPathElement)> {
+        match afields.iter().position(|f| f.name.as_deref() == Some(name)) {
+            Some(pos) => Ok((&afields[pos].ty, FieldPathElement::Field(pos))),
+            None => Err(self.error(head.span, format!("Type has no field {}", name))),
+        }
+    }
+
     fn convert_array(&mut self, a: &'a ast::Array) -> Result<Array<C::Src>> {
         Ok(Array {
             element_type: Box::new(self.convert_type(&a.element_type)?),
@@ -489,3 +1306,60 @@ impl<'a, C: Convert> Computer<'a, C> {
         })
     }
 }
+
+fn warning_span(mut t: &ast::Type, path: &[PathElement]) -> Span {
+    // Named types are converted to opaque types before the layout is computed. Therefore
+    // every element of the path has a counterpart in the AST.
+    for p in path {
+        t = match (p, &t.variant) {
+            (PathElement::Field(i), ast::TypeVariant::Record(r)) => &r.fields[*i].ty,
+            (PathElement::TypedefTarget, ast::TypeVariant::Typedef(td)) => td,
+            (PathElement::ArrayElement, ast::TypeVariant::Array(a)) => &a.element_type,
+            (PathElement::Field(i), ast::TypeVariant::RustStruct(s)) => &s.fields[*i].ty,
+            (PathElement::VariantField(i, j), ast::TypeVariant::RustEnum(e)) => {
+                &e.variants[*i].fields[*j].ty
+            }
+            _ => unreachable!(),
+        };
+    }
+    Span(t.lo, t.lo)
+}
+
+fn unevaluated_condition(input: Source, c: &ast::Expr) -> anyhow::Error {
+    source_error(
+        input,
+        c.span,
+        "Conditions must be evaluated before the layouts are computed",
+    )
+}
+
+/// Returns whether `s` matches the glob `pattern`. `*` matches any sequence of characters
+/// and `?` matches any single character.
+fn glob_matches(pattern: &[u8], s: &[u8]) -> bool {
+    let (mut p, mut i) = (0, 0);
+    // The position of the last `*` in the pattern and the position in `s` at which it
+    // stopped matching. On a mismatch, the `*` is extended by one character instead of
+    // backtracking further.
+    let mut star = None;
+    while i < s.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, i));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == s[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match star {
+                Some((sp, si)) => {
+                    star = Some((sp, si + 1));
+                    p = sp + 1;
+                    i = si + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
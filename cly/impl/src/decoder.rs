@@ -0,0 +1,381 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use crate::ast;
+use crate::ast::{Declaration, DeclarationType, ExprType};
+use crate::converter::ConversionResult;
+use anyhow::{anyhow, Result};
+use repc_impl::bitfield::{bit_field_accesses, read_field, BitFieldAccess};
+use repc_impl::builder::common::integer_range;
+use repc_impl::layout::{BuiltinType, RecordField, Type, TypeLayout, TypeVariant};
+use repc_impl::target::{Endianness, Target};
+use repc_impl::typedefs::c_typedef;
+use repc_impl::util::BITS_PER_BYTE;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// A field or padding region of a decoded object.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedField {
+    /// The path of the field, e.g., `hdr.flags.bits[2]`.
+    ///
+    /// The path starts with the name of the decoded type. It is empty for padding.
+    pub path: String,
+    /// The offset of the field in bits.
+    pub offset_bits: u64,
+    /// The size of the field in bits.
+    pub size_bits: u64,
+    /// Whether the field is a bit-field.
+    pub bit_field: bool,
+    /// The value of the field.
+    pub value: DecodedValue,
+}
+
+/// The value of a decoded field.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DecodedValue {
+    Int(i128),
+    Bool(bool),
+    F32(f32),
+    F64(f64),
+    Pointer(u128),
+    /// The value of an enum and the name of the constant used to declare the matching
+    /// variant, if any.
+    Enum(i128, Option<String>),
+    /// The bytes of a value whose type cannot be decoded, e.g., an opaque type.
+    Bytes(Vec<u8>),
+    /// Bytes that are not part of any field.
+    Padding(Vec<u8>),
+}
+
+/// Decodes `bytes` as an object of the type declared as `name`.
+///
+/// `layouts` must be the result of computing the layouts of `declarations` for `target`.
+/// The fields are returned in the order of their offsets. Every member of a union and
+/// every variant of a Rust enum is decoded. Bytes after the end of the object are ignored.
+pub fn decode(
+    declarations: &[Declaration],
+    layouts: &ConversionResult,
+    target: Target,
+    name: &str,
+    bytes: &[u8],
+) -> Result<Vec<DecodedField>> {
+    let decls: HashMap<_, _> = declarations.iter().map(|d| (&*d.name, d)).collect();
+    let (aty, ty) = match (decls.get(name), layouts.types.get(name)) {
+        (Some(d), Some(ty)) => match &d.ty {
+            DeclarationType::Type(aty) => (aty, ty),
+            DeclarationType::Const(_) | DeclarationType::Assert(_) => {
+                return Err(anyhow!("{} is not a type", name))
+            }
+        },
+        _ => return Err(anyhow!("The type {} is not declared", name)),
+    };
+    let size_bytes = ty.layout.size_bits / BITS_PER_BYTE;
+    if (bytes.len() as u64) < size_bytes {
+        return Err(anyhow!(
+            "{} has size {} but only {} bytes are available",
+            name,
+            size_bytes,
+            bytes.len()
+        ));
+    }
+    let bytes = &bytes[..size_bytes as usize];
+    let mut decoder = Decoder {
+        target,
+        decls,
+        layouts,
+        bytes,
+        covered: vec![false; bytes.len()],
+        fields: vec![],
+    };
+    decoder.decode_type(aty, ty, 0, name.to_string());
+    let mut padding_start = None;
+    for i in 0..=bytes.len() {
+        match (decoder.covered.get(i), padding_start) {
+            (Some(false), None) => padding_start = Some(i),
+            (Some(true), Some(start)) | (None, Some(start)) => {
+                decoder.fields.push(DecodedField {
+                    path: String::new(),
+                    offset_bits: start as u64 * BITS_PER_BYTE,
+                    size_bits: (i - start) as u64 * BITS_PER_BYTE,
+                    bit_field: false,
+                    value: DecodedValue::Padding(bytes[start..i].to_vec()),
+                });
+                padding_start = None;
+            }
+            _ => {}
+        }
+    }
+    let mut fields = decoder.fields;
+    fields.sort_by_key(|f| f.offset_bits);
+    Ok(fields)
+}
+
+struct Decoder<'a> {
+    target: Target,
+    decls: HashMap<&'a str, &'a Declaration>,
+    layouts: &'a ConversionResult,
+    bytes: &'a [u8],
+    covered: Vec<bool>,
+    fields: Vec<DecodedField>,
+}
+
+impl<'a> Decoder<'a> {
+    fn decode_type(
+        &mut self,
+        aty: &'a ast::Type,
+        ty: &'a Type<TypeLayout>,
+        offset_bits: u64,
+        path: String,
+    ) {
+        let value = match (&aty.variant, &ty.variant) {
+            (ast::TypeVariant::Name(n, ..), _) => {
+                match (self.decls.get(&**n), self.layouts.types.get(n)) {
+                    (Some(d), Some(ty)) => {
+                        if let DeclarationType::Type(aty) = &d.ty {
+                            self.decode_type(aty, ty, offset_bits, path);
+                            return;
+                        }
+                        self.bytes_value(offset_bits, ty.layout.size_bits)
+                    }
+                    // Predefined typedefs are converted to opaque types.
+                    _ => match c_typedef(self.target, n).map(|t| t.variant) {
+                        Some(TypeVariant::Typedef(t)) => match t.variant {
+                            TypeVariant::Builtin(bi) => self.builtin_value(bi, offset_bits, ty),
+                            _ => self.bytes_value(offset_bits, ty.layout.size_bits),
+                        },
+                        _ => self.bytes_value(offset_bits, ty.layout.size_bits),
+                    },
+                }
+            }
+            (ast::TypeVariant::Typedef(aty), TypeVariant::Typedef(ty)) => {
+                self.decode_type(aty, ty, offset_bits, path);
+                return;
+            }
+            (ast::TypeVariant::Record(ar), TypeVariant::Record(r)) => {
+                let accesses = bit_field_accesses(self.target, ty).unwrap();
+                self.decode_fields(&ar.fields, &r.fields, &accesses, offset_bits, &path);
+                return;
+            }
+            (ast::TypeVariant::RustStruct(ar), TypeVariant::RustStruct(r)) => {
+                self.decode_fields(&ar.fields, &r.fields, &[], offset_bits, &path);
+                return;
+            }
+            (ast::TypeVariant::RustEnum(ae), TypeVariant::RustEnum(e)) => {
+                for (av, v) in ae.variants.iter().zip(e.variants.iter()) {
+                    let path = format!("{}::{}", path, av.name);
+                    self.decode_fields(&av.fields, &v.fields, &[], offset_bits, &path);
+                }
+                return;
+            }
+            (ast::TypeVariant::Array(aa), TypeVariant::Array(a)) => {
+                let size_bits = a.element_type.layout.size_bits;
+                for i in 0..a.num_elements.unwrap_or(0) {
+                    self.decode_type(
+                        &aa.element_type,
+                        &a.element_type,
+                        offset_bits + i * size_bits,
+                        format!("{}[{}]", path, i),
+                    );
+                }
+                return;
+            }
+            (ast::TypeVariant::Builtin(bi), _) => self.builtin_value(*bi, offset_bits, ty),
+            (ast::TypeVariant::Enum(ae), TypeVariant::Enum(e)) => {
+                let signed = e.underlying_type.is_some_and(|u| u.signed);
+                let value = self.int(offset_bits, ty.layout.size_bits, signed);
+                DecodedValue::Enum(value, variant_name(ae, &e.variants, value))
+            }
+            _ => self.bytes_value(offset_bits, ty.layout.size_bits),
+        };
+        self.push(path, offset_bits, ty.layout.size_bits, false, value);
+    }
+
+    fn decode_fields(
+        &mut self,
+        afields: &'a [ast::RecordField],
+        fields: &'a [RecordField<TypeLayout>],
+        accesses: &[BitFieldAccess],
+        offset_bits: u64,
+        path: &str,
+    ) {
+        for (i, (af, f)) in afields.iter().zip(fields.iter()).enumerate() {
+            let (layout, name) = match (f.layout, &af.name) {
+                (Some(l), Some(n)) => (l, n),
+                _ => continue,
+            };
+            let path = format!("{}.{}", path, name);
+            let offset_bits = offset_bits + layout.offset_bits;
+            match accesses.iter().find(|a| a.field_index == i) {
+                Some(access) => {
+                    let value = self.bit_field_value(af, f, access, offset_bits);
+                    self.push(path, offset_bits, layout.size_bits, true, value);
+                }
+                None => self.decode_type(&af.ty, &f.ty, offset_bits, path),
+            }
+        }
+    }
+
+    fn bit_field_value(
+        &mut self,
+        af: &'a ast::RecordField,
+        f: &RecordField<TypeLayout>,
+        access: &BitFieldAccess,
+        offset_bits: u64,
+    ) -> DecodedValue {
+        // The access describes the bit-field relative to the start of the record.
+        let record_start = (offset_bits - f.layout.unwrap().offset_bits) / BITS_PER_BYTE;
+        let value = read_field(access, &self.bytes[record_start as usize..]);
+        let (mut aty, mut ty) = (&af.ty, &f.ty);
+        loop {
+            match (&aty.variant, &ty.variant) {
+                (ast::TypeVariant::Typedef(a), TypeVariant::Typedef(t)) => {
+                    aty = a;
+                    ty = t;
+                }
+                (ast::TypeVariant::Name(n, ..), _) => match self.decls.get(&**n) {
+                    Some(Declaration {
+                        ty: DeclarationType::Type(a),
+                        params,
+                        ..
+                    }) if params.is_empty() => {
+                        aty = a;
+                        ty = &self.layouts.types[n];
+                    }
+                    _ => return DecodedValue::Int(value),
+                },
+                (_, TypeVariant::Builtin(BuiltinType::Bool)) if value <= 1 => {
+                    return DecodedValue::Bool(value == 1)
+                }
+                (ast::TypeVariant::Enum(ae), TypeVariant::Enum(e)) => {
+                    return DecodedValue::Enum(value, variant_name(ae, &e.variants, value))
+                }
+                _ => return DecodedValue::Int(value),
+            }
+        }
+    }
+
+    fn builtin_value(
+        &mut self,
+        bi: BuiltinType,
+        offset_bits: u64,
+        ty: &Type<TypeLayout>,
+    ) -> DecodedValue {
+        let size_bits = ty.layout.size_bits;
+        match bi {
+            BuiltinType::Bool => match self.int(offset_bits, size_bits, false) {
+                v @ (0 | 1) => DecodedValue::Bool(v == 1),
+                v => DecodedValue::Int(v),
+            },
+            BuiltinType::Pointer => DecodedValue::Pointer(self.uint(offset_bits, size_bits)),
+            BuiltinType::F32 | BuiltinType::Float | BuiltinType::F64 | BuiltinType::Double => {
+                match size_bits {
+                    32 => DecodedValue::F32(f32::from_bits(self.uint(offset_bits, 32) as u32)),
+                    64 => DecodedValue::F64(f64::from_bits(self.uint(offset_bits, 64) as u64)),
+                    _ => self.bytes_value(offset_bits, size_bits),
+                }
+            }
+            _ => match integer_range(self.target, bi) {
+                Some((min, _)) => DecodedValue::Int(self.int(offset_bits, size_bits, min < 0)),
+                None => self.bytes_value(offset_bits, size_bits),
+            },
+        }
+    }
+
+    fn bytes_value(&self, offset_bits: u64, size_bits: u64) -> DecodedValue {
+        let start = (offset_bits / BITS_PER_BYTE) as usize;
+        let end = start + (size_bits / BITS_PER_BYTE) as usize;
+        DecodedValue::Bytes(self.bytes[start..end].to_vec())
+    }
+
+    fn uint(&self, offset_bits: u64, size_bits: u64) -> u128 {
+        let start = (offset_bits / BITS_PER_BYTE) as usize;
+        let bytes = &self.bytes[start..start + (size_bits / BITS_PER_BYTE) as usize];
+        let mut value = 0;
+        for i in 0..bytes.len() {
+            let b = match self.target.endianness() {
+                Endianness::Little => bytes[bytes.len() - 1 - i],
+                Endianness::Big => bytes[i],
+            };
+            value = (value << 8) | b as u128;
+        }
+        value
+    }
+
+    fn int(&self, offset_bits: u64, size_bits: u64, signed: bool) -> i128 {
+        let value = self.uint(offset_bits, size_bits);
+        let unused = 128 - size_bits as u32;
+        match signed && size_bits > 0 {
+            true => ((value << unused) as i128) >> unused,
+            false => value as i128,
+        }
+    }
+
+    fn push(
+        &mut self,
+        path: String,
+        offset_bits: u64,
+        size_bits: u64,
+        bit_field: bool,
+        value: DecodedValue,
+    ) {
+        if size_bits == 0 {
+            return;
+        }
+        let start = (offset_bits / BITS_PER_BYTE) as usize;
+        let end = (offset_bits + size_bits).div_ceil(BITS_PER_BYTE) as usize;
+        self.covered[start..end].iter_mut().for_each(|c| *c = true);
+        self.fields.push(DecodedField {
+            path,
+            offset_bits,
+            size_bits,
+            bit_field,
+            value,
+        });
+    }
+}
+
+/// Returns the name of the constant used to declare the variant with value `value`.
+fn variant_name(e: &ast::Enum, values: &[i128], value: i128) -> Option<String> {
+    let pos = values.iter().position(|&v| v == value)?;
+    match &e.variants[pos].ty {
+        ExprType::Name(n) => Some(n.clone()),
+        _ => None,
+    }
+}
+
+impl Display for DecodedField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let offset = self.offset_bits / BITS_PER_BYTE;
+        match &self.value {
+            DecodedValue::Padding(_) => write!(f, "<padding> @ {}", offset)?,
+            _ => write!(f, "{} @ {}", self.path, offset)?,
+        }
+        if self.bit_field {
+            write!(
+                f,
+                ".{}:{}",
+                self.offset_bits % BITS_PER_BYTE,
+                self.size_bits
+            )?;
+        }
+        f.write_str(" = ")?;
+        match &self.value {
+            DecodedValue::Int(v) => write!(f, "{}", v),
+            DecodedValue::Bool(v) => write!(f, "{}", v),
+            DecodedValue::F32(v) => write!(f, "{:?}", v),
+            DecodedValue::F64(v) => write!(f, "{:?}", v),
+            DecodedValue::Pointer(v) => write!(f, "{:#x}", v),
+            DecodedValue::Enum(v, Some(n)) => write!(f, "{} ({})", v, n),
+            DecodedValue::Enum(v, None) => write!(f, "{}", v),
+            DecodedValue::Bytes(b) | DecodedValue::Padding(b) => {
+                for (i, b) in b.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "{:02x}", b)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,253 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//! Documentation of declarations and their layouts in JSON and Markdown.
+use crate::ast;
+use crate::ast::{Declaration, DeclarationType};
+use crate::converter::ConversionResult;
+use crate::json::{object, Json};
+use repc_impl::layout::{RecordField, TypeLayout, TypeVariant};
+use std::fmt::Write;
+
+/// The output format of [`document`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DocFormat {
+    Json,
+    Markdown,
+}
+
+/// Returns the documentation of the types and constants in `declarations`.
+///
+/// `input` is the text the spans of the declarations point into and `layouts` the result of
+/// computing their layouts. Each declaration is documented with its doc comment and its
+/// layout or value. The fields of records, Rust structs, and Rust enums are documented with
+/// their doc comments, types, and layouts. The fields of a Rust enum are prefixed by the
+/// name of their variant, e.g. `Some.p`.
+pub fn document(
+    input: &str,
+    declarations: &[Declaration],
+    layouts: &ConversionResult,
+    format: DocFormat,
+) -> String {
+    let items: Vec<_> = declarations
+        .iter()
+        .filter_map(|d| item(input, d, layouts))
+        .collect();
+    match format {
+        DocFormat::Json => {
+            let items: Vec<_> = items.iter().map(Item::to_json).collect();
+            format!("{}\n", Json::from(items))
+        }
+        DocFormat::Markdown => {
+            let mut res = String::new();
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    res.push('\n');
+                }
+                item.write_markdown(&mut res);
+            }
+            res
+        }
+    }
+}
+
+struct Item<'a> {
+    name: String,
+    doc: Option<&'a str>,
+    kind: ItemKind,
+    fields: Vec<Field<'a>>,
+}
+
+enum ItemKind {
+    Type(Option<TypeLayout>),
+    Const(i128),
+}
+
+struct Field<'a> {
+    name: String,
+    doc: Option<&'a str>,
+    ty: String,
+    offset_bits: u64,
+    size_bits: u64,
+    bit_field: bool,
+}
+
+fn item<'a>(input: &str, d: &'a Declaration, layouts: &ConversionResult) -> Option<Item<'a>> {
+    let doc = d.doc.as_deref();
+    let (kind, fields) = match &d.ty {
+        DeclarationType::Type(_) if !d.params.is_empty() => {
+            let name = format!("{}({})", d.name, d.params.join(", "));
+            let kind = ItemKind::Type(None);
+            return Some(Item {
+                name,
+                doc,
+                kind,
+                fields: vec![],
+            });
+        }
+        DeclarationType::Type(aty) => {
+            let ty = layouts.types.get(&d.name)?;
+            let mut fields = vec![];
+            match (&aty.variant, &ty.variant) {
+                (ast::TypeVariant::Record(ar), TypeVariant::Record(r)) => {
+                    collect_fields(input, "", &ar.fields, &r.fields, &mut fields)
+                }
+                (ast::TypeVariant::RustStruct(ar), TypeVariant::RustStruct(r)) => {
+                    collect_fields(input, "", &ar.fields, &r.fields, &mut fields)
+                }
+                (ast::TypeVariant::RustEnum(ae), TypeVariant::RustEnum(e)) => {
+                    for (av, v) in ae.variants.iter().zip(e.variants.iter()) {
+                        let prefix = format!("{}.", av.name);
+                        collect_fields(input, &prefix, &av.fields, &v.fields, &mut fields);
+                    }
+                }
+                _ => {}
+            }
+            (ItemKind::Type(Some(ty.layout)), fields)
+        }
+        DeclarationType::Const(_) => (ItemKind::Const(*layouts.consts.get(&d.name)?), vec![]),
+        DeclarationType::Assert(_) => return None,
+    };
+    Some(Item {
+        name: d.name.clone(),
+        doc,
+        kind,
+        fields,
+    })
+}
+
+fn collect_fields<'a>(
+    input: &str,
+    prefix: &str,
+    afields: &'a [ast::RecordField],
+    fields: &[RecordField<TypeLayout>],
+    res: &mut Vec<Field<'a>>,
+) {
+    for (af, f) in afields.iter().zip(fields.iter()) {
+        let (name, layout) = match (&af.name, f.layout) {
+            (Some(n), Some(l)) => (n, l),
+            _ => continue,
+        };
+        let ty = &input[af.ty.lo..af.ty.hi];
+        res.push(Field {
+            name: format!("{}{}", prefix, name),
+            doc: af.doc.as_deref(),
+            ty: ty.split_whitespace().collect::<Vec<_>>().join(" "),
+            offset_bits: layout.offset_bits,
+            size_bits: layout.size_bits,
+            bit_field: af.bit_width.is_some(),
+        });
+    }
+}
+
+/// Returns an integer as a JSON number or, if it cannot be represented exactly, as a
+/// string.
+fn int(v: i128) -> Json {
+    match v.unsigned_abs() <= 1 << 53 {
+        true => Json::Number(v as f64),
+        false => v.to_string().into(),
+    }
+}
+
+fn doc_json(doc: Option<&str>) -> Json {
+    doc.map(Json::from).unwrap_or(Json::Null)
+}
+
+impl Item<'_> {
+    fn to_json(&self) -> Json {
+        let mut members = vec![
+            ("name", self.name.as_str().into()),
+            ("doc", doc_json(self.doc)),
+        ];
+        match self.kind {
+            ItemKind::Type(layout) => {
+                members.push(("kind", "type".into()));
+                if let Some(l) = layout {
+                    members.push(("size_bits", l.size_bits.into()));
+                    members.push(("field_alignment_bits", l.field_alignment_bits.into()));
+                    members.push(("pointer_alignment_bits", l.pointer_alignment_bits.into()));
+                }
+                let fields: Vec<_> = self.fields.iter().map(Field::to_json).collect();
+                members.push(("fields", fields.into()));
+            }
+            ItemKind::Const(v) => {
+                members.push(("kind", "const".into()));
+                members.push(("value", int(v)));
+            }
+        }
+        object(members)
+    }
+
+    fn write_markdown(&self, res: &mut String) {
+        let _ = writeln!(res, "## {}\n", self.name);
+        if let Some(doc) = self.doc {
+            let _ = writeln!(res, "{}\n", doc);
+        }
+        match self.kind {
+            ItemKind::Type(Some(l)) => {
+                let _ = write!(res, "Size: {} bytes, alignment: ", l.size_bits / 8);
+                if l.field_alignment_bits == l.pointer_alignment_bits {
+                    let _ = writeln!(res, "{} bytes", l.field_alignment_bits / 8);
+                } else {
+                    let _ = writeln!(
+                        res,
+                        "{} bytes as a field, {} bytes otherwise",
+                        l.field_alignment_bits / 8,
+                        l.pointer_alignment_bits / 8
+                    );
+                }
+            }
+            ItemKind::Type(None) => {
+                let _ = writeln!(res, "Generic type. The layout depends on the arguments.");
+            }
+            ItemKind::Const(v) => {
+                let _ = writeln!(res, "Value: {}", v);
+            }
+        }
+        if self.fields.is_empty() {
+            return;
+        }
+        // Offsets and sizes are given in bytes. Bit-fields are given as `byte.bit` and
+        // `:width` like in the output of `cly decode`.
+        res.push_str("\n| Field | Type | Offset | Size | Description |\n");
+        res.push_str("|-------|------|--------|------|-------------|\n");
+        for f in &self.fields {
+            let offset = match f.bit_field {
+                true => format!("{}.{}", f.offset_bits / 8, f.offset_bits % 8),
+                false => (f.offset_bits / 8).to_string(),
+            };
+            let size = match f.bit_field {
+                true => format!(":{}", f.size_bits),
+                false => (f.size_bits / 8).to_string(),
+            };
+            let _ = writeln!(
+                res,
+                "| {} | `{}` | {} | {} | {} |",
+                f.name,
+                f.ty,
+                offset,
+                size,
+                table_cell(f.doc.unwrap_or(""))
+            );
+        }
+    }
+}
+
+impl Field<'_> {
+    fn to_json(&self) -> Json {
+        let mut members = vec![
+            ("name", self.name.as_str().into()),
+            ("doc", doc_json(self.doc)),
+            ("type", self.ty.as_str().into()),
+            ("offset_bits", self.offset_bits.into()),
+            ("size_bits", self.size_bits.into()),
+        ];
+        if self.bit_field {
+            members.push(("bit_field", true.into()));
+        }
+        object(members)
+    }
+}
+
+/// Escapes a doc comment for a cell of a Markdown table.
+fn table_cell(doc: &str) -> String {
+    doc.replace('|', "\\|").replace('\n', " ")
+}
@@ -0,0 +1,439 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use crate::ast;
+use crate::ast::{Declaration, DeclarationType, ExprType};
+use crate::converter::ConversionResult;
+use crate::json::Json;
+use anyhow::{anyhow, bail, Result};
+use repc_impl::bitfield::{bit_field_accesses, write_field, BitFieldAccess};
+use repc_impl::builder::common::integer_range;
+use repc_impl::layout::{BuiltinType, RecordField, RecordKind, Type, TypeLayout, TypeVariant};
+use repc_impl::target::{Endianness, Target};
+use repc_impl::typedefs::c_typedef;
+use repc_impl::util::BITS_PER_BYTE;
+use std::collections::HashMap;
+use toml::Value;
+
+/// Encodes `value` as an object of the type declared as `name`.
+///
+/// `layouts` must be the result of computing the layouts of `declarations` for `target`.
+///
+/// Records and Rust structs are encoded from tables whose keys are the names of the fields.
+/// Arrays are encoded from arrays. Integers, enums, and pointers are encoded from integers
+/// or from strings containing decimal or hexadecimal integers. Enums can also be encoded
+/// from the names of the constants used to declare their variants. Rust enums are encoded
+/// from tables with a single key, the name of the variant.
+///
+/// Fields and array elements without a value are set to zero. If no member of a union has
+/// a value, its first member is set to zero. All other bytes are set to `fill`.
+pub fn encode(
+    declarations: &[Declaration],
+    layouts: &ConversionResult,
+    target: Target,
+    name: &str,
+    value: &Value,
+    fill: u8,
+) -> Result<Vec<u8>> {
+    let decls: HashMap<_, _> = declarations.iter().map(|d| (&*d.name, d)).collect();
+    let (aty, ty) = match (decls.get(name), layouts.types.get(name)) {
+        (Some(d), Some(ty)) => match &d.ty {
+            DeclarationType::Type(aty) => (aty, ty),
+            DeclarationType::Const(_) | DeclarationType::Assert(_) => {
+                return Err(anyhow!("{} is not a type", name))
+            }
+        },
+        _ => return Err(anyhow!("The type {} is not declared", name)),
+    };
+    let mut encoder = Encoder {
+        target,
+        decls,
+        layouts,
+        bytes: vec![fill; (ty.layout.size_bits / BITS_PER_BYTE) as usize],
+    };
+    encoder.encode_type(aty, ty, 0, name, Some(value))?;
+    Ok(encoder.bytes)
+}
+
+/// Parses the field values passed to [`encode`].
+///
+/// Input that starts with `{` is parsed as a JSON object, all other input as a TOML
+/// document. JSON numbers must be integers that can be represented exactly; larger
+/// integers can be given as strings.
+pub fn parse_values(input: &str) -> Result<Value> {
+    if input.trim_start().starts_with('{') {
+        json_to_toml(&Json::parse(input)?)
+    } else {
+        Ok(input.parse()?)
+    }
+}
+
+fn json_to_toml(json: &Json) -> Result<Value> {
+    let value = match json {
+        Json::Null => bail!("null is not a valid value"),
+        Json::Bool(b) => Value::Boolean(*b),
+        Json::Number(n) if n.fract() == 0.0 && n.abs() <= (1u64 << 53) as f64 => {
+            Value::Integer(*n as i64)
+        }
+        Json::Number(n) => bail!(
+            "The number {} is not an integer that can be represented exactly",
+            n
+        ),
+        Json::String(s) => Value::String(s.clone()),
+        Json::Array(a) => Value::Array(a.iter().map(json_to_toml).collect::<Result<_>>()?),
+        Json::Object(o) => {
+            let mut table = toml::value::Table::new();
+            for (k, v) in o {
+                table.insert(k.clone(), json_to_toml(v)?);
+            }
+            Value::Table(table)
+        }
+    };
+    Ok(value)
+}
+
+struct Encoder<'a> {
+    target: Target,
+    decls: HashMap<&'a str, &'a Declaration>,
+    layouts: &'a ConversionResult,
+    bytes: Vec<u8>,
+}
+
+impl<'a> Encoder<'a> {
+    fn encode_type(
+        &mut self,
+        aty: &'a ast::Type,
+        ty: &'a Type<TypeLayout>,
+        offset_bits: u64,
+        path: &str,
+        value: Option<&Value>,
+    ) -> Result<()> {
+        let size_bits = ty.layout.size_bits;
+        match (&aty.variant, &ty.variant) {
+            (ast::TypeVariant::Name(n, ..), _) => {
+                match (self.decls.get(&**n), self.layouts.types.get(n)) {
+                    (Some(d), Some(ty)) => match &d.ty {
+                        DeclarationType::Type(aty) => {
+                            self.encode_type(aty, ty, offset_bits, path, value)?
+                        }
+                        DeclarationType::Const(_) | DeclarationType::Assert(_) => unreachable!(),
+                    },
+                    // Predefined typedefs are converted to opaque types.
+                    _ => match c_typedef(self.target, n).map(|t| t.variant) {
+                        Some(TypeVariant::Typedef(t)) => match t.variant {
+                            TypeVariant::Builtin(bi) => {
+                                self.encode_builtin(bi, offset_bits, size_bits, path, value)?
+                            }
+                            _ => self.encode_bytes(offset_bits, size_bits, path, value)?,
+                        },
+                        _ => self.encode_bytes(offset_bits, size_bits, path, value)?,
+                    },
+                }
+            }
+            (ast::TypeVariant::Typedef(aty), TypeVariant::Typedef(ty)) => {
+                self.encode_type(aty, ty, offset_bits, path, value)?
+            }
+            (ast::TypeVariant::Record(ar), TypeVariant::Record(r)) => {
+                let accesses = bit_field_accesses(self.target, ty)?;
+                let table = table(path, value)?;
+                let union = r.kind == RecordKind::Union;
+                self.encode_fields(
+                    &ar.fields,
+                    &r.fields,
+                    &accesses,
+                    offset_bits,
+                    path,
+                    table,
+                    union,
+                )?
+            }
+            (ast::TypeVariant::RustStruct(ar), TypeVariant::RustStruct(r)) => {
+                let table = table(path, value)?;
+                self.encode_fields(&ar.fields, &r.fields, &[], offset_bits, path, table, false)?
+            }
+            (ast::TypeVariant::RustEnum(ae), TypeVariant::RustEnum(e)) => {
+                let (name, fields) = match value {
+                    None => (&*ae.variants[0].name, None),
+                    Some(Value::String(s)) => (&**s, None),
+                    Some(Value::Table(t)) if t.len() == 1 => {
+                        let (name, fields) = t.iter().next().unwrap();
+                        (&**name, Some(fields))
+                    }
+                    _ => bail!("{}: Expected a variant name or a table with one key", path),
+                };
+                let pos = match ae.variants.iter().position(|v| v.name == name) {
+                    Some(pos) => pos,
+                    None => bail!("{}: Unknown variant {}", path, name),
+                };
+                match e.tag_layout {
+                    Some(tag) => {
+                        let discriminant = e.variants[pos].discriminant as u128;
+                        self.write(offset_bits + tag.offset_bits, tag.size_bits, discriminant);
+                    }
+                    // Without a tag, the variant without fields is represented by a null
+                    // pointer in the field of the other variant.
+                    None if e.variants[pos].fields.is_empty() => {
+                        let fields = e.variants.iter().flat_map(|v| &v.fields);
+                        for (f, layout) in fields.filter_map(|f| Some((f, f.layout?))) {
+                            self.write(offset_bits + layout.offset_bits, f.ty.layout.size_bits, 0);
+                        }
+                    }
+                    None => {}
+                }
+                let path = format!("{}::{}", path, name);
+                let table = table(&path, fields)?;
+                let (av, v) = (&ae.variants[pos], &e.variants[pos]);
+                self.encode_fields(&av.fields, &v.fields, &[], offset_bits, &path, table, false)?
+            }
+            (ast::TypeVariant::Array(aa), TypeVariant::Array(a)) => {
+                let values = match value {
+                    None => &[][..],
+                    Some(Value::Array(a)) => &a[..],
+                    Some(_) => bail!("{}: Expected an array", path),
+                };
+                let num_elements = a.num_elements.unwrap_or(0);
+                if values.len() as u64 > num_elements {
+                    bail!("{}: The array has only {} elements", path, num_elements);
+                }
+                let element_size_bits = a.element_type.layout.size_bits;
+                for i in 0..num_elements {
+                    self.encode_type(
+                        &aa.element_type,
+                        &a.element_type,
+                        offset_bits + i * element_size_bits,
+                        &format!("{}[{}]", path, i),
+                        values.get(i as usize),
+                    )?;
+                }
+            }
+            (ast::TypeVariant::Builtin(bi), _) => {
+                self.encode_builtin(*bi, offset_bits, size_bits, path, value)?
+            }
+            (ast::TypeVariant::Enum(ae), TypeVariant::Enum(e)) => {
+                let signed = e.underlying_type.is_some_and(|u| u.signed);
+                let v = enum_value(ae, &e.variants, path, value)?;
+                check_range(path, v, size_bits, signed)?;
+                self.write(offset_bits, size_bits, v as u128);
+            }
+            _ => self.encode_bytes(offset_bits, size_bits, path, value)?,
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn encode_fields(
+        &mut self,
+        afields: &'a [ast::RecordField],
+        fields: &'a [RecordField<TypeLayout>],
+        accesses: &[BitFieldAccess],
+        offset_bits: u64,
+        path: &str,
+        table: Option<&toml::value::Table>,
+        union: bool,
+    ) -> Result<()> {
+        if let Some(table) = table {
+            for key in table.keys() {
+                if afields.iter().all(|f| f.name.as_deref() != Some(key)) {
+                    bail!("{}: Unknown field {}", path, key);
+                }
+            }
+        }
+        let given = |name: &str| table.and_then(|t| t.get(name));
+        let union_member = match union {
+            true => afields
+                .iter()
+                .position(|f| f.name.as_deref().is_some_and(|n| given(n).is_some()))
+                .or_else(|| afields.iter().position(|f| f.name.is_some())),
+            false => None,
+        };
+        for (i, (af, f)) in afields.iter().zip(fields.iter()).enumerate() {
+            let (layout, name) = match (f.layout, &af.name) {
+                (Some(l), Some(n)) => (l, n),
+                _ => continue,
+            };
+            if union && union_member != Some(i) {
+                if given(name).is_some() {
+                    bail!("{}: At most one member of a union can have a value", path);
+                }
+                continue;
+            }
+            let path = format!("{}.{}", path, name);
+            let value = given(name);
+            let offset_bits = offset_bits + layout.offset_bits;
+            match accesses.iter().find(|a| a.field_index == i) {
+                Some(access) => {
+                    let v = self.bit_field_value(af, f, &path, value)?;
+                    check_range(&path, v, access.width_bits, access.signed)?;
+                    // The access describes the bit-field relative to the start of the record.
+                    let start = (offset_bits - layout.offset_bits) / BITS_PER_BYTE;
+                    write_field(access, &mut self.bytes[start as usize..], v);
+                }
+                None => self.encode_type(&af.ty, &f.ty, offset_bits, &path, value)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn bit_field_value(
+        &self,
+        af: &'a ast::RecordField,
+        f: &'a RecordField<TypeLayout>,
+        path: &str,
+        value: Option<&Value>,
+    ) -> Result<i128> {
+        let (mut aty, mut ty) = (&af.ty, &f.ty);
+        loop {
+            match (&aty.variant, &ty.variant) {
+                (ast::TypeVariant::Typedef(a), TypeVariant::Typedef(t)) => {
+                    aty = a;
+                    ty = t;
+                }
+                (ast::TypeVariant::Name(n, ..), _) => match self.decls.get(&**n) {
+                    Some(Declaration {
+                        ty: DeclarationType::Type(a),
+                        params,
+                        ..
+                    }) if params.is_empty() => {
+                        aty = a;
+                        ty = &self.layouts.types[n];
+                    }
+                    _ => return int(path, value),
+                },
+                (ast::TypeVariant::Enum(ae), TypeVariant::Enum(e)) => {
+                    return enum_value(ae, &e.variants, path, value)
+                }
+                _ => return int(path, value),
+            }
+        }
+    }
+
+    fn encode_builtin(
+        &mut self,
+        bi: BuiltinType,
+        offset_bits: u64,
+        size_bits: u64,
+        path: &str,
+        value: Option<&Value>,
+    ) -> Result<()> {
+        let v = match bi {
+            BuiltinType::F32 | BuiltinType::Float | BuiltinType::F64 | BuiltinType::Double => {
+                let v = match value {
+                    None => 0.0,
+                    Some(Value::Float(f)) => *f,
+                    Some(Value::Integer(i)) => *i as f64,
+                    Some(_) => bail!("{}: Expected a number", path),
+                };
+                match size_bits {
+                    32 => (v as f32).to_bits() as u128,
+                    64 => v.to_bits() as u128,
+                    _ => bail!(
+                        "{}: Floating point types of this size are not supported",
+                        path
+                    ),
+                }
+            }
+            BuiltinType::Pointer => {
+                let v = int(path, value)?;
+                check_range(path, v, size_bits, false)?;
+                v as u128
+            }
+            _ => {
+                let v = int(path, value)?;
+                match integer_range(self.target, bi) {
+                    Some((min, max)) if v < min || v > max => {
+                        bail!("{}: The value {} is out of range", path, v)
+                    }
+                    Some(_) => v as u128,
+                    None => bail!("{}: Values of this type are not supported", path),
+                }
+            }
+        };
+        self.write(offset_bits, size_bits, v);
+        Ok(())
+    }
+
+    fn encode_bytes(
+        &mut self,
+        offset_bits: u64,
+        size_bits: u64,
+        path: &str,
+        value: Option<&Value>,
+    ) -> Result<()> {
+        let start = (offset_bits / BITS_PER_BYTE) as usize;
+        let size = (size_bits / BITS_PER_BYTE) as usize;
+        let values = match value {
+            None => &[][..],
+            Some(Value::Array(a)) => &a[..],
+            Some(_) => bail!("{}: Expected an array of bytes", path),
+        };
+        if values.len() > size {
+            bail!("{}: The type has only {} bytes", path, size);
+        }
+        for i in 0..size {
+            let b = int(path, values.get(i))?;
+            check_range(path, b, BITS_PER_BYTE, false)?;
+            self.bytes[start + i] = b as u8;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset_bits: u64, size_bits: u64, value: u128) {
+        let start = (offset_bits / BITS_PER_BYTE) as usize;
+        let bytes = &mut self.bytes[start..start + (size_bits / BITS_PER_BYTE) as usize];
+        let n = bytes.len();
+        for (i, b) in bytes.iter_mut().enumerate() {
+            let pos = match self.target.endianness() {
+                Endianness::Little => i,
+                Endianness::Big => n - 1 - i,
+            };
+            *b = (value >> (pos * 8)) as u8;
+        }
+    }
+}
+
+fn table<'b>(path: &str, value: Option<&'b Value>) -> Result<Option<&'b toml::value::Table>> {
+    match value {
+        None => Ok(None),
+        Some(Value::Table(t)) => Ok(Some(t)),
+        Some(_) => bail!("{}: Expected a table", path),
+    }
+}
+
+fn int(path: &str, value: Option<&Value>) -> Result<i128> {
+    let parsed = match value {
+        None => Some(0),
+        Some(Value::Integer(i)) => Some(*i as i128),
+        Some(Value::Boolean(b)) => Some(*b as i128),
+        Some(Value::String(s)) => match s.strip_prefix("0x") {
+            Some(hex) => i128::from_str_radix(hex, 16).ok(),
+            None => s.parse().ok(),
+        },
+        Some(_) => None,
+    };
+    parsed.ok_or_else(|| anyhow!("{}: Expected an integer", path))
+}
+
+fn enum_value(e: &ast::Enum, values: &[i128], path: &str, value: Option<&Value>) -> Result<i128> {
+    if let Some(Value::String(s)) = value {
+        let pos = e
+            .variants
+            .iter()
+            .position(|v| matches!(&v.ty, ExprType::Name(n) if n == s));
+        if let Some(pos) = pos {
+            return Ok(values[pos]);
+        }
+    }
+    int(path, value)
+}
+
+fn check_range(path: &str, value: i128, width_bits: u64, signed: bool) -> Result<()> {
+    let (min, max) = match (signed, width_bits) {
+        (_, 0) => (0, 0),
+        (true, 128) => (i128::MIN, i128::MAX),
+        (true, w) => (-(1 << (w - 1)), (1 << (w - 1)) - 1),
+        (false, 128) => (0, i128::MAX),
+        (false, w) => (0, (1 << w) - 1),
+    };
+    if value < min || value > max {
+        bail!("{}: The value {} is out of range", path, value);
+    }
+    Ok(())
+}
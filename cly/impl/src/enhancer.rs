@@ -2,7 +2,10 @@
 use crate::ast;
 use crate::ast::DeclarationType;
 use crate::converter::ConversionResult;
-use repc_impl::layout::{Annotation, Array, Record, RecordField, Type, TypeLayout, TypeVariant};
+use repc_impl::layout::{
+    Annotation, Array, Enum, Record, RecordField, RustEnum, RustStruct, Type, TypeLayout,
+    TypeVariant,
+};
 use repc_impl::util::BITS_PER_BYTE;
 
 pub fn enhance_declarations(d: &[ast::Declaration], m: &ConversionResult) -> Vec<ast::Declaration> {
@@ -11,7 +14,15 @@ pub fn enhance_declarations(d: &[ast::Declaration], m: &ConversionResult) -> Vec
         res.push(ast::Declaration {
             name: d.name.clone(),
             span: d.span,
+            lo: d.lo,
+            doc: d.doc.clone(),
+            params: d.params.clone(),
+            condition: d.condition.clone(),
             ty: match &d.ty {
+                // Generic declarations do not have a layout.
+                DeclarationType::Type(t) if !d.params.is_empty() => {
+                    DeclarationType::Type(t.clone())
+                }
                 DeclarationType::Type(t) => {
                     DeclarationType::Type(enhance_type(t, m.types.get(&d.name).unwrap()))
                 }
@@ -19,6 +30,7 @@ pub fn enhance_declarations(d: &[ast::Declaration], m: &ConversionResult) -> Vec
                     c,
                     *m.consts.get(&d.name).unwrap(),
                 )),
+                DeclarationType::Assert(a) => DeclarationType::Assert(a.clone()),
             },
         });
     }
@@ -39,11 +51,18 @@ fn enhance_type(t: &ast::Type, ty: &Type<TypeLayout>) -> ast::Type {
         (ast::TypeVariant::Enum(a), TypeVariant::Enum(ar)) => {
             ast::TypeVariant::Enum(enhance_enum(a, ar))
         }
+        (ast::TypeVariant::RustStruct(s), TypeVariant::RustStruct(sc)) => {
+            ast::TypeVariant::RustStruct(enhance_rust_struct(s, sc))
+        }
+        (ast::TypeVariant::RustEnum(e), TypeVariant::RustEnum(ec)) => {
+            ast::TypeVariant::RustEnum(enhance_rust_enum(e, ec))
+        }
         (_, _) => t.variant.clone(),
     };
     ast::Type {
         id: t.id,
         lo: t.lo,
+        hi: t.hi,
         layout: Some(ty.layout),
         layout_hi: t.layout_hi,
         annotations: enhance_annotations(&t.annotations, &ty.annotations),
@@ -54,15 +73,47 @@ fn enhance_type(t: &ast::Type, ty: &Type<TypeLayout>) -> ast::Type {
 fn enhance_record(r: &ast::Record, rc: &Record<TypeLayout>) -> ast::Record {
     ast::Record {
         kind: r.kind,
-        fields: r
-            .fields
+        fields: enhance_record_fields(&r.fields, &rc.fields),
+    }
+}
+
+fn enhance_rust_struct(s: &ast::RustStruct, sc: &RustStruct<TypeLayout>) -> ast::RustStruct {
+    ast::RustStruct {
+        repr: s.repr,
+        fields: enhance_record_fields(&s.fields, &sc.fields),
+    }
+}
+
+fn enhance_rust_enum(e: &ast::RustEnum, ec: &RustEnum<TypeLayout>) -> ast::RustEnum {
+    ast::RustEnum {
+        repr: e.repr,
+        variants: e
+            .variants
             .iter()
-            .zip(rc.fields.iter())
-            .map(|(f, fc)| enhance_record_field(f, fc))
+            .zip(ec.variants.iter())
+            .map(|(v, vc)| ast::RustEnumVariant {
+                name: v.name.clone(),
+                span: v.span,
+                fields: enhance_record_fields(&v.fields, &vc.fields),
+                discriminant: v
+                    .discriminant
+                    .as_ref()
+                    .map(|d| Box::new(enhance_top_level_expr(d, vc.discriminant))),
+            })
             .collect(),
     }
 }
 
+fn enhance_record_fields(
+    f: &[ast::RecordField],
+    fc: &[RecordField<TypeLayout>],
+) -> Vec<ast::RecordField> {
+    f.iter()
+        .zip(fc.iter())
+        .map(|(f, fc)| enhance_record_field(f, fc))
+        .collect()
+}
+
 fn enhance_record_field(f: &ast::RecordField, fc: &RecordField<TypeLayout>) -> ast::RecordField {
     let mut bit_width = f.bit_width.clone();
     if let (Some(bit_width), Some(bw)) = (&mut bit_width, fc.bit_width) {
@@ -73,7 +124,10 @@ fn enhance_record_field(f: &ast::RecordField, fc: &RecordField<TypeLayout>) -> a
         pos: f.pos,
         lo: f.lo,
         layout: fc.layout,
+        signed: fc.signed,
         layout_hi: f.layout_hi,
+        doc: f.doc.clone(),
+        condition: f.condition.clone(),
         annotations: enhance_annotations(&f.annotations, &fc.annotations),
         name: f.name.clone(),
         bit_width,
@@ -91,11 +145,17 @@ fn enhance_array(a: &ast::Array, ar: &Array<TypeLayout>) -> ast::Array {
     }
 }
 
-fn enhance_enum(a: &[ast::Expr], ar: &[i128]) -> Vec<ast::Expr> {
-    a.iter()
-        .zip(ar.iter())
-        .map(|(l, r)| enhance_top_level_expr(l, *r))
-        .collect()
+fn enhance_enum(a: &ast::Enum, ar: &Enum) -> ast::Enum {
+    ast::Enum {
+        fixed_underlying_type: a.fixed_underlying_type,
+        variants: a
+            .variants
+            .iter()
+            .zip(ar.variants.iter())
+            .map(|(l, r)| enhance_top_level_expr(l, *r))
+            .collect(),
+        underlying_type: ar.underlying_type,
+    }
 }
 
 fn enhance_annotations(a: &[ast::Annotation], an: &[Annotation]) -> Vec<ast::Annotation> {
@@ -112,6 +172,11 @@ fn enhance_annotation(a: &ast::Annotation, an: &Annotation) -> ast::Annotation {
             a.value = Some((*r / BITS_PER_BYTE) as i128);
             ast::Annotation::Aligned(Some(a))
         }
+        (ast::Annotation::Alignas(l), Annotation::Alignas(r)) => {
+            let mut a = l.clone();
+            a.value = Some((*r / BITS_PER_BYTE) as i128);
+            ast::Annotation::Alignas(a)
+        }
         (ast::Annotation::PragmaPack(l), Annotation::PragmaPack(r)) => {
             let mut a = l.clone();
             a.value = Some((*r / BITS_PER_BYTE) as i128);
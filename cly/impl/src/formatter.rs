@@ -0,0 +1,539 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//! Canonical formatting of cly files.
+//!
+//! The formatter prints the declarations of a file in a canonical form and preserves the
+//! comments of the file. Each comment is printed before the first item (import, declaration,
+//! field, or variant) that starts after it. Comments that follow code on the same line stay
+//! at the end of the line. Blank lines between items are collapsed into a single blank line.
+use crate::ast::{
+    Annotation, BinaryExprType, BuiltinExpr, Declaration, DeclarationType, Expr, ExprType, Import,
+    IndexType, OffsetofType, RecordField, RustEnumVariant, Span, TargetProperty, Type,
+    TypeArgument, TypeExprType, TypeVariant, UnaryExprType,
+};
+use crate::printer::{builtin_type_name, write_field_layout, write_type_layout};
+use repc_impl::layout::{RecordKind, RustEnumRepr, RustStructRepr};
+
+/// Formats a file consisting of `imports` and `declarations` whose comments are `comments`.
+pub fn format(
+    input: &str,
+    imports: &[Import],
+    declarations: &[Declaration],
+    comments: &[Span],
+) -> String {
+    let mut f = Formatter {
+        input,
+        comments,
+        out: String::new(),
+        indent: 0,
+        block_start: true,
+    };
+    let mut imports = imports.iter().peekable();
+    for d in declarations {
+        while let Some(i) = imports.next_if(|i| i.span.0 < d.lo) {
+            f.import(i);
+        }
+        f.declaration(d);
+    }
+    for i in imports {
+        f.import(i);
+    }
+    f.comments(input.len());
+    f.out
+}
+
+struct Formatter<'a> {
+    input: &'a str,
+    /// The comments that have not yet been printed.
+    comments: &'a [Span],
+    out: String,
+    indent: usize,
+    /// Whether nothing has been printed since the start of the file or of a block.
+    block_start: bool,
+}
+
+/// The precedence of conditional expressions. All binary operators bind more tightly.
+const CONDITIONAL: usize = 10;
+/// The precedence of expressions that never have to be parenthesized.
+const ATOMIC: usize = 100;
+
+impl<'a> Formatter<'a> {
+    fn import(&mut self, i: &Import) {
+        self.item(i.span.0);
+        self.out.push_str("import \"");
+        self.out.push_str(&i.path);
+        self.out.push('"');
+        if let Some(ns) = &i.namespace {
+            self.out.push_str(" as ");
+            self.out.push_str(ns);
+        }
+        self.line_end();
+    }
+
+    fn declaration(&mut self, d: &Declaration) {
+        self.item(d.lo);
+        self.condition(&d.condition);
+        match &d.ty {
+            DeclarationType::Type(t) => {
+                self.out.push_str(&d.name);
+                if !d.params.is_empty() {
+                    self.out.push('(');
+                    self.out.push_str(&d.params.join(", "));
+                    self.out.push(')');
+                }
+                self.out.push_str(" = ");
+                self.ty(t);
+            }
+            DeclarationType::Const(e) => {
+                self.out.push_str("const ");
+                self.out.push_str(&d.name);
+                self.out.push_str(" = ");
+                self.top_level_expr(e);
+            }
+            DeclarationType::Assert(a) => {
+                self.out.push_str("assert ");
+                self.expr(&a.expr, 0);
+                if let Some(msg) = &a.message {
+                    self.out.push_str(", \"");
+                    self.out.push_str(msg);
+                    self.out.push('"');
+                }
+            }
+        }
+        self.line_end();
+    }
+
+    fn condition(&mut self, condition: &Option<Box<Expr>>) {
+        if let Some(c) = condition {
+            self.out.push_str("@if(");
+            self.expr(c, 0);
+            self.out.push_str(") ");
+        }
+    }
+
+    fn ty(&mut self, t: &Type) {
+        write_type_layout(&mut self.out, t).unwrap();
+        self.annotations(&t.annotations);
+        match &t.variant {
+            TypeVariant::Builtin(bi) => self.out.push_str(builtin_type_name(*bi)),
+            TypeVariant::Record(r) => {
+                self.out.push_str(match r.kind {
+                    RecordKind::Struct => "struct ",
+                    RecordKind::Union => "union ",
+                });
+                self.fields(&r.fields, t.hi);
+            }
+            TypeVariant::Typedef(t) => {
+                self.out.push_str("typedef ");
+                self.ty(t);
+            }
+            TypeVariant::Array(a) => {
+                self.out.push('[');
+                if let Some(n) = &a.num_elements {
+                    self.top_level_expr(n);
+                }
+                self.out.push(']');
+                self.ty(&a.element_type);
+            }
+            TypeVariant::Opaque(o) => {
+                self.out.push_str("opaque { size: ");
+                self.expr(&o.size_bits, 0);
+                let field = self.expr_to_string(&o.field_alignment_bits);
+                let pointer = self.expr_to_string(&o.pointer_alignment_bits);
+                if field == pointer {
+                    self.out.push_str(", alignment: ");
+                    self.out.push_str(&field);
+                } else {
+                    self.out.push_str(", field_alignment: ");
+                    self.out.push_str(&field);
+                    self.out.push_str(", pointer_alignment: ");
+                    self.out.push_str(&pointer);
+                }
+                // The required alignment defaults to 8 bits.
+                if o.required_alignment_bits.ty != ExprType::Lit(8) {
+                    self.out.push_str(", required_alignment: ");
+                    self.expr(&o.required_alignment_bits, 0);
+                }
+                self.out.push_str(" }");
+            }
+            TypeVariant::Name(name, args, _) => {
+                self.out.push_str(name);
+                if !args.is_empty() {
+                    self.out.push('(');
+                    for (i, a) in args.iter().enumerate() {
+                        if i > 0 {
+                            self.out.push_str(", ");
+                        }
+                        match a {
+                            TypeArgument::Type(t) => self.ty(t),
+                            TypeArgument::Expr(e) => self.expr(e, 0),
+                        }
+                    }
+                    self.out.push(')');
+                }
+            }
+            TypeVariant::Enum(e) => {
+                match e.fixed_underlying_type {
+                    Some(bi) => {
+                        self.out.push_str("enum: ");
+                        self.out.push_str(builtin_type_name(bi));
+                        self.out.push(' ');
+                    }
+                    None => self.out.push_str("enum "),
+                }
+                self.block(&e.variants, t.hi, |f, v| {
+                    f.item(v.span.0);
+                    f.top_level_expr(v);
+                    f.out.push(',');
+                    f.line_end();
+                });
+            }
+            TypeVariant::RustStruct(s) => {
+                self.out.push_str(match s.repr {
+                    RustStructRepr::C => "repr(C) struct ",
+                    RustStructRepr::Transparent => "repr(transparent) struct ",
+                });
+                self.fields(&s.fields, t.hi);
+            }
+            TypeVariant::RustEnum(e) => {
+                self.out.push_str("repr(");
+                match e.repr {
+                    RustEnumRepr::C => self.out.push('C'),
+                    RustEnumRepr::Int(bi) => self.out.push_str(builtin_type_name(bi)),
+                    RustEnumRepr::CInt(bi) => {
+                        self.out.push_str("C, ");
+                        self.out.push_str(builtin_type_name(bi));
+                    }
+                    RustEnumRepr::Rust => self.out.push_str("Rust"),
+                }
+                self.out.push_str(") enum ");
+                self.block(&e.variants, t.hi, |f, v| f.rust_enum_variant(v));
+            }
+        }
+    }
+
+    fn annotations(&mut self, annotations: &[Annotation]) {
+        let mut annotations: Vec<_> = annotations.iter().collect();
+        annotations.sort_by_key(|a| match a {
+            Annotation::PragmaPack(_) => 0,
+            Annotation::AttrPacked => 1,
+            Annotation::Aligned(_) => 2,
+            Annotation::Alignas(_) => 3,
+        });
+        for a in annotations {
+            let (name, arg) = match a {
+                Annotation::PragmaPack(e) => ("@pragma_pack", Some(e)),
+                Annotation::AttrPacked => ("@attr_packed", None),
+                Annotation::Aligned(e) => ("@align", e.as_ref()),
+                Annotation::Alignas(e) => ("@alignas", Some(e)),
+            };
+            self.out.push_str(name);
+            if let Some(e) = arg {
+                self.out.push('(');
+                self.top_level_expr(e);
+                self.out.push(')');
+            }
+            self.out.push(' ');
+        }
+    }
+
+    fn fields(&mut self, fields: &[RecordField], hi: usize) {
+        self.block(fields, hi, |f, field| {
+            f.item(field.lo);
+            f.field(field);
+            f.out.push(',');
+            f.line_end();
+        });
+    }
+
+    fn field(&mut self, f: &RecordField) {
+        write_field_layout(&mut self.out, f).unwrap();
+        self.condition(&f.condition);
+        self.annotations(&f.annotations);
+        self.out.push_str(f.name.as_deref().unwrap_or("_"));
+        self.out.push(' ');
+        self.ty(&f.ty);
+        if let Some(bw) = &f.bit_width {
+            self.out.push(':');
+            self.top_level_expr(bw);
+        }
+    }
+
+    fn rust_enum_variant(&mut self, v: &RustEnumVariant) {
+        self.item(v.span.0);
+        self.out.push_str(&v.name);
+        if !v.fields.is_empty() {
+            // The fields of a variant are printed on a single line unless the variant
+            // contains comments or a field spans multiple lines.
+            let len = self.out.len();
+            let mut inline = !self.has_comment_before(v.span.1);
+            if inline {
+                self.out.push_str(" { ");
+                for (i, field) in v.fields.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.field(field);
+                }
+                self.out.push_str(" }");
+                inline = !self.out[len..].contains('\n');
+            }
+            if !inline {
+                self.out.truncate(len);
+                self.out.push(' ');
+                let hi = match &v.discriminant {
+                    Some(d) => d.span.0,
+                    None => v.span.1,
+                };
+                self.fields(&v.fields, hi);
+            }
+        }
+        if let Some(d) = &v.discriminant {
+            self.out.push_str(" = ");
+            self.top_level_expr(d);
+        }
+        self.out.push(',');
+        self.line_end();
+    }
+
+    /// Prints a brace-delimited list of items that ends at `hi`.
+    fn block<T>(&mut self, items: &[T], hi: usize, mut item: impl FnMut(&mut Self, &T)) {
+        if items.is_empty() && !self.has_comment_before(hi) {
+            self.out.push_str("{ }");
+            return;
+        }
+        self.out.push('{');
+        self.line_end();
+        self.block_start = true;
+        self.indent += 1;
+        for i in items {
+            item(self, i);
+        }
+        self.comments(hi);
+        self.indent -= 1;
+        self.write_indent();
+        self.out.push('}');
+    }
+
+    fn top_level_expr(&mut self, e: &Expr) {
+        if let (Some(v), false) = (e.value, matches!(e.ty, ExprType::Lit(_))) {
+            self.out.push_str(&format!("{{{}}}", v));
+        }
+        self.expr(e, 0);
+    }
+
+    fn expr_to_string(&mut self, e: &Expr) -> String {
+        let len = self.out.len();
+        self.expr(e, 0);
+        self.out.split_off(len)
+    }
+
+    /// Prints an expression. The expression is parenthesized if it binds less tightly than
+    /// `precedence`.
+    fn expr(&mut self, e: &Expr, precedence: usize) {
+        match &e.ty {
+            ExprType::Lit(v) => self.literal(e, *v),
+            ExprType::Unary(op, inner) => {
+                self.out.push(match op {
+                    UnaryExprType::Neg => '-',
+                    UnaryExprType::Not => '!',
+                    UnaryExprType::BitNot => '~',
+                });
+                self.expr(inner, ATOMIC);
+            }
+            ExprType::Binary(
+                op @ (BinaryExprType::Min | BinaryExprType::Max | BinaryExprType::AlignUp),
+                l,
+                r,
+            ) => {
+                self.out.push_str(match op {
+                    BinaryExprType::Min => "min(",
+                    BinaryExprType::Max => "max(",
+                    _ => "align_up(",
+                });
+                self.expr(l, 0);
+                self.out.push_str(", ");
+                self.expr(r, 0);
+                self.out.push(')');
+            }
+            ExprType::Binary(op, l, r) => {
+                let (symbol, p) = binary_operator(*op);
+                let parens = p < precedence;
+                if parens {
+                    self.out.push('(');
+                }
+                // Binary operators are left-associative.
+                self.expr(l, p);
+                self.out.push(' ');
+                self.out.push_str(symbol);
+                self.out.push(' ');
+                self.expr(r, p + 1);
+                if parens {
+                    self.out.push(')');
+                }
+            }
+            ExprType::Conditional(c, t, e) => {
+                let parens = CONDITIONAL < precedence;
+                if parens {
+                    self.out.push('(');
+                }
+                self.expr(c, CONDITIONAL + 1);
+                self.out.push_str(" ? ");
+                self.expr(t, 0);
+                self.out.push_str(" : ");
+                self.expr(e, 0);
+                if parens {
+                    self.out.push(')');
+                }
+            }
+            ExprType::TypeExpr(kind, t) => {
+                self.out.push_str(match kind {
+                    TypeExprType::Sizeof => "sizeof(",
+                    TypeExprType::SizeofBits => "sizeof_bits(",
+                    TypeExprType::Alignof => "alignof(",
+                    TypeExprType::AlignofBits => "alignof_bits(",
+                    TypeExprType::PointerAlignof => "pointer_alignof(",
+                    TypeExprType::PointerAlignofBits => "pointer_alignof_bits(",
+                    TypeExprType::RequiredAlignof => "required_alignof(",
+                    TypeExprType::RequiredAlignofBits => "required_alignof_bits(",
+                });
+                self.ty(t);
+                self.out.push(')');
+            }
+            ExprType::Builtin(b) => self.out.push_str(match b {
+                BuiltinExpr::BitsPerByte => "BITS_PER_BYTE",
+                BuiltinExpr::TargetPointerWidth => "TARGET_POINTER_WIDTH",
+            }),
+            ExprType::Name(name) => self.out.push_str(name),
+            ExprType::Offsetof(kind, t, indices) => {
+                self.out.push_str(match kind {
+                    OffsetofType::Bytes => "offsetof(",
+                    OffsetofType::Bits => "offsetof_bits(",
+                });
+                self.ty(t);
+                self.out.push_str(", ");
+                for (i, index) in indices.iter().enumerate() {
+                    match &index.ty {
+                        IndexType::Field(name) => {
+                            if i > 0 {
+                                self.out.push('.');
+                            }
+                            self.out.push_str(name);
+                        }
+                        IndexType::Array(e) => {
+                            self.out.push('[');
+                            self.top_level_expr(e);
+                            self.out.push(']');
+                        }
+                    }
+                }
+                self.out.push(')');
+            }
+            ExprType::Target(property, value) => {
+                self.out.push_str(match property {
+                    TargetProperty::Name => "target_name(\"",
+                    TargetProperty::Compiler => "target_compiler(\"",
+                    TargetProperty::Os => "target_os(\"",
+                    TargetProperty::Endian => "target_endian(\"",
+                });
+                self.out.push_str(value);
+                self.out.push_str("\")");
+            }
+        }
+    }
+
+    /// Prints an integer literal. Literals keep the base and digit separators of the input.
+    fn literal(&mut self, e: &Expr, v: i128) {
+        // The span of a top-level expression includes its value and the span of a
+        // parenthesized literal includes the parentheses.
+        let text = self.input[e.span.0.max(e.value_hi)..e.span.1]
+            .trim_matches(|c: char| c == '(' || c == ')' || c.is_whitespace());
+        let (digits, base) = match text.get(..2) {
+            Some("0x") => (&text[2..], 16),
+            Some("0o") => (&text[2..], 8),
+            Some("0b") => (&text[2..], 2),
+            _ => (text, 10),
+        };
+        match i128::from_str_radix(&digits.replace('_', ""), base) {
+            Ok(n) if n == v => self.out.push_str(text),
+            _ => self.out.push_str(&v.to_string()),
+        }
+    }
+
+    /// Prints the comments before `pos` and starts a new line for an item at `pos`.
+    fn item(&mut self, pos: usize) {
+        self.comments(pos);
+        self.blank_line(pos);
+        self.write_indent();
+    }
+
+    /// Prints the comments before `pos`.
+    fn comments(&mut self, pos: usize) {
+        while let Some((c, rest)) = self.comments.split_first() {
+            if c.0 >= pos {
+                break;
+            }
+            self.comments = rest;
+            let text = self.input[c.0..c.1].trim_end();
+            let line_start = self.input[..c.0].rfind('\n').map(|i| i + 1).unwrap_or(0);
+            let trailing = !self.input[line_start..c.0].trim().is_empty();
+            if trailing && self.out.ends_with('\n') {
+                self.out.pop();
+                self.out.push(' ');
+            } else {
+                self.blank_line(c.0);
+                self.write_indent();
+            }
+            self.out.push_str(text);
+            self.line_end();
+        }
+    }
+
+    fn has_comment_before(&self, pos: usize) -> bool {
+        self.comments.first().map(|c| c.0 < pos).unwrap_or(false)
+    }
+
+    /// Prints a blank line if the input contains a blank line before `pos`.
+    fn blank_line(&mut self, pos: usize) {
+        let before = &self.input[..pos];
+        let whitespace = &before[before.trim_end().len()..];
+        if !self.block_start && whitespace.matches('\n').count() > 1 {
+            self.out.push('\n');
+        }
+    }
+
+    fn write_indent(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+    }
+
+    fn line_end(&mut self) {
+        self.out.push('\n');
+        self.block_start = false;
+    }
+}
+
+/// Returns the symbol and the precedence of a binary operator.
+fn binary_operator(op: BinaryExprType) -> (&'static str, usize) {
+    match op {
+        BinaryExprType::Mul => ("*", 90),
+        BinaryExprType::Div => ("/", 90),
+        BinaryExprType::Mod => ("%", 90),
+        BinaryExprType::Add => ("+", 80),
+        BinaryExprType::Sub => ("-", 80),
+        BinaryExprType::Shl => ("<<", 75),
+        BinaryExprType::Shr => (">>", 75),
+        BinaryExprType::Eq => ("==", 70),
+        BinaryExprType::NotEq => ("!=", 70),
+        BinaryExprType::Lt => ("<", 70),
+        BinaryExprType::Le => ("<=", 70),
+        BinaryExprType::Gt => (">", 70),
+        BinaryExprType::Ge => (">=", 70),
+        BinaryExprType::BitAnd => ("&", 66),
+        BinaryExprType::BitXor => ("^", 64),
+        BinaryExprType::BitOr => ("|", 62),
+        BinaryExprType::LogicalAnd => ("&&", 60),
+        BinaryExprType::LogicalOr => ("||", 50),
+        BinaryExprType::Min | BinaryExprType::Max | BinaryExprType::AlignUp => ("", ATOMIC),
+    }
+}
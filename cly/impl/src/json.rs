@@ -0,0 +1,316 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//! A minimal JSON implementation for the messages of the language server.
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::fmt::{Display, Formatter, Write};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// An object. The members are kept in the order in which they were written.
+    Object(Vec<(String, Json)>),
+}
+
+static NULL: Json = Json::Null;
+
+impl Json {
+    pub fn parse(s: &str) -> Result<Json> {
+        let mut parser = Parser {
+            s: s.as_bytes(),
+            pos: 0,
+        };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.pos != parser.s.len() {
+            return Err(anyhow!("Trailing characters after JSON value"));
+        }
+        Ok(value)
+    }
+
+    /// Returns the member `key` of an object or `null` if there is no such member.
+    pub fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|m| m.0 == key)
+                .map(|m| &m.1)
+                .unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Json::Number(n) if n >= 0.0 && n.fract() == 0.0 => Some(n as u64),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == Json::Null
+    }
+}
+
+/// Creates an object from its members.
+pub fn object(members: Vec<(&str, Json)>) -> Json {
+    Json::Object(
+        members
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+    )
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Json::String(s)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Json::Bool(b)
+    }
+}
+
+impl From<u64> for Json {
+    fn from(n: u64) -> Self {
+        Json::Number(n as f64)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(v: Vec<Json>) -> Self {
+        Json::Array(v)
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write_string(f, s),
+            Json::Array(v) => {
+                f.write_char('[')?;
+                for (i, v) in v.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(members) => {
+                f.write_char('{')?;
+                for (i, (k, v)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.s.len() && matches!(self.s[self.pos], b' ' | b'\t' | b'\n' | b'\r') {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Result<u8> {
+        self.skip_whitespace();
+        match self.s.get(self.pos) {
+            Some(&c) => Ok(c),
+            None => Err(anyhow!("Unexpected end of JSON input")),
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<()> {
+        if self.peek()? != c {
+            return Err(anyhow!(
+                "Expected `{}` at offset {} of JSON input",
+                c as char,
+                self.pos
+            ));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn parse_value(&mut self) -> Result<Json> {
+        match self.peek()? {
+            b'{' => {
+                self.pos += 1;
+                let mut members = vec![];
+                if self.peek()? == b'}' {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    let key = self.parse_string()?;
+                    self.expect(b':')?;
+                    members.push((key, self.parse_value()?));
+                    match self.peek()? {
+                        b',' => self.pos += 1,
+                        _ => {
+                            self.expect(b'}')?;
+                            return Ok(Json::Object(members));
+                        }
+                    }
+                }
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut values = vec![];
+                if self.peek()? == b']' {
+                    self.pos += 1;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.parse_value()?);
+                    match self.peek()? {
+                        b',' => self.pos += 1,
+                        _ => {
+                            self.expect(b']')?;
+                            return Ok(Json::Array(values));
+                        }
+                    }
+                }
+            }
+            b'"' => Ok(Json::String(self.parse_string()?)),
+            b't' => self.parse_literal("true", Json::Bool(true)),
+            b'f' => self.parse_literal("false", Json::Bool(false)),
+            b'n' => self.parse_literal("null", Json::Null),
+            _ => self.parse_number(),
+        }
+    }
+
+    fn parse_literal(&mut self, lit: &str, value: Json) -> Result<Json> {
+        if !self.s[self.pos..].starts_with(lit.as_bytes()) {
+            return Err(anyhow!("Invalid JSON literal at offset {}", self.pos));
+        }
+        self.pos += lit.len();
+        Ok(value)
+    }
+
+    fn parse_number(&mut self) -> Result<Json> {
+        let start = self.pos;
+        while self.pos < self.s.len()
+            && matches!(
+                self.s[self.pos],
+                b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E'
+            )
+        {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.s[start..self.pos])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(Json::Number)
+            .ok_or_else(|| anyhow!("Invalid JSON value at offset {}", start))
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+        self.expect(b'"')?;
+        let mut res = vec![];
+        loop {
+            let c = match self.s.get(self.pos) {
+                Some(&c) => c,
+                None => return Err(anyhow!("Unterminated JSON string")),
+            };
+            self.pos += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let e = self.s.get(self.pos).copied();
+                    self.pos += 1;
+                    let c = match e {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => self.parse_unicode_escape()?,
+                        _ => return Err(anyhow!("Invalid escape sequence in JSON string")),
+                    };
+                    res.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                c => res.push(c),
+            }
+        }
+        String::from_utf8(res).map_err(|_| anyhow!("Invalid UTF-8 in JSON string"))
+    }
+
+    fn parse_utf16_unit(&mut self) -> Result<u32> {
+        let unit = self
+            .s
+            .get(self.pos..self.pos + 4)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u32::from_str_radix(h, 16).ok())
+            .ok_or_else(|| anyhow!("Invalid unicode escape in JSON string"))?;
+        self.pos += 4;
+        Ok(unit)
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char> {
+        let hi = self.parse_utf16_unit()?;
+        // Characters outside of the basic multilingual plane are escaped as surrogate pairs.
+        let c = if (0xd800..0xdc00).contains(&hi) && self.s[self.pos..].starts_with(b"\\u") {
+            self.pos += 2;
+            let lo = self.parse_utf16_unit()?;
+            0x10000 + ((hi - 0xd800) << 10) + (lo.wrapping_sub(0xdc00) & 0x3ff)
+        } else {
+            hi
+        };
+        char::from_u32(c).ok_or_else(|| anyhow!("Invalid unicode escape in JSON string"))
+    }
+}
@@ -8,18 +8,35 @@ use std::fmt::{Display, Formatter};
 pub enum Token {
     Ident(usize),
     Number(i128),
+    String(usize),
     Const,
+    Import,
+    If,
+    Assert,
     Typedef,
     Unnamed,
     BitsPerByte,
+    TargetPointerWidth,
+    TargetName,
+    TargetCompiler,
+    TargetOs,
+    TargetEndian,
     PragmaPack,
     AttrPacked,
     Align,
+    Alignas,
     Sizeof,
     SizeofBits,
+    Alignof,
+    AlignofBits,
+    PointerAlignof,
+    PointerAlignofBits,
+    RequiredAlignof,
+    RequiredAlignofBits,
     OffsetOf,
     OffsetOfBits,
     Opaque,
+    Repr,
     Enum,
     Struct,
     Union,
@@ -69,6 +86,13 @@ pub enum Token {
     Not,
     OrOr,
     AndAnd,
+    Or,
+    And,
+    Caret,
+    Tilde,
+    Shl,
+    Shr,
+    Question,
     At,
     Colon,
 }
@@ -78,18 +102,35 @@ impl Display for Token {
         let s = match self {
             Token::Ident(_) => return f.write_str("identifier"),
             Token::Number(_) => return f.write_str("integer literal"),
+            Token::String(_) => return f.write_str("string literal"),
             Token::Const => "const",
+            Token::Import => "import",
+            Token::If => "if",
+            Token::Assert => "assert",
             Token::Typedef => "typedef",
             Token::Unnamed => "_",
             Token::BitsPerByte => "BITS_PER_BYTE",
+            Token::TargetPointerWidth => "TARGET_POINTER_WIDTH",
+            Token::TargetName => "target_name",
+            Token::TargetCompiler => "target_compiler",
+            Token::TargetOs => "target_os",
+            Token::TargetEndian => "target_endian",
             Token::PragmaPack => "pragma_pack",
             Token::AttrPacked => "attr_packed",
             Token::Align => "align",
+            Token::Alignas => "alignas",
             Token::Sizeof => "sizeof",
             Token::SizeofBits => "sizeof_bits",
+            Token::Alignof => "alignof",
+            Token::AlignofBits => "alignof_bits",
+            Token::PointerAlignof => "pointer_alignof",
+            Token::PointerAlignofBits => "pointer_alignof_bits",
+            Token::RequiredAlignof => "required_alignof",
+            Token::RequiredAlignofBits => "required_alignof_bits",
             Token::OffsetOf => "offsetof",
             Token::OffsetOfBits => "offsetof_bits",
             Token::Opaque => "opaque",
+            Token::Repr => "repr",
             Token::Enum => "enum",
             Token::Struct => "struct",
             Token::Union => "union",
@@ -139,6 +180,13 @@ impl Display for Token {
             Token::Not => "!",
             Token::OrOr => "||",
             Token::AndAnd => "&&",
+            Token::Or => "|",
+            Token::And => "&",
+            Token::Caret => "^",
+            Token::Tilde => "~",
+            Token::Shl => "<<",
+            Token::Shr => ">>",
+            Token::Question => "?",
             Token::At => "@",
             Token::Colon => ":",
         };
@@ -158,35 +206,99 @@ pub struct Spanned<T> {
     pub val: T,
 }
 
-pub fn lex(chars: &[u8]) -> ParseResult<(Vec<Spanned<Token>>, Vec<String>)> {
-    Lexer::new(chars).lex()
+/// The tokens, the contents of identifiers and strings, and the spans of the comments of
+/// an input.
+pub type Lexed = (Vec<Spanned<Token>>, Vec<String>, Vec<Span>);
+
+/// Lexes `chars[pos..]`. The spans of the tokens are offsets into `chars`.
+pub fn lex(chars: &[u8], pos: usize) -> ParseResult<Lexed> {
+    Lexer::new(chars, pos).lex()
 }
 
+/// The keywords of the language and their tokens.
+pub(crate) const KEYWORDS: &[(&str, Token)] = &[
+    ("const", Token::Const),
+    ("import", Token::Import),
+    ("if", Token::If),
+    ("assert", Token::Assert),
+    ("typedef", Token::Typedef),
+    ("BITS_PER_BYTE", Token::BitsPerByte),
+    ("TARGET_POINTER_WIDTH", Token::TargetPointerWidth),
+    ("target_name", Token::TargetName),
+    ("target_compiler", Token::TargetCompiler),
+    ("target_os", Token::TargetOs),
+    ("target_endian", Token::TargetEndian),
+    ("pragma_pack", Token::PragmaPack),
+    ("attr_packed", Token::AttrPacked),
+    ("align", Token::Align),
+    ("alignas", Token::Alignas),
+    ("sizeof", Token::Sizeof),
+    ("sizeof_bits", Token::SizeofBits),
+    ("alignof", Token::Alignof),
+    ("alignof_bits", Token::AlignofBits),
+    ("pointer_alignof", Token::PointerAlignof),
+    ("pointer_alignof_bits", Token::PointerAlignofBits),
+    ("required_alignof", Token::RequiredAlignof),
+    ("required_alignof_bits", Token::RequiredAlignofBits),
+    ("offsetof", Token::OffsetOf),
+    ("offsetof_bits", Token::OffsetOfBits),
+    ("opaque", Token::Opaque),
+    ("repr", Token::Repr),
+    ("enum", Token::Enum),
+    ("struct", Token::Struct),
+    ("union", Token::Union),
+    ("unit", Token::Unit),
+    ("bool", Token::Bool),
+    ("u8", Token::U8),
+    ("i8", Token::I8),
+    ("u16", Token::U16),
+    ("i16", Token::I16),
+    ("u32", Token::U32),
+    ("i32", Token::I32),
+    ("u64", Token::U64),
+    ("i64", Token::I64),
+    ("u128", Token::U128),
+    ("i128", Token::I128),
+    ("char", Token::Char),
+    ("signed", Token::Signed),
+    ("unsigned", Token::Unsigned),
+    ("short", Token::Short),
+    ("int", Token::Int),
+    ("long", Token::Long),
+    ("f32", Token::F32),
+    ("f64", Token::F64),
+    ("float", Token::Float),
+    ("double", Token::Double),
+    ("ptr", Token::Ptr),
+];
+
 struct Lexer<'a> {
     pos: usize,
     chars: &'a [u8],
     strings: Vec<String>,
+    comments: Vec<Span>,
 }
 
 impl<'a> Lexer<'a> {
-    fn new(chars: &'a [u8]) -> Self {
+    fn new(chars: &'a [u8], pos: usize) -> Self {
         Self {
-            pos: 0,
+            pos,
             chars,
             strings: vec![],
+            comments: vec![],
         }
     }
 
-    fn lex(mut self) -> ParseResult<(Vec<Spanned<Token>>, Vec<String>)> {
+    fn lex(mut self) -> ParseResult<Lexed> {
         let mut tokens = vec![];
         while let Some(token) = self.lex_one()? {
             tokens.push(token);
         }
-        Ok((tokens, self.strings))
+        Ok((tokens, self.strings, self.comments))
     }
 
     fn lex_one(&mut self) -> ParseResult<Option<Spanned<Token>>> {
-        self.skip_whitespace();
+        self.skip_whitespace()?;
         if self.pos == self.chars.len() {
             return Ok(None);
         }
@@ -204,6 +316,8 @@ impl<'a> Lexer<'a> {
                 (b'>', b'=') => Some(Token::Ge),
                 (b'|', b'|') => Some(Token::OrOr),
                 (b'&', b'&') => Some(Token::AndAnd),
+                (b'<', b'<') => Some(Token::Shl),
+                (b'>', b'>') => Some(Token::Shr),
                 _ => None,
             };
             if let Some(token) = token {
@@ -231,6 +345,11 @@ impl<'a> Lexer<'a> {
             b'/' => Some(Token::Div),
             b'%' => Some(Token::Mod),
             b'!' => Some(Token::Not),
+            b'|' => Some(Token::Or),
+            b'&' => Some(Token::And),
+            b'^' => Some(Token::Caret),
+            b'~' => Some(Token::Tilde),
+            b'?' => Some(Token::Question),
             b'@' => Some(Token::At),
             b':' => Some(Token::Colon),
             b'_' => {
@@ -246,6 +365,10 @@ impl<'a> Lexer<'a> {
             return Ok(Some(token.spanned(span)));
         }
 
+        if cur == b'"' {
+            return self.lex_string(span).map(Some);
+        }
+
         if !is_ident_cont(cur) {
             return Err(ParseError {
                 msg: format!("Unknown symbol {:?}", cur as char),
@@ -319,46 +442,7 @@ impl<'a> Lexer<'a> {
 
         let ident = unsafe { String::from_utf8_unchecked(ident) };
 
-        let keyword = match &*ident {
-            "const" => Some(Token::Const),
-            "typedef" => Some(Token::Typedef),
-            "BITS_PER_BYTE" => Some(Token::BitsPerByte),
-            "pragma_pack" => Some(Token::PragmaPack),
-            "attr_packed" => Some(Token::AttrPacked),
-            "align" => Some(Token::Align),
-            "sizeof" => Some(Token::Sizeof),
-            "sizeof_bits" => Some(Token::SizeofBits),
-            "offsetof" => Some(Token::OffsetOf),
-            "offsetof_bits" => Some(Token::OffsetOfBits),
-            "opaque" => Some(Token::Opaque),
-            "enum" => Some(Token::Enum),
-            "struct" => Some(Token::Struct),
-            "union" => Some(Token::Union),
-            "unit" => Some(Token::Unit),
-            "bool" => Some(Token::Bool),
-            "u8" => Some(Token::U8),
-            "i8" => Some(Token::I8),
-            "u16" => Some(Token::U16),
-            "i16" => Some(Token::I16),
-            "u32" => Some(Token::U32),
-            "i32" => Some(Token::I32),
-            "u64" => Some(Token::U64),
-            "i64" => Some(Token::I64),
-            "u128" => Some(Token::U128),
-            "i128" => Some(Token::I128),
-            "char" => Some(Token::Char),
-            "signed" => Some(Token::Signed),
-            "unsigned" => Some(Token::Unsigned),
-            "short" => Some(Token::Short),
-            "int" => Some(Token::Int),
-            "long" => Some(Token::Long),
-            "f32" => Some(Token::F32),
-            "f64" => Some(Token::F64),
-            "float" => Some(Token::Float),
-            "double" => Some(Token::Double),
-            "ptr" => Some(Token::Ptr),
-            _ => None,
-        };
+        let keyword = KEYWORDS.iter().find(|k| k.0 == ident).map(|k| k.1);
 
         let token = match keyword {
             Some(k) => k,
@@ -371,19 +455,54 @@ impl<'a> Lexer<'a> {
         Ok(Some(token.spanned(span)))
     }
 
-    fn skip_whitespace(&mut self) {
+    fn lex_string(&mut self, mut span: Span) -> ParseResult<Spanned<Token>> {
+        let lo = self.pos;
+        while self.pos < self.chars.len() && !matches!(self.chars[self.pos], b'"' | b'\n') {
+            self.pos += 1;
+        }
+        span.1 = self.pos;
+        if self.pos == self.chars.len() || self.chars[self.pos] != b'"' {
+            return Err(ParseError {
+                msg: "Unterminated string literal".to_string(),
+                span,
+            });
+        }
+        self.pos += 1;
+        span.1 += 1;
+        let s = String::from_utf8_lossy(&self.chars[lo..self.pos - 1]).into_owned();
+        self.strings.push(s);
+        Ok(Token::String(self.strings.len() - 1).spanned(span))
+    }
+
+    fn skip_whitespace(&mut self) -> ParseResult<()> {
         while self.pos < self.chars.len() {
-            match self.chars[self.pos] {
-                b' ' | b'\t' | b'\n' | b'\r' => self.pos += 1,
-                b'/' if self.pos + 1 < self.chars.len() && self.chars[self.pos + 1] == b'/' => {
+            let lo = self.pos;
+            match (self.chars[self.pos], self.chars.get(self.pos + 1)) {
+                (b' ' | b'\t' | b'\n' | b'\r', _) => self.pos += 1,
+                (b'/', Some(b'/')) => {
                     self.pos += 2;
                     while self.pos < self.chars.len() && self.chars[self.pos] != b'\n' {
                         self.pos += 1;
                     }
+                    self.comments.push(Span(lo, self.pos));
+                }
+                // Block comments do not nest.
+                (b'/', Some(b'*')) => {
+                    match self.chars[lo + 2..].windows(2).position(|w| w == b"*/") {
+                        Some(len) => self.pos = lo + 2 + len + 2,
+                        None => {
+                            return Err(ParseError {
+                                msg: "Unterminated block comment".to_string(),
+                                span: Span(lo, lo + 2),
+                            })
+                        }
+                    }
+                    self.comments.push(Span(lo, self.pos));
                 }
-                _ => return,
+                _ => break,
             }
         }
+        Ok(())
     }
 }
 
@@ -1,18 +1,36 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 use crate::ast::{Declaration, Span};
+use crate::loader::SourceFile;
 use anyhow::{anyhow, Result};
-pub use converter::{compute_layouts, extract_layouts};
+pub use converter::{
+    compute_layouts, compute_layouts_with_warnings, compute_project_layouts, evaluate_conditions,
+    extract_layouts,
+};
+pub use decoder::{decode, DecodedField, DecodedValue};
+pub use doc::{document, DocFormat};
+pub use encoder::{encode, parse_values};
 pub use enhancer::enhance_declarations;
-pub use printer::{printer, Printer};
+pub use loader::{load, load_str, Project};
+pub use printer::{find_stale_layouts, printer, Printer, StaleLine};
+pub use result::{Label, SourceError};
 use std::fmt;
 use std::fmt::{Display, Formatter};
+use std::path::Path;
 
 pub mod ast;
 pub mod converter;
+mod decoder;
+mod doc;
+mod encoder;
 mod enhancer;
+mod formatter;
+mod json;
 mod lexer;
+pub mod loader;
+pub mod lsp;
 mod parser;
 mod printer;
+pub mod repl;
 mod result;
 #[cfg(test)]
 mod tests;
@@ -24,6 +42,24 @@ pub fn parse(input: &str) -> Result<Vec<Declaration>> {
     }
 }
 
+/// Formats a file in the canonical form. Comments are preserved.
+pub fn format(input: &str) -> Result<String> {
+    match parser::parse_file(input.as_bytes(), 0) {
+        Ok((imports, declarations, comments)) => {
+            Ok(formatter::format(input, &imports, &declarations, &comments))
+        }
+        Err(e) => Err(anyhow!("At {}: {}", to_span(input, e.span), e.msg)),
+    }
+}
+
+/// Removes the layouts of types and fields and the values of expressions from a file.
+pub fn strip(input: &str) -> Result<String> {
+    match parser::parse_file(input.as_bytes(), 0) {
+        Ok((_, declarations, _)) => Ok(printer::stripper(input, &declarations).to_string()),
+        Err(e) => Err(anyhow!("At {}: {}", to_span(input, e.span), e.msg)),
+    }
+}
+
 struct LC(usize, usize);
 
 fn to_line_column(input: &str, pos: usize) -> LC {
@@ -46,17 +82,100 @@ fn to_line_column(input: &str, pos: usize) -> LC {
     )
 }
 
-struct S(LC, LC);
+/// The text that spans point into.
+#[derive(Copy, Clone)]
+enum Source<'a> {
+    /// A single input without a file name.
+    Text(&'a str),
+    /// The concatenated contents of files.
+    Files(&'a str, &'a [SourceFile]),
+}
+
+impl<'a> From<&'a str> for Source<'a> {
+    fn from(input: &'a str) -> Self {
+        Source::Text(input)
+    }
+}
 
-fn to_span(input: &str, span: Span) -> S {
-    impl Display for S {
+impl<'a> From<&'a Project> for Source<'a> {
+    fn from(project: &'a Project) -> Self {
+        Source::Files(&project.input, &project.files)
+    }
+}
+
+impl<'a> Source<'a> {
+    /// Returns the text that spans point into.
+    fn text(self) -> &'a str {
+        match self {
+            Source::Text(input) => input,
+            Source::Files(input, _) => input,
+        }
+    }
+}
+
+struct S<'a>(Option<&'a Path>, LC, LC);
+
+/// Returns the index of the file containing `span`, the contents of the file, and `span`
+/// relative to the start of the file.
+fn locate(source: Source<'_>, span: Span) -> (Option<usize>, &str, Span) {
+    match source {
+        Source::Text(input) => (None, input, span),
+        Source::Files(input, files) => {
+            // Spans at the boundary of two files belong to the later file.
+            let file = files
+                .iter()
+                .rposition(|f| f.span.0 <= span.0 && span.0 <= f.span.1)
+                .unwrap();
+            let fs = files[file].span;
+            let span = Span(span.0 - fs.0, span.1 - fs.0);
+            (Some(file), &input[fs.0..fs.1], span)
+        }
+    }
+}
+
+/// Returns a label for the location `span` in `source`.
+fn label<'a>(source: impl Into<Source<'a>>, span: Span, msg: impl Display) -> Label {
+    let source = source.into();
+    let (file, _, relative) = locate(source, span);
+    Label {
+        file,
+        span: relative,
+        msg: msg.to_string(),
+        location: to_span(source, span).to_string(),
+    }
+}
+
+/// Returns a [`SourceError`] at the location `span` in `source`.
+fn source_error<'a>(source: impl Into<Source<'a>>, span: Span, msg: impl Display) -> anyhow::Error {
+    SourceError {
+        msg: None,
+        labels: vec![label(source, span, msg)],
+    }
+    .into()
+}
+
+fn to_span<'a>(source: impl Into<Source<'a>>, span: Span) -> S<'a> {
+    impl Display for S<'_> {
         fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-            if self.0 .0 == self.1 .0 && self.0 .1 + 1 > self.1 .1 {
-                write!(f, "{}", self.0)
+            if let Some(path) = self.0 {
+                write!(f, "{}:", path.display())?;
+            }
+            if self.1 .0 == self.2 .0 && self.1 .1 + 1 > self.2 .1 {
+                write!(f, "{}", self.1)
             } else {
-                write!(f, "{} - {}", self.0, self.1)
+                write!(f, "{} - {}", self.1, self.2)
             }
         }
     }
-    S(to_line_column(input, span.0), to_line_column(input, span.1))
+    let source = source.into();
+    let (file, input, span) = locate(source, span);
+    let path = match (source, file) {
+        (Source::Files(_, files), Some(file)) => files[file].path.as_deref(),
+        _ => None,
+    };
+    S(
+        path,
+        to_line_column(input, span.0),
+        to_line_column(input, span.1),
+    )
 }
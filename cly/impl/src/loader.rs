@@ -0,0 +1,366 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//! Loading of declarations that are spread over multiple files.
+//!
+//! A file can import the declarations of another file with `import "path.cly"`. The
+//! imported declarations are then referenced by their names. With
+//! `import "path.cly" as ns`, they are referenced as `ns.name` instead.
+use crate::ast::{Annotation, Declaration, DeclarationType, Expr, ExprType, IndexType, Span};
+use crate::ast::{RecordField, Type, TypeArgument, TypeVariant};
+use crate::converter::evaluate_conditions_in_source;
+use crate::{parser, source_error, Source};
+use anyhow::{anyhow, Result};
+use repc_impl::builder::Options;
+use repc_impl::target::Target;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// The declarations of a file and of all files imported by it.
+pub struct Project {
+    /// The contents of all files, concatenated.
+    ///
+    /// The spans in the declarations are offsets into this string.
+    pub input: String,
+    /// The files in the order in which they were loaded.
+    ///
+    /// The first file is the root file. A file that is imported with different namespaces
+    /// is loaded once per namespace.
+    pub files: Vec<SourceFile>,
+    /// The declarations of all files.
+    ///
+    /// The names of declarations that were imported with a namespace are prefixed by the
+    /// namespace, e.g. `ns.name`.
+    pub declarations: Vec<Declaration>,
+    num_root_declarations: usize,
+}
+
+/// A file of a [`Project`].
+pub struct SourceFile {
+    /// The path of the file or `None` if the root file was not read from a file.
+    pub path: Option<PathBuf>,
+    /// The location of the contents of the file in [`Project::input`].
+    pub span: Span,
+}
+
+impl Project {
+    /// Returns the contents of the root file.
+    pub fn root_input(&self) -> &str {
+        let span = self.files[0].span;
+        &self.input[span.0..span.1]
+    }
+
+    /// Returns the declarations of the root file.
+    ///
+    /// Their spans are offsets into [`root_input`](Project::root_input).
+    pub fn root_declarations(&self) -> &[Declaration] {
+        &self.declarations[..self.num_root_declarations]
+    }
+
+    /// Evaluates the `@if` conditions of all declarations for `target`.
+    ///
+    /// See [`evaluate_conditions`](crate::evaluate_conditions).
+    pub fn evaluate_conditions(&mut self, target: Target, options: Options) -> Result<()> {
+        let (root, imported) = self.declarations.split_at(self.num_root_declarations);
+        let source = Source::Files(&self.input, &self.files);
+        let root = evaluate_conditions_in_source(source, root, target, options)?;
+        let imported = evaluate_conditions_in_source(source, imported, target, options)?;
+        self.num_root_declarations = root.len();
+        self.declarations = root;
+        self.declarations.extend(imported);
+        Ok(())
+    }
+}
+
+/// Loads the file at `path` and all files imported by it.
+pub fn load(path: &Path) -> Result<Project> {
+    let input =
+        fs::read_to_string(path).map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+    let canonical = path
+        .canonicalize()
+        .map_err(|e| anyhow!("cannot open {}: {}", path.display(), e))?;
+    let mut loader = Loader::default();
+    loader.stack.push((canonical, path.to_owned()));
+    loader.load_file(Some(path.to_owned()), &input, "")?;
+    Ok(loader.finish())
+}
+
+/// Loads the declarations in `input` and all files imported by them.
+///
+/// Imports are resolved relative to `dir`.
+pub fn load_str(input: &str, dir: &Path) -> Result<Project> {
+    let mut loader = Loader {
+        dir: dir.to_owned(),
+        ..Default::default()
+    };
+    loader.load_file(None, input, "")?;
+    Ok(loader.finish())
+}
+
+/// Loads the entries of an interactive session and all files imported by them.
+///
+/// Every entry is loaded as a separate file. Entries other than the last are named `[n]`
+/// where `n` is the 1-based number of the entry. A declaration replaces the declarations
+/// with the same name in earlier entries.
+///
+/// Returns the project and the locations of the entries in [`Project::input`].
+pub(crate) fn load_session(entries: &[&str], dir: &Path) -> Result<(Project, Vec<Span>)> {
+    let mut loader = Loader {
+        dir: dir.to_owned(),
+        ..Default::default()
+    };
+    let mut starts = vec![];
+    let mut spans = vec![];
+    for (i, entry) in entries.iter().enumerate() {
+        let path = match i + 1 < entries.len() {
+            true => Some(PathBuf::from(format!("[{}]", i + 1))),
+            false => None,
+        };
+        starts.push(loader.declarations.len());
+        spans.push(Span(loader.input.len(), loader.input.len() + entry.len()));
+        loader.load_file(path, entry, "")?;
+    }
+    let mut project = loader.finish();
+    let mut shadowed = HashSet::new();
+    let mut declarations = vec![];
+    let mut end = project.declarations.len();
+    for start in starts.into_iter().rev() {
+        let mut names = vec![];
+        for d in project.declarations.drain(start..end).rev() {
+            if d.name.is_empty() || !shadowed.contains(&d.name) {
+                names.push(d.name.clone());
+                declarations.push(d);
+            }
+        }
+        shadowed.extend(names);
+        end = start;
+    }
+    declarations.reverse();
+    project.num_root_declarations = declarations.len();
+    project.declarations = declarations;
+    Ok((project, spans))
+}
+
+#[derive(Default)]
+struct Loader {
+    input: String,
+    files: Vec<SourceFile>,
+    declarations: Vec<Declaration>,
+    /// The namespace prefix and the declarations of every loaded file.
+    scopes: Vec<(String, Range<usize>)>,
+    /// The canonical paths and namespace prefixes of all loaded files.
+    loaded: HashSet<(PathBuf, String)>,
+    /// The canonical and the displayed paths of the files that are currently being loaded.
+    stack: Vec<(PathBuf, PathBuf)>,
+    /// The directory of the root file if it was not read from a file.
+    dir: PathBuf,
+}
+
+impl Loader {
+    fn load_file(&mut self, path: Option<PathBuf>, input: &str, prefix: &str) -> Result<()> {
+        let lo = self.input.len();
+        self.input.push_str(input);
+        let hi = self.input.len();
+        let dir = match &path {
+            Some(p) => p.parent().unwrap_or_else(|| Path::new("")).to_owned(),
+            None => self.dir.clone(),
+        };
+        self.files.push(SourceFile {
+            path,
+            span: Span(lo, hi),
+        });
+        let (imports, declarations, _) = match parser::parse_file(&self.input.as_bytes()[..hi], lo)
+        {
+            Ok(r) => r,
+            Err(e) => return Err(self.error(e.span, e.msg)),
+        };
+        let start = self.declarations.len();
+        for mut d in declarations {
+            if !d.name.is_empty() {
+                d.name = format!("{}{}", prefix, d.name);
+            }
+            self.declarations.push(d);
+        }
+        self.scopes
+            .push((prefix.to_owned(), start..self.declarations.len()));
+        for import in imports {
+            let path = dir.join(&import.path);
+            let canonical = path.canonicalize().map_err(|e| {
+                self.error(
+                    import.span,
+                    format!("Cannot open {}: {}", path.display(), e),
+                )
+            })?;
+            if let Some(pos) = self.stack.iter().position(|s| s.0 == canonical) {
+                let mut cycle: Vec<_> = self.stack[pos..]
+                    .iter()
+                    .map(|s| s.1.display().to_string())
+                    .collect();
+                cycle.push(path.display().to_string());
+                return Err(self.error(
+                    import.span,
+                    format!("Circular import: {}", cycle.join(" -> ")),
+                ));
+            }
+            let prefix = match &import.namespace {
+                Some(ns) => format!("{}{}.", prefix, ns),
+                None => prefix.to_owned(),
+            };
+            if !self.loaded.insert((canonical.clone(), prefix.clone())) {
+                continue;
+            }
+            let input = fs::read_to_string(&path).map_err(|e| {
+                self.error(
+                    import.span,
+                    format!("Cannot read {}: {}", path.display(), e),
+                )
+            })?;
+            self.stack.push((canonical, path.clone()));
+            self.load_file(Some(path), &input, &prefix)?;
+            self.stack.pop();
+        }
+        Ok(())
+    }
+
+    fn error(&self, span: Span, msg: impl Display) -> anyhow::Error {
+        source_error(Source::Files(&self.input, &self.files), span, msg)
+    }
+
+    fn finish(mut self) -> Project {
+        let names: HashSet<_> = self.declarations.iter().map(|d| d.name.clone()).collect();
+        for (prefix, range) in &self.scopes {
+            if prefix.is_empty() {
+                continue;
+            }
+            // A name in a file refers to the declaration in the namespace of the file. Names
+            // that are not declared anywhere, such as predefined types, are left unchanged.
+            let resolve = |name: &mut String| {
+                let qualified = format!("{}{}", prefix, name);
+                if names.contains(&qualified) || names.contains(&*name) {
+                    *name = qualified;
+                }
+            };
+            for d in &mut self.declarations[range.clone()] {
+                if let Some(c) = &mut d.condition {
+                    rename_expr(c, &resolve);
+                }
+                // The parameters of generic declarations shadow all other names.
+                let params = &d.params;
+                let resolve = |name: &mut String| {
+                    if !params.contains(name) {
+                        resolve(name);
+                    }
+                };
+                match &mut d.ty {
+                    DeclarationType::Type(t) => rename_type(t, &resolve),
+                    DeclarationType::Const(e) => rename_expr(e, &resolve),
+                    DeclarationType::Assert(a) => rename_expr(&mut a.expr, &resolve),
+                }
+            }
+        }
+        let num_root_declarations = self.scopes[0].1.end;
+        Project {
+            input: self.input,
+            files: self.files,
+            declarations: self.declarations,
+            num_root_declarations,
+        }
+    }
+}
+
+fn rename_type(t: &mut Type, f: &impl Fn(&mut String)) {
+    for a in &mut t.annotations {
+        rename_annotation(a, f);
+    }
+    match &mut t.variant {
+        TypeVariant::Builtin(_) => {}
+        TypeVariant::Record(r) => rename_fields(&mut r.fields, f),
+        TypeVariant::Typedef(t) => rename_type(t, f),
+        TypeVariant::Array(a) => {
+            rename_type(&mut a.element_type, f);
+            if let Some(n) = &mut a.num_elements {
+                rename_expr(n, f);
+            }
+        }
+        TypeVariant::Opaque(o) => {
+            rename_expr(&mut o.size_bits, f);
+            rename_expr(&mut o.pointer_alignment_bits, f);
+            rename_expr(&mut o.field_alignment_bits, f);
+            rename_expr(&mut o.required_alignment_bits, f);
+        }
+        TypeVariant::Name(name, args, _) => {
+            f(name);
+            for arg in args {
+                match arg {
+                    TypeArgument::Type(t) => rename_type(t, f),
+                    TypeArgument::Expr(e) => rename_expr(e, f),
+                }
+            }
+        }
+        TypeVariant::Enum(e) => {
+            for v in &mut e.variants {
+                rename_expr(v, f);
+            }
+        }
+        TypeVariant::RustStruct(s) => rename_fields(&mut s.fields, f),
+        TypeVariant::RustEnum(e) => {
+            for v in &mut e.variants {
+                rename_fields(&mut v.fields, f);
+                if let Some(d) = &mut v.discriminant {
+                    rename_expr(d, f);
+                }
+            }
+        }
+    }
+}
+
+fn rename_fields(fields: &mut [RecordField], f: &impl Fn(&mut String)) {
+    for field in fields {
+        if let Some(c) = &mut field.condition {
+            rename_expr(c, f);
+        }
+        for a in &mut field.annotations {
+            rename_annotation(a, f);
+        }
+        if let Some(w) = &mut field.bit_width {
+            rename_expr(w, f);
+        }
+        rename_type(&mut field.ty, f);
+    }
+}
+
+fn rename_annotation(a: &mut Annotation, f: &impl Fn(&mut String)) {
+    match a {
+        Annotation::PragmaPack(e) | Annotation::Alignas(e) | Annotation::Aligned(Some(e)) => {
+            rename_expr(e, f)
+        }
+        Annotation::AttrPacked | Annotation::Aligned(None) => {}
+    }
+}
+
+fn rename_expr(e: &mut Expr, f: &impl Fn(&mut String)) {
+    match &mut e.ty {
+        ExprType::Lit(_) | ExprType::Builtin(_) | ExprType::Target(..) => {}
+        ExprType::Unary(_, e) => rename_expr(e, f),
+        ExprType::Binary(_, l, r) => {
+            rename_expr(l, f);
+            rename_expr(r, f);
+        }
+        ExprType::Conditional(c, t, e) => {
+            rename_expr(c, f);
+            rename_expr(t, f);
+            rename_expr(e, f);
+        }
+        ExprType::TypeExpr(_, t) => rename_type(t, f),
+        ExprType::Name(name) => f(name),
+        ExprType::Offsetof(_, t, indices) => {
+            rename_type(t, f);
+            for i in indices {
+                if let IndexType::Array(e) = &mut i.ty {
+                    rename_expr(e, f);
+                }
+            }
+        }
+    }
+}
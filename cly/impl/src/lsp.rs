@@ -0,0 +1,738 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//! A language server for cly files.
+//!
+//! The server communicates with the editor via the language server protocol over a pair of
+//! streams, usually stdin and stdout. It supports
+//!
+//! - diagnostics for parse errors, layout errors, and warnings,
+//! - the layouts and doc comments of types and fields on hover,
+//! - go-to-definition for names, and
+//! - completion of declared names and keywords.
+//!
+//! The target can be set with the `target` initialization option or the `cly.target`
+//! setting.
+use crate::ast::{
+    Annotation, Declaration, DeclarationType, Expr, ExprType, IndexType, RecordField, Span, Type,
+    TypeArgument, TypeVariant,
+};
+use crate::converter::ConversionResult;
+use crate::json::{object, Json};
+use crate::lexer::KEYWORDS;
+use crate::printer::{write_field_layout, write_type_layout};
+use crate::result::{Label, SourceError};
+use crate::{compute_project_layouts, enhance_declarations, label, load_str, Project};
+use anyhow::{anyhow, Result};
+use repc_impl::builder::Options;
+use repc_impl::target::Target;
+use repc_impl::typedefs::C_TYPEDEFS;
+use std::collections::HashMap;
+use std::env;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+const METHOD_NOT_FOUND: f64 = -32601.0;
+const INVALID_PARAMS: f64 = -32602.0;
+
+const MESSAGE_TYPE_ERROR: u64 = 1;
+
+const SEVERITY_ERROR: u64 = 1;
+const SEVERITY_WARNING: u64 = 2;
+
+const KIND_KEYWORD: u64 = 14;
+const KIND_CONSTANT: u64 = 21;
+const KIND_STRUCT: u64 = 22;
+
+/// Serves language server requests read from `input` until the client exits.
+///
+/// `target` and `options` are used to compute layouts unless the client configures a
+/// different target. Invalid messages and notifications are logged to stderr and otherwise
+/// ignored.
+pub fn serve(
+    input: impl BufRead,
+    output: impl Write,
+    target: Target,
+    options: Options,
+) -> Result<()> {
+    let mut input = input;
+    let mut server = Server {
+        output,
+        target,
+        options,
+        documents: HashMap::new(),
+        names: HashMap::new(),
+    };
+    while let Some(msg) = read_message(&mut input)? {
+        let msg = match Json::parse(&msg) {
+            Ok(msg) => msg,
+            Err(e) => {
+                eprintln!("Invalid message: {}", e);
+                continue;
+            }
+        };
+        if !server.handle(&msg)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn read_message(input: &mut impl BufRead) -> Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(l) = line.strip_prefix("Content-Length:") {
+            length = Some(
+                l.trim()
+                    .parse::<usize>()
+                    .map_err(|_| anyhow!("Invalid Content-Length header: {}", line))?,
+            );
+        }
+    }
+    let length = length.ok_or_else(|| anyhow!("Message without Content-Length header"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(
+        String::from_utf8(body).map_err(|_| anyhow!("Message is not valid UTF-8"))?,
+    ))
+}
+
+struct Server<W> {
+    output: W,
+    target: Target,
+    options: Options,
+    /// The contents of the open documents by their URI.
+    documents: HashMap<String, String>,
+    /// The names declared in the open documents when they could last be loaded.
+    names: HashMap<String, Vec<(String, u64)>>,
+}
+
+/// The result of loading a document and computing its layouts.
+struct Analysis {
+    /// `None` if the document could not be loaded.
+    project: Option<Project>,
+    /// `None` if the layouts could not be computed.
+    layouts: Option<ConversionResult>,
+    diagnostics: Vec<Json>,
+}
+
+impl<W: Write> Server<W> {
+    /// Handles a message. Returns `false` if the server should exit.
+    fn handle(&mut self, msg: &Json) -> Result<bool> {
+        let method = msg.get("method").as_str().unwrap_or("");
+        let params = msg.get("params");
+        let id = msg.get("id");
+        if method == "exit" {
+            return Ok(false);
+        }
+        if id.is_null() {
+            // Notifications have no response that could carry an error.
+            if let Err(e) = self.notification(method, params) {
+                eprintln!("Invalid {} notification: {:#}", method, e);
+            }
+            return Ok(true);
+        }
+        let response = match self.request(method, params) {
+            Ok(Some(result)) => ("result", result),
+            Ok(None) => (
+                "error",
+                object(vec![
+                    ("code", Json::Number(METHOD_NOT_FOUND)),
+                    ("message", format!("Unknown method {}", method).into()),
+                ]),
+            ),
+            Err(e) => (
+                "error",
+                object(vec![
+                    ("code", Json::Number(INVALID_PARAMS)),
+                    ("message", e.to_string().into()),
+                ]),
+            ),
+        };
+        self.send(object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("id", id.clone()),
+            response,
+        ]))?;
+        Ok(true)
+    }
+
+    /// Handles a request. Returns `None` if the method is not supported.
+    fn request(&mut self, method: &str, params: &Json) -> Result<Option<Json>> {
+        let res = match method {
+            "initialize" => {
+                self.configure(params.get("initializationOptions"))?;
+                object(vec![(
+                    "capabilities",
+                    object(vec![
+                        ("textDocumentSync", 1.into()),
+                        ("hoverProvider", true.into()),
+                        ("definitionProvider", true.into()),
+                        ("completionProvider", object(vec![])),
+                    ]),
+                )])
+            }
+            "shutdown" => Json::Null,
+            "textDocument/hover" => {
+                let (uri, offset) = self.position(params)?;
+                let analysis = self.analyze(&uri);
+                match hover(&analysis, offset) {
+                    Some(text) => object(vec![(
+                        "contents",
+                        object(vec![("kind", "plaintext".into()), ("value", text.into())]),
+                    )]),
+                    None => Json::Null,
+                }
+            }
+            "textDocument/definition" => {
+                let (uri, offset) = self.position(params)?;
+                let analysis = self.analyze(&uri);
+                match &analysis.project {
+                    Some(project) => match definition(project, offset) {
+                        Some(span) => location(&uri, project, span),
+                        None => Json::Null,
+                    },
+                    None => Json::Null,
+                }
+            }
+            "textDocument/completion" => {
+                let uri = document_uri(params)?;
+                let mut items = vec![];
+                for (name, kind) in self.names.get(uri).into_iter().flatten() {
+                    items.push(completion_item(name, *kind));
+                }
+                for name in C_TYPEDEFS {
+                    items.push(completion_item(name, KIND_STRUCT));
+                }
+                for (keyword, _) in KEYWORDS {
+                    items.push(completion_item(keyword, KIND_KEYWORD));
+                }
+                items.into()
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(res))
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> Result<()> {
+        match method {
+            "textDocument/didOpen" => {
+                let doc = params.get("textDocument");
+                let (uri, text) = match (doc.get("uri").as_str(), doc.get("text").as_str()) {
+                    (Some(uri), Some(text)) => (uri.to_string(), text.to_string()),
+                    _ => return Ok(()),
+                };
+                self.documents.insert(uri.clone(), text);
+                self.publish_diagnostics(&uri)?;
+            }
+            "textDocument/didChange" => {
+                let uri = document_uri(params)?.to_string();
+                // The server only supports full document synchronization.
+                if let Json::Array(changes) = params.get("contentChanges") {
+                    if let Some(text) = changes.last().and_then(|c| c.get("text").as_str()) {
+                        self.documents.insert(uri.clone(), text.to_string());
+                    }
+                }
+                self.publish_diagnostics(&uri)?;
+            }
+            "textDocument/didClose" => {
+                let uri = document_uri(params)?.to_string();
+                self.documents.remove(&uri);
+                self.names.remove(&uri);
+                self.send_diagnostics(&uri, vec![])?;
+            }
+            "workspace/didChangeConfiguration" => {
+                if let Err(e) = self.configure(params.get("settings").get("cly")) {
+                    let msg = format!("Invalid cly settings: {:#}", e);
+                    self.show_message(MESSAGE_TYPE_ERROR, &msg)?;
+                }
+                let uris: Vec<_> = self.documents.keys().cloned().collect();
+                for uri in uris {
+                    self.publish_diagnostics(&uri)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn configure(&mut self, settings: &Json) -> Result<()> {
+        if let Some(target) = settings.get("target").as_str() {
+            self.target = target.parse()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the URI and the offset of a `TextDocumentPositionParams`.
+    fn position(&self, params: &Json) -> Result<(String, usize)> {
+        let uri = document_uri(params)?;
+        let text = self.document(uri)?;
+        let pos = params.get("position");
+        match (pos.get("line").as_u64(), pos.get("character").as_u64()) {
+            (Some(line), Some(character)) => Ok((
+                uri.to_string(),
+                offset(text, line as usize, character as usize),
+            )),
+            _ => Err(anyhow!("Invalid position")),
+        }
+    }
+
+    fn document(&self, uri: &str) -> Result<&str> {
+        match self.documents.get(uri) {
+            Some(text) => Ok(text),
+            None => Err(anyhow!("Unknown document {}", uri)),
+        }
+    }
+
+    fn analyze(&mut self, uri: &str) -> Analysis {
+        let text = self.documents.get(uri).map(|t| &**t).unwrap_or("");
+        let dir = match uri_to_path(uri) {
+            Some(p) => p.parent().map(|p| p.to_owned()).unwrap_or_default(),
+            None => env::current_dir().unwrap_or_default(),
+        };
+        let mut analysis = Analysis {
+            project: None,
+            layouts: None,
+            diagnostics: vec![],
+        };
+        let mut project = match load_str(text, &dir) {
+            Ok(p) => p,
+            Err(e) => {
+                analysis.diagnostics = error_diagnostics(text, &e);
+                return analysis;
+            }
+        };
+        let names = project
+            .declarations
+            .iter()
+            .filter(|d| !d.name.is_empty())
+            .map(|d| match d.ty {
+                DeclarationType::Const(_) => (d.name.clone(), KIND_CONSTANT),
+                _ => (d.name.clone(), KIND_STRUCT),
+            })
+            .collect();
+        self.names.insert(uri.to_string(), names);
+        let layouts = project
+            .evaluate_conditions(self.target, self.options)
+            .and_then(|_| compute_project_layouts(&project, self.target, self.options));
+        match layouts {
+            Ok((layouts, warnings)) => {
+                for w in warnings {
+                    let label = label(&project, w.span, w.kind);
+                    analysis
+                        .diagnostics
+                        .push(label_diagnostic(text, &label, SEVERITY_WARNING));
+                }
+                analysis.layouts = Some(layouts);
+            }
+            Err(e) => {
+                analysis.diagnostics = error_diagnostics(text, &e);
+            }
+        }
+        analysis.project = Some(project);
+        analysis
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> Result<()> {
+        let analysis = self.analyze(uri);
+        self.send_diagnostics(uri, analysis.diagnostics)
+    }
+
+    fn send_diagnostics(&mut self, uri: &str, diagnostics: Vec<Json>) -> Result<()> {
+        self.send(object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            (
+                "params",
+                object(vec![
+                    ("uri", uri.into()),
+                    ("diagnostics", diagnostics.into()),
+                ]),
+            ),
+        ]))
+    }
+
+    fn show_message(&mut self, ty: u64, msg: &str) -> Result<()> {
+        self.send(object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("method", "window/showMessage".into()),
+            (
+                "params",
+                object(vec![("type", ty.into()), ("message", msg.into())]),
+            ),
+        ]))
+    }
+
+    fn send(&mut self, msg: Json) -> Result<()> {
+        let msg = msg.to_string();
+        write!(self.output, "Content-Length: {}\r\n\r\n{}", msg.len(), msg)?;
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+fn document_uri(params: &Json) -> Result<&str> {
+    params
+        .get("textDocument")
+        .get("uri")
+        .as_str()
+        .ok_or_else(|| anyhow!("Missing document URI"))
+}
+
+fn completion_item(label: &str, kind: u64) -> Json {
+    object(vec![("label", label.into()), ("kind", kind.into())])
+}
+
+/// Converts an error to diagnostics.
+///
+/// Every label of a [`SourceError`] becomes a diagnostic. Labels in other files and errors
+/// without location are reported at the start of `text`.
+fn error_diagnostics(text: &str, e: &anyhow::Error) -> Vec<Json> {
+    let mut res = vec![];
+    match e.downcast_ref::<SourceError>() {
+        Some(e) => {
+            if let Some(msg) = &e.msg {
+                res.push(diagnostic(range(text, 0, 0), msg, SEVERITY_ERROR));
+            }
+            for label in &e.labels {
+                res.push(label_diagnostic(text, label, SEVERITY_ERROR));
+            }
+        }
+        None => res.push(diagnostic(
+            range(text, 0, 0),
+            &e.to_string(),
+            SEVERITY_ERROR,
+        )),
+    }
+    res
+}
+
+/// Converts a label in the root file `text` or in another file of the project to a
+/// diagnostic.
+fn label_diagnostic(text: &str, label: &Label, severity: u64) -> Json {
+    match label.file {
+        None | Some(0) => diagnostic(
+            range(text, label.span.0, label.span.1),
+            &label.msg,
+            severity,
+        ),
+        Some(_) => {
+            let msg = format!("{}: {}", label.location, label.msg);
+            diagnostic(range(text, 0, 0), &msg, severity)
+        }
+    }
+}
+
+fn diagnostic(range: Json, message: &str, severity: u64) -> Json {
+    object(vec![
+        ("range", range),
+        ("severity", severity.into()),
+        ("source", "cly".into()),
+        ("message", message.into()),
+    ])
+}
+
+/// Returns the range between two offsets.
+fn range(text: &str, lo: usize, hi: usize) -> Json {
+    object(vec![
+        ("start", position(text, lo.min(text.len()))),
+        ("end", position(text, hi.min(text.len()))),
+    ])
+}
+
+/// Converts an offset to a position, i.e. a line and a column in UTF-16 code units.
+fn position(text: &str, offset: usize) -> Json {
+    let before = &text[..floor_char_boundary(text, offset)];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line = before.matches('\n').count() as u64;
+    let character = before[line_start..].encode_utf16().count() as u64;
+    object(vec![("line", line.into()), ("character", character.into())])
+}
+
+/// Converts a line and a column in UTF-16 code units to an offset.
+fn offset(text: &str, line: usize, character: usize) -> usize {
+    let start: usize = text.split_inclusive('\n').take(line).map(|l| l.len()).sum();
+    let mut units = 0;
+    for (i, c) in text[start..].char_indices() {
+        if units >= character || c == '\n' {
+            return start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+fn floor_char_boundary(text: &str, mut offset: usize) -> usize {
+    offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let mut bytes = vec![];
+    let mut i = 0;
+    while i < path.len() {
+        let b = path.as_bytes()[i];
+        match path.get(i + 1..i + 3).map(|h| u8::from_str_radix(h, 16)) {
+            Some(Ok(c)) if b == b'%' => {
+                bytes.push(c);
+                i += 3;
+            }
+            _ => {
+                bytes.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = "file://".to_string();
+    for b in path.to_string_lossy().bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(b as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    uri
+}
+
+/// Returns the location of `span` in the files of `project`.
+fn location(uri: &str, project: &Project, span: Span) -> Json {
+    let file = project
+        .files
+        .iter()
+        .rfind(|f| f.span.0 <= span.0 && span.0 <= f.span.1)
+        .unwrap();
+    let text = &project.input[file.span.0..file.span.1];
+    let uri = match &file.path {
+        Some(p) => path_to_uri(p),
+        None => uri.to_string(),
+    };
+    object(vec![
+        ("uri", uri.into()),
+        (
+            "range",
+            range(text, span.0 - file.span.0, span.1 - file.span.0),
+        ),
+    ])
+}
+
+fn contains(span: Span, offset: usize) -> bool {
+    span.0 <= offset && offset <= span.1
+}
+
+/// Returns the span of the declaration of the name at `offset` in the root file.
+fn definition(project: &Project, offset: usize) -> Option<Span> {
+    for d in project.root_declarations() {
+        let mut refs = vec![];
+        declaration_references(d, &mut refs);
+        if let Some((name, _)) = refs.into_iter().find(|r| contains(r.1, offset)) {
+            // Parameters of generic declarations have no declaration of their own.
+            if d.params.iter().any(|p| p == name) {
+                return None;
+            }
+            return project
+                .declarations
+                .iter()
+                .find(|d| d.name == name)
+                .map(|d| d.span);
+        }
+    }
+    None
+}
+
+/// Collects the names referenced in `d` and their spans.
+fn declaration_references<'a>(d: &'a Declaration, res: &mut Vec<(&'a str, Span)>) {
+    if let Some(c) = &d.condition {
+        expr_references(c, res);
+    }
+    match &d.ty {
+        DeclarationType::Type(t) => type_references(t, res),
+        DeclarationType::Const(e) => expr_references(e, res),
+        DeclarationType::Assert(a) => expr_references(&a.expr, res),
+    }
+}
+
+fn type_references<'a>(t: &'a Type, res: &mut Vec<(&'a str, Span)>) {
+    for a in &t.annotations {
+        annotation_references(a, res);
+    }
+    match &t.variant {
+        TypeVariant::Builtin(_) => {}
+        TypeVariant::Record(r) => fields_references(&r.fields, res),
+        TypeVariant::Typedef(t) => type_references(t, res),
+        TypeVariant::Array(a) => {
+            type_references(&a.element_type, res);
+            if let Some(n) = &a.num_elements {
+                expr_references(n, res);
+            }
+        }
+        TypeVariant::Opaque(o) => {
+            expr_references(&o.size_bits, res);
+            expr_references(&o.pointer_alignment_bits, res);
+            expr_references(&o.field_alignment_bits, res);
+            expr_references(&o.required_alignment_bits, res);
+        }
+        TypeVariant::Name(name, args, span) => {
+            for a in args {
+                match a {
+                    TypeArgument::Type(t) => type_references(t, res),
+                    TypeArgument::Expr(e) => expr_references(e, res),
+                }
+            }
+            // The span of the name excludes the arguments.
+            res.push((name, Span(span.0, span.0 + name.len())));
+        }
+        TypeVariant::Enum(e) => {
+            for v in &e.variants {
+                expr_references(v, res);
+            }
+        }
+        TypeVariant::RustStruct(s) => fields_references(&s.fields, res),
+        TypeVariant::RustEnum(e) => {
+            for v in &e.variants {
+                fields_references(&v.fields, res);
+                if let Some(d) = &v.discriminant {
+                    expr_references(d, res);
+                }
+            }
+        }
+    }
+}
+
+fn fields_references<'a>(fields: &'a [RecordField], res: &mut Vec<(&'a str, Span)>) {
+    for f in fields {
+        if let Some(c) = &f.condition {
+            expr_references(c, res);
+        }
+        for a in &f.annotations {
+            annotation_references(a, res);
+        }
+        if let Some(w) = &f.bit_width {
+            expr_references(w, res);
+        }
+        type_references(&f.ty, res);
+    }
+}
+
+fn annotation_references<'a>(a: &'a Annotation, res: &mut Vec<(&'a str, Span)>) {
+    match a {
+        Annotation::PragmaPack(e) | Annotation::Alignas(e) | Annotation::Aligned(Some(e)) => {
+            expr_references(e, res)
+        }
+        Annotation::AttrPacked | Annotation::Aligned(None) => {}
+    }
+}
+
+fn expr_references<'a>(e: &'a Expr, res: &mut Vec<(&'a str, Span)>) {
+    match &e.ty {
+        ExprType::Lit(_) | ExprType::Builtin(_) | ExprType::Target(..) => {}
+        ExprType::Unary(_, e) => expr_references(e, res),
+        ExprType::Binary(_, l, r) => {
+            expr_references(l, res);
+            expr_references(r, res);
+        }
+        ExprType::Conditional(c, t, e) => {
+            expr_references(c, res);
+            expr_references(t, res);
+            expr_references(e, res);
+        }
+        ExprType::TypeExpr(_, t) => type_references(t, res),
+        ExprType::Name(name) => res.push((name, e.span)),
+        ExprType::Offsetof(_, t, indices) => {
+            type_references(t, res);
+            for i in indices {
+                if let IndexType::Array(e) = &i.ty {
+                    expr_references(e, res);
+                }
+            }
+        }
+    }
+}
+
+/// Returns a description of the layout of the declaration, field, or type at `offset`.
+fn hover(analysis: &Analysis, offset: usize) -> Option<String> {
+    let (project, layouts) = match (&analysis.project, &analysis.layouts) {
+        (Some(p), Some(l)) => (p, l),
+        _ => return None,
+    };
+    for d in enhance_declarations(project.root_declarations(), layouts) {
+        let res = match &d.ty {
+            // Generic declarations only have layouts when they are instantiated.
+            DeclarationType::Type(_) if !d.params.is_empty() => None,
+            DeclarationType::Type(t) if contains(d.span, offset) => {
+                let mut res = format!("{}: ", d.name);
+                write_type_layout(&mut res, t).ok()?;
+                Some(with_doc(res, &d.doc))
+            }
+            DeclarationType::Type(t) => type_hover(t, offset),
+            DeclarationType::Const(_) if contains(d.span, offset) => {
+                let res = format!("const {} = {}", d.name, layouts.consts[&d.name]);
+                Some(with_doc(res, &d.doc))
+            }
+            _ => None,
+        };
+        if res.is_some() {
+            return res;
+        }
+    }
+    None
+}
+
+/// Appends the doc comment of a declaration or field to its hover text.
+fn with_doc(mut res: String, doc: &Option<String>) -> String {
+    if let Some(doc) = doc {
+        res.push_str("\n\n");
+        res.push_str(doc);
+    }
+    res
+}
+
+fn type_hover(t: &Type, offset: usize) -> Option<String> {
+    match &t.variant {
+        TypeVariant::Record(r) => fields_hover(&r.fields, offset),
+        TypeVariant::RustStruct(s) => fields_hover(&s.fields, offset),
+        TypeVariant::RustEnum(e) => e
+            .variants
+            .iter()
+            .find_map(|v| fields_hover(&v.fields, offset)),
+        TypeVariant::Typedef(t) => type_hover(t, offset),
+        TypeVariant::Array(a) => type_hover(&a.element_type, offset),
+        TypeVariant::Name(name, _, span) if contains(*span, offset) => {
+            let mut res = format!("{}: ", name);
+            write_type_layout(&mut res, t).ok()?;
+            Some(res)
+        }
+        _ => None,
+    }
+}
+
+fn fields_hover(fields: &[RecordField], offset: usize) -> Option<String> {
+    for f in fields {
+        // The part of the field before its type contains the name.
+        if f.lo <= offset && offset < f.ty.lo {
+            let mut res = format!("{}: ", f.name.as_deref().unwrap_or("_"));
+            write_field_layout(&mut res, f).ok()?;
+            res.push(' ');
+            write_type_layout(&mut res, &f.ty).ok()?;
+            return Some(with_doc(res, &f.doc));
+        }
+        if let Some(res) = type_hover(&f.ty, offset) {
+            return Some(res);
+        }
+    }
+    None
+}
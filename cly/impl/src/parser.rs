@@ -1,18 +1,115 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 use crate::ast::{
-    Annotation, Array, BinaryExprType, BuiltinExpr, Declaration, DeclarationType, Expr, ExprType,
-    Index, IndexType, OffsetofType, OpaqueTypeLayout, Record, RecordField, Span, Type,
-    TypeExprType, TypeVariant, UnaryExprType,
+    Annotation, Array, Assertion, BinaryExprType, BuiltinExpr, Declaration, DeclarationType, Enum,
+    Expr, ExprType, Import, Index, IndexType, OffsetofType, OpaqueTypeLayout, Record, RecordField,
+    RustEnum, RustEnumVariant, RustStruct, Span, TargetProperty, Type, TypeArgument, TypeExprType,
+    TypeVariant, UnaryExprType,
 };
 use crate::lexer;
 use crate::lexer::{Spanned, Token};
 use crate::result::{ParseError, ParseResult};
-use repc_impl::layout::{BuiltinType, FieldLayout, RecordKind, TypeLayout};
+use repc_impl::layout::{
+    BuiltinType, EnumUnderlyingType, FieldLayout, RecordKind, RustEnumRepr, RustStructRepr,
+    TypeLayout,
+};
 use repc_impl::util::BITS_PER_BYTE;
 
 pub fn parse(input: &[u8]) -> ParseResult<Vec<Declaration>> {
-    let (tokens, strings) = lexer::lex(input)?;
-    Parser::new(tokens, strings).parse_declarations()
+    let (imports, declarations, _) = parse_file(input, 0)?;
+    match imports.first() {
+        Some(i) => Err(ParseError {
+            msg: "Imports can only be resolved when loading files".to_string(),
+            span: i.span,
+        }),
+        None => Ok(declarations),
+    }
+}
+
+/// The imports, the declarations, and the spans of the comments of a file.
+pub type ParsedFile = (Vec<Import>, Vec<Declaration>, Vec<Span>);
+
+/// Parses the file contained in `input[pos..]`. The spans in the result are offsets into
+/// `input`.
+pub fn parse_file(input: &[u8], pos: usize) -> ParseResult<ParsedFile> {
+    let (tokens, strings, comments) = lexer::lex(input, pos)?;
+    let mut parser = Parser::new(tokens, strings);
+    parser.docs = doc_comments(input, &comments);
+    let declarations = parser.parse_declarations()?;
+    Ok((parser.imports, declarations, comments))
+}
+
+/// Returns whether `input` looks like an expression rather than declarations.
+pub fn is_expr(input: &[u8]) -> bool {
+    let tokens = match lexer::lex(input, 0) {
+        Ok((tokens, _, _)) => tokens,
+        Err(_) => return false,
+    };
+    match tokens.first().map(|t| t.val) {
+        None | Some(Token::Const | Token::Assert | Token::Import | Token::At) => false,
+        Some(Token::Ident(_)) => !matches!(
+            tokens.get(1).map(|t| t.val),
+            Some(Token::Eq | Token::LeftParen)
+        ),
+        Some(_) => true,
+    }
+}
+
+/// Returns whether `input` ends in the middle of a declaration or expression, that is,
+/// whether it contains unclosed brackets or ends with an operator.
+pub fn is_incomplete(input: &[u8]) -> bool {
+    let tokens = match lexer::lex(input, 0) {
+        Ok((tokens, _, _)) => tokens,
+        Err(_) => return false,
+    };
+    let mut depth = 0;
+    for t in &tokens {
+        match t.val {
+            Token::LeftParen | Token::LeftBrace | Token::LeftBracket => depth += 1,
+            Token::RightParen | Token::RightBrace | Token::RightBracket => depth -= 1,
+            _ => {}
+        }
+    }
+    let ends_with_operator = match tokens.last().map(|t| t.val) {
+        Some(Token::Eq | Token::Comma | Token::Question | Token::Colon) => true,
+        Some(Token::Not | Token::Tilde) => true,
+        Some(t) => precedence(t) > 0,
+        None => false,
+    };
+    depth > 0 || ends_with_operator
+}
+
+/// Parses the expression contained in `input[pos..]`. The spans in the result are offsets
+/// into `input`.
+pub fn parse_expr(input: &[u8], pos: usize) -> ParseResult<Expr> {
+    let (tokens, strings, _) = lexer::lex(input, pos)?;
+    let mut parser = Parser::new(tokens, strings);
+    let expr = parser.parse_expr()?;
+    match parser.tokens.get(parser.pos) {
+        Some(t) => Err(ParseError {
+            msg: format!("Unexpected {}. Expected the end of the expression.", t.val),
+            span: t.span,
+        }),
+        None => Ok(expr),
+    }
+}
+
+/// Returns the doc comments among `comments` with their text after `///` and an optional
+/// space.
+fn doc_comments(input: &[u8], comments: &[Span]) -> Vec<Spanned<String>> {
+    comments
+        .iter()
+        .filter_map(|c| {
+            let text = input[c.0..c.1].strip_prefix(b"///")?;
+            if text.starts_with(b"/") {
+                return None;
+            }
+            let text = text.strip_prefix(b" ").unwrap_or(text);
+            Some(Spanned {
+                span: *c,
+                val: String::from_utf8_lossy(text).trim_end().to_string(),
+            })
+        })
+        .collect()
 }
 
 struct Parser {
@@ -20,6 +117,8 @@ struct Parser {
     pos: usize,
     tokens: Vec<Spanned<Token>>,
     strings: Vec<String>,
+    imports: Vec<Import>,
+    docs: Vec<Spanned<String>>,
 }
 
 impl Parser {
@@ -29,40 +128,144 @@ impl Parser {
             pos: 0,
             tokens,
             strings,
+            imports: vec![],
+            docs: vec![],
         }
     }
 
-    fn parse_declarations(mut self) -> ParseResult<Vec<Declaration>> {
+    fn parse_declarations(&mut self) -> ParseResult<Vec<Declaration>> {
         let mut res = vec![];
         while self.pos < self.tokens.len() {
-            res.push(self.parse_declaration()?);
+            if self.peek()?.val == Token::Import {
+                let import = self.parse_import()?;
+                self.imports.push(import);
+            } else {
+                res.push(self.parse_declaration()?);
+            }
         }
         Ok(res)
     }
 
+    fn parse_import(&mut self) -> ParseResult<Import> {
+        let lo = self.parse_token(Token::Import)?.span.0;
+        let path = self.parse_string()?;
+        let mut namespace = None;
+        if let Some(Token::Ident(id)) = self.tokens.get(self.pos).map(|t| t.val) {
+            if self.strings[id] == "as" {
+                self.pos += 1;
+                namespace = Some(self.parse_ident()?.0);
+            }
+        }
+        Ok(Import {
+            path,
+            namespace,
+            span: Span(lo, self.tokens[self.pos - 1].span.1),
+        })
+    }
+
     fn parse_declaration(&mut self) -> ParseResult<Declaration> {
+        let doc = self.parse_doc();
+        let lo = self.peek()?.span.0;
+        let condition = self.parse_condition()?;
         let cur = self.peek()?;
-        match cur.val {
+        let mut d = match cur.val {
             Token::Ident(_) => self.parse_type_declaration(),
             Token::Const => self.parse_const_declaration(),
+            Token::Assert => self.parse_assertion(),
             _ => Err(ParseError {
-                msg: format!("Unexpected {}. Expected `const` or identifier.", cur.val),
+                msg: format!(
+                    "Unexpected {}. Expected `const`, `assert`, `import`, or identifier.",
+                    cur.val
+                ),
                 span: cur.span,
             }),
+        }?;
+        d.lo = lo;
+        d.doc = doc;
+        d.condition = condition;
+        Ok(d)
+    }
+
+    /// Returns the doc comments between the previous and the next token.
+    fn parse_doc(&self) -> Option<String> {
+        let lo = match self.pos {
+            0 => 0,
+            _ => self.tokens[self.pos - 1].span.1,
+        };
+        let hi = self
+            .tokens
+            .get(self.pos)
+            .map(|t| t.span.0)
+            .unwrap_or(usize::MAX);
+        let start = self.docs.partition_point(|d| d.span.0 < lo);
+        let lines: Vec<_> = self.docs[start..]
+            .iter()
+            .take_while(|d| d.span.1 <= hi)
+            .map(|d| &*d.val)
+            .collect();
+        match lines.is_empty() {
+            true => None,
+            false => Some(lines.join("\n")),
         }
     }
 
+    fn parse_condition(&mut self) -> ParseResult<Option<Box<Expr>>> {
+        match self.tokens.get(self.pos + 1) {
+            Some(t) if t.val == Token::If && self.peek()?.val == Token::At => {}
+            _ => return Ok(None),
+        }
+        self.pos += 2;
+        self.parse_token(Token::LeftParen)?;
+        let expr = self.parse_expr()?;
+        self.parse_token(Token::RightParen)?;
+        Ok(Some(Box::new(expr)))
+    }
+
     fn parse_type_declaration(&mut self) -> ParseResult<Declaration> {
         let (name, span) = self.parse_ident()?;
+        let params = self.parse_params()?;
         self.parse_token(Token::Eq)?;
         let ty = self.parse_type()?;
         Ok(Declaration {
             name,
             span,
+            lo: span.0,
+            doc: None,
+            params,
+            condition: None,
             ty: DeclarationType::Type(ty),
         })
     }
 
+    fn parse_params(&mut self) -> ParseResult<Vec<String>> {
+        let mut params = vec![];
+        if self.peek()?.val != Token::LeftParen {
+            return Ok(params);
+        }
+        self.pos += 1;
+        loop {
+            let (param, span) = self.parse_ident()?;
+            if params.contains(&param) {
+                return Err(ParseError {
+                    msg: format!("Parameter {} is declared multiple times", param),
+                    span,
+                });
+            }
+            params.push(param);
+            let cur = self.next()?;
+            match cur.val {
+                Token::Comma => {}
+                Token::RightParen => return Ok(params),
+                _ => {
+                    return Err(ParseError {
+                        msg: format!("Unexpected {}. Expected `,` or `)`", cur.val),
+                        span: cur.span,
+                    })
+                }
+            }
+        }
+    }
+
     fn parse_const_declaration(&mut self) -> ParseResult<Declaration> {
         self.parse_token(Token::Const)?;
         let (name, span) = self.parse_ident()?;
@@ -71,10 +274,35 @@ impl Parser {
         Ok(Declaration {
             name,
             span,
+            lo: span.0,
+            doc: None,
+            params: vec![],
+            condition: None,
             ty: DeclarationType::Const(expr),
         })
     }
 
+    fn parse_assertion(&mut self) -> ParseResult<Declaration> {
+        let lo = self.parse_token(Token::Assert)?.span.0;
+        let expr = self.parse_expr()?;
+        let message = match self.tokens.get(self.pos).map(|t| t.val) {
+            Some(Token::Comma) => {
+                self.pos += 1;
+                Some(self.parse_string()?)
+            }
+            _ => None,
+        };
+        Ok(Declaration {
+            name: String::new(),
+            span: Span(lo, self.tokens[self.pos - 1].span.1),
+            lo,
+            doc: None,
+            params: vec![],
+            condition: None,
+            ty: DeclarationType::Assert(Assertion { expr, message }),
+        })
+    }
+
     fn parse_top_level_expr(&mut self) -> ParseResult<Expr> {
         let lo = self.peek()?.span.0;
         let (val, hi) = self.parse_expr_value()?;
@@ -135,6 +363,11 @@ impl Parser {
                         Token::Mod => BinaryExprType::Mod,
                         Token::OrOr => BinaryExprType::LogicalOr,
                         Token::AndAnd => BinaryExprType::LogicalAnd,
+                        Token::And => BinaryExprType::BitAnd,
+                        Token::Or => BinaryExprType::BitOr,
+                        Token::Caret => BinaryExprType::BitXor,
+                        Token::Shl => BinaryExprType::Shl,
+                        Token::Shr => BinaryExprType::Shr,
                         _ => unreachable!(),
                     };
                     e_stack.push(Expr {
@@ -164,7 +397,12 @@ impl Parser {
                 | Token::Div
                 | Token::Mod
                 | Token::OrOr
-                | Token::AndAnd => {}
+                | Token::AndAnd
+                | Token::And
+                | Token::Or
+                | Token::Caret
+                | Token::Shl
+                | Token::Shr => {}
                 _ => break,
             }
             self.pos += 1;
@@ -178,7 +416,20 @@ impl Parser {
         assert_eq!(o_stack.len(), 0);
         assert_eq!(e_stack.len(), 1);
 
-        Ok(e_stack.pop().unwrap())
+        let cond = e_stack.pop().unwrap();
+        if self.tokens.get(self.pos).map(|t| t.val) != Some(Token::Question) {
+            return Ok(cond);
+        }
+        self.pos += 1;
+        let then = self.parse_expr()?;
+        self.parse_token(Token::Colon)?;
+        let els = self.parse_expr()?;
+        Ok(Expr {
+            span: Span(cond.span.0, els.span.1),
+            value: None,
+            value_hi: 0,
+            ty: ExprType::Conditional(Box::new(cond), Box::new(then), Box::new(els)),
+        })
     }
 
     fn parse_atomic_expr(&mut self) -> ParseResult<Expr> {
@@ -188,17 +439,46 @@ impl Parser {
             Token::Minus => {
                 ExprType::Unary(UnaryExprType::Neg, Box::new(self.parse_atomic_expr()?))
             }
+            Token::Tilde => {
+                ExprType::Unary(UnaryExprType::BitNot, Box::new(self.parse_atomic_expr()?))
+            }
             Token::LeftParen => {
                 let expr = self.parse_expr()?;
                 self.parse_token(Token::RightParen)?;
                 expr.ty
             }
             Token::BitsPerByte => ExprType::Builtin(BuiltinExpr::BitsPerByte),
+            Token::TargetPointerWidth => ExprType::Builtin(BuiltinExpr::TargetPointerWidth),
+            Token::TargetName | Token::TargetCompiler | Token::TargetOs | Token::TargetEndian => {
+                let property = match cur.val {
+                    Token::TargetName => TargetProperty::Name,
+                    Token::TargetCompiler => TargetProperty::Compiler,
+                    Token::TargetOs => TargetProperty::Os,
+                    _ => TargetProperty::Endian,
+                };
+                self.parse_token(Token::LeftParen)?;
+                let value = self.parse_string()?;
+                self.parse_token(Token::RightParen)?;
+                ExprType::Target(property, value)
+            }
             Token::Number(v) => ExprType::Lit(v),
-            Token::Sizeof | Token::SizeofBits => {
+            Token::Sizeof
+            | Token::SizeofBits
+            | Token::Alignof
+            | Token::AlignofBits
+            | Token::PointerAlignof
+            | Token::PointerAlignofBits
+            | Token::RequiredAlignof
+            | Token::RequiredAlignofBits => {
                 let kind = match cur.val {
                     Token::Sizeof => TypeExprType::Sizeof,
-                    _ => TypeExprType::SizeofBits,
+                    Token::SizeofBits => TypeExprType::SizeofBits,
+                    Token::Alignof => TypeExprType::Alignof,
+                    Token::AlignofBits => TypeExprType::AlignofBits,
+                    Token::PointerAlignof => TypeExprType::PointerAlignof,
+                    Token::PointerAlignofBits => TypeExprType::PointerAlignofBits,
+                    Token::RequiredAlignof => TypeExprType::RequiredAlignof,
+                    _ => TypeExprType::RequiredAlignofBits,
                 };
                 self.parse_token(Token::LeftParen)?;
                 let dst = self.parse_type()?;
@@ -217,7 +497,12 @@ impl Parser {
                 self.parse_token(Token::RightParen)?;
                 ExprType::Offsetof(kind, dst, indices)
             }
-            Token::Ident(i) => ExprType::Name(self.strings[i].clone()),
+            Token::Ident(i)
+                if self.tokens.get(self.pos).map(|t| t.val) == Some(Token::LeftParen) =>
+            {
+                self.parse_function_call(i, cur.span)?
+            }
+            Token::Ident(i) => ExprType::Name(self.parse_qualified_name(i)),
             _ => {
                 return Err(ParseError {
                     msg: format!("Unexpected {}. Expected an expression.", cur.val),
@@ -233,6 +518,29 @@ impl Parser {
         })
     }
 
+    fn parse_function_call(&mut self, name: usize, span: Span) -> ParseResult<ExprType> {
+        let ty = match &*self.strings[name] {
+            "min" => BinaryExprType::Min,
+            "max" => BinaryExprType::Max,
+            "align_up" => BinaryExprType::AlignUp,
+            _ => {
+                return Err(ParseError {
+                    msg: format!(
+                        "Unknown function {}. Expected `min`, `max`, or `align_up`.",
+                        self.strings[name]
+                    ),
+                    span,
+                })
+            }
+        };
+        self.parse_token(Token::LeftParen)?;
+        let left = self.parse_expr()?;
+        self.parse_token(Token::Comma)?;
+        let right = self.parse_expr()?;
+        self.parse_token(Token::RightParen)?;
+        Ok(ExprType::Binary(ty, Box::new(left), Box::new(right)))
+    }
+
     fn parse_offsetof_path(&mut self) -> ParseResult<Vec<Index>> {
         let mut res = vec![];
         loop {
@@ -277,13 +585,25 @@ impl Parser {
             self.type_id
         };
         let lo = self.peek()?.span.0;
-        let (layout, hi) = self.parse_static_type_layout()?;
+        let (layout, underlying_type, hi) = self.parse_static_type_layout()?;
         let layout_hi = hi.unwrap_or(lo);
         let annotations = self.parse_annotations()?;
-        let variant = self.parse_type_variant(id)?;
+        let mut variant = self.parse_type_variant(id)?;
+        if let Some(underlying_type) = underlying_type {
+            match &mut variant {
+                TypeVariant::Enum(e) => e.underlying_type = Some(underlying_type),
+                _ => {
+                    return Err(ParseError {
+                        msg: "Only enums can have an underlying type".to_string(),
+                        span: Span(lo, layout_hi),
+                    })
+                }
+            }
+        }
         Ok(Type {
             id,
             lo,
+            hi: self.tokens[self.pos - 1].span.1,
             layout,
             layout_hi,
             annotations,
@@ -296,17 +616,57 @@ impl Parser {
         match next.val {
             Token::Ident(id) => {
                 self.pos += 1;
-                Ok(TypeVariant::Name(self.strings[id].clone(), next.span))
+                let name = self.parse_qualified_name(id);
+                let args = self.parse_type_arguments()?;
+                let span = Span(next.span.0, self.tokens[self.pos - 1].span.1);
+                Ok(TypeVariant::Name(name, args, span))
             }
             Token::Typedef => self.parse_typedef(),
             Token::Opaque => self.parse_opaque(),
             Token::Enum => self.parse_enum(),
             Token::Struct | Token::Union => self.parse_record(parent_id),
+            Token::Repr => self.parse_rust_type(parent_id),
             Token::LeftBracket => self.parse_array(),
             _ => self.parse_builtin_type(),
         }
     }
 
+    fn parse_type_arguments(&mut self) -> ParseResult<Vec<TypeArgument>> {
+        let mut args = vec![];
+        if self.tokens.get(self.pos).map(|t| t.val) != Some(Token::LeftParen) {
+            return Ok(args);
+        }
+        self.pos += 1;
+        loop {
+            args.push(self.parse_type_argument()?);
+            let cur = self.next()?;
+            match cur.val {
+                Token::Comma => {}
+                Token::RightParen => return Ok(args),
+                _ => {
+                    return Err(ParseError {
+                        msg: format!("Unexpected {}. Expected `,` or `)`", cur.val),
+                        span: cur.span,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Parses a type argument as a type if possible and as an expression otherwise.
+    fn parse_type_argument(&mut self) -> ParseResult<TypeArgument> {
+        let (pos, type_id) = (self.pos, self.type_id);
+        if let Ok(ty) = self.parse_type() {
+            if let Some(Token::Comma | Token::RightParen) = self.tokens.get(self.pos).map(|t| t.val)
+            {
+                return Ok(TypeArgument::Type(ty));
+            }
+        }
+        self.pos = pos;
+        self.type_id = type_id;
+        Ok(TypeArgument::Expr(self.parse_expr()?))
+    }
+
     fn parse_typedef(&mut self) -> ParseResult<TypeVariant> {
         self.parse_token(Token::Typedef)?;
         let dst = self.parse_type()?;
@@ -384,26 +744,54 @@ impl Parser {
         Ok(TypeVariant::Builtin(bi))
     }
 
-    fn parse_static_type_layout(&mut self) -> ParseResult<(Option<TypeLayout>, Option<usize>)> {
+    fn parse_static_type_layout(&mut self) -> ParseResult<StaticTypeLayout> {
         if self.peek()?.val != Token::LeftBrace {
-            return Ok((None, None));
+            return Ok((None, None, None));
         }
-        let (size, field, pointer, required, span) = self.parse_type_layout(Self::parse_u64)?;
+        let mut underlying_type = None;
+        let (size, field, pointer, required, span) = self.parse_type_layout(
+            Self::parse_static_layout_value,
+            &mut [("underlying_type", &mut underlying_type)],
+        )?;
+        let underlying_type = match underlying_type {
+            Some(v) => Some(v.into_enum_underlying_type()?),
+            None => None,
+        };
         Ok((
             Some(TypeLayout {
-                size_bits: size,
-                field_alignment_bits: field,
-                pointer_alignment_bits: pointer,
-                required_alignment_bits: required.unwrap_or(BITS_PER_BYTE),
+                size_bits: size.into_u64()?,
+                field_alignment_bits: field.into_u64()?,
+                pointer_alignment_bits: pointer.into_u64()?,
+                required_alignment_bits: match required {
+                    Some(r) => r.into_u64()?,
+                    None => BITS_PER_BYTE,
+                },
             }),
+            underlying_type,
             Some(span.1),
         ))
     }
 
+    fn parse_static_layout_value(&mut self) -> ParseResult<Spanned<StaticLayoutValue>> {
+        let lo = self.peek()?.span.0;
+        let val = match self.peek()?.val {
+            Token::Number(_) => StaticLayoutValue::Int(self.parse_u64()?),
+            _ => match self.parse_builtin_type()? {
+                TypeVariant::Builtin(bi) => StaticLayoutValue::Type(bi),
+                _ => unreachable!(),
+            },
+        };
+        let hi = self.tokens[self.pos - 1].span.1;
+        Ok(Spanned {
+            val,
+            span: Span(lo, hi),
+        })
+    }
+
     fn parse_opaque(&mut self) -> ParseResult<TypeVariant> {
         self.parse_token(Token::Opaque)?;
         let (size, field, pointer, required, span) =
-            self.parse_type_layout(|slf| slf.parse_expr())?;
+            self.parse_type_layout(|slf| slf.parse_expr(), &mut [])?;
         Ok(TypeVariant::Opaque(OpaqueTypeLayout {
             size_bits: Box::new(size),
             pointer_alignment_bits: Box::new(pointer),
@@ -419,12 +807,26 @@ impl Parser {
 
     fn parse_enum(&mut self) -> ParseResult<TypeVariant> {
         self.parse_token(Token::Enum)?;
-        let mut expr = vec![];
+        let fixed_underlying_type = match self.peek()?.val {
+            Token::Colon => {
+                self.pos += 1;
+                match self.parse_builtin_type()? {
+                    TypeVariant::Builtin(bi) => Some(bi),
+                    _ => unreachable!(),
+                }
+            }
+            _ => None,
+        };
+        let mut variants = vec![];
         self.parse_brace_list(|slf| {
-            expr.push(slf.parse_top_level_expr()?);
+            variants.push(slf.parse_top_level_expr()?);
             Ok(())
         })?;
-        Ok(TypeVariant::Enum(expr))
+        Ok(TypeVariant::Enum(Enum {
+            fixed_underlying_type,
+            variants,
+            underlying_type: None,
+        }))
     }
 
     fn parse_annotations(&mut self) -> ParseResult<Vec<Annotation>> {
@@ -458,10 +860,16 @@ impl Parser {
                 };
                 Annotation::Aligned(val)
             }
+            Token::Alignas => {
+                self.parse_token(Token::LeftParen)?;
+                let val = self.parse_top_level_expr()?;
+                self.parse_token(Token::RightParen)?;
+                Annotation::Alignas(Box::new(val))
+            }
             _ => {
                 return Err(ParseError {
                     msg: format!(
-                        "Unexpected {}. Expected `pragma_pack`, `attr_packed`, or `align`.",
+                        "Unexpected {}. Expected the name of an annotation.",
                         cur.val
                     ),
                     span: cur.span,
@@ -491,25 +899,131 @@ impl Parser {
             Token::Union => RecordKind::Union,
             _ => unreachable!(),
         };
+        let mut fields = self.parse_record_fields(parent_id)?;
+        set_field_positions(&mut fields);
+        Ok(TypeVariant::Record(Record { kind, fields }))
+    }
+
+    fn parse_record_fields(&mut self, parent_id: usize) -> ParseResult<Vec<RecordField>> {
         let mut fields = vec![];
         self.parse_brace_list(|slf| {
             fields.push(slf.parse_record_field(parent_id)?);
             Ok(())
         })?;
-        let mut i = 0;
-        for field in &mut fields {
-            if field.name.is_some() {
-                field.pos = Some(i);
-                i += 1;
+        Ok(fields)
+    }
+
+    fn parse_rust_type(&mut self, parent_id: usize) -> ParseResult<TypeVariant> {
+        let lo = self.parse_token(Token::Repr)?.span.0;
+        self.parse_token(Token::LeftParen)?;
+        let (mut c, mut transparent, mut rust, mut int) = (false, false, false, None);
+        let hi = loop {
+            let cur = self.next()?;
+            match cur.val {
+                Token::Ident(id) if self.strings[id] == "C" => c = true,
+                Token::Ident(id) if self.strings[id] == "transparent" => transparent = true,
+                Token::Ident(id) if self.strings[id] == "Rust" => rust = true,
+                Token::U8 => int = Some(BuiltinType::U8),
+                Token::I8 => int = Some(BuiltinType::I8),
+                Token::U16 => int = Some(BuiltinType::U16),
+                Token::I16 => int = Some(BuiltinType::I16),
+                Token::U32 => int = Some(BuiltinType::U32),
+                Token::I32 => int = Some(BuiltinType::I32),
+                Token::U64 => int = Some(BuiltinType::U64),
+                Token::I64 => int = Some(BuiltinType::I64),
+                _ => {
+                    return Err(ParseError {
+                        msg: format!("Unexpected {}. Expected a representation.", cur.val),
+                        span: cur.span,
+                    })
+                }
+            }
+            let next = self.next()?;
+            match next.val {
+                Token::Comma => {}
+                Token::RightParen => break next.span.1,
+                _ => {
+                    return Err(ParseError {
+                        msg: format!("Unexpected {}. Expected `,` or `)`", next.val),
+                        span: next.span,
+                    })
+                }
             }
+        };
+        let span = Span(lo, hi);
+        let cur = self.next()?;
+        match cur.val {
+            Token::Struct => {
+                let repr = match (c, transparent, rust, int) {
+                    (true, false, false, None) => RustStructRepr::C,
+                    (false, true, false, None) => RustStructRepr::Transparent,
+                    _ => {
+                        return Err(ParseError {
+                            msg: "Expected `repr(C)` or `repr(transparent)`".to_string(),
+                            span,
+                        })
+                    }
+                };
+                let mut fields = self.parse_record_fields(parent_id)?;
+                set_field_positions(&mut fields);
+                Ok(TypeVariant::RustStruct(RustStruct { repr, fields }))
+            }
+            Token::Enum => {
+                let repr = match (c, transparent, rust, int) {
+                    (true, false, false, None) => RustEnumRepr::C,
+                    (false, false, false, Some(bi)) => RustEnumRepr::Int(bi),
+                    (true, false, false, Some(bi)) => RustEnumRepr::CInt(bi),
+                    (false, false, true, None) => RustEnumRepr::Rust,
+                    _ => {
+                        return Err(ParseError {
+                            msg: "Expected `repr(C)`, `repr(T)`, `repr(C, T)`, or `repr(Rust)`"
+                                .to_string(),
+                            span,
+                        })
+                    }
+                };
+                let mut variants = vec![];
+                self.parse_brace_list(|slf| {
+                    variants.push(slf.parse_rust_enum_variant(parent_id)?);
+                    Ok(())
+                })?;
+                set_variant_field_positions(&mut variants);
+                Ok(TypeVariant::RustEnum(RustEnum { repr, variants }))
+            }
+            _ => Err(ParseError {
+                msg: format!("Unexpected {}. Expected `struct` or `enum`.", cur.val),
+                span: cur.span,
+            }),
         }
-        Ok(TypeVariant::Record(Record { kind, fields }))
+    }
+
+    fn parse_rust_enum_variant(&mut self, parent_id: usize) -> ParseResult<RustEnumVariant> {
+        let (name, name_span) = self.parse_ident()?;
+        let fields = match self.peek()?.val {
+            Token::LeftBrace => self.parse_record_fields(parent_id)?,
+            _ => vec![],
+        };
+        let discriminant = match self.peek()?.val {
+            Token::Eq => {
+                self.pos += 1;
+                Some(Box::new(self.parse_top_level_expr()?))
+            }
+            _ => None,
+        };
+        Ok(RustEnumVariant {
+            name,
+            span: Span(name_span.0, self.tokens[self.pos - 1].span.1),
+            fields,
+            discriminant,
+        })
     }
 
     fn parse_record_field(&mut self, parent_id: usize) -> ParseResult<RecordField> {
+        let doc = self.parse_doc();
         let lo = self.peek()?.span.0;
-        let (layout, hi) = self.parse_field_layout()?;
+        let (layout, signed, hi) = self.parse_field_layout()?;
         let layout_hi = hi.unwrap_or(lo);
+        let condition = self.parse_condition()?;
         let annotations = self.parse_annotations()?;
         let cur = self.next()?;
         let name = match cur.val {
@@ -535,7 +1049,10 @@ impl Parser {
             pos: None,
             lo,
             layout,
+            signed,
             layout_hi,
+            doc,
+            condition,
             annotations,
             name,
             bit_width,
@@ -558,15 +1075,20 @@ impl Parser {
         Ok(num as u64)
     }
 
-    fn parse_field_layout(&mut self) -> ParseResult<(Option<FieldLayout>, Option<usize>)> {
+    fn parse_field_layout(&mut self) -> ParseResult<StaticFieldLayout> {
         if self.peek()?.val != Token::LeftBrace {
-            return Ok((None, None));
+            return Ok((None, None, None));
         }
         let mut size = None;
         let mut offset = None;
-        let keys = &mut [("size", &mut size), ("offset", &mut offset)][..];
-        let span = self.parse_key_value_list(Self::parse_u64, keys)?;
-        for key in keys {
+        let mut signedness = None;
+        let keys = &mut [
+            ("size", &mut size),
+            ("offset", &mut offset),
+            ("signedness", &mut signedness),
+        ][..];
+        let span = self.parse_key_value_list(Self::parse_field_layout_value, keys)?;
+        for key in &keys[..2] {
             if key.1.is_none() {
                 return Err(ParseError {
                     msg: format!("Missing key {}", key.0),
@@ -576,13 +1098,29 @@ impl Parser {
         }
         Ok((
             Some(FieldLayout {
-                offset_bits: offset.unwrap(),
-                size_bits: size.unwrap(),
+                offset_bits: offset.unwrap().into_u64()?,
+                size_bits: size.unwrap().into_u64()?,
             }),
+            signedness.map(|s| s.into_signedness()).transpose()?,
             Some(span.1),
         ))
     }
 
+    fn parse_field_layout_value(&mut self) -> ParseResult<Spanned<FieldLayoutValue>> {
+        let lo = self.peek()?.span.0;
+        let val = match self.peek()?.val {
+            Token::Signed | Token::Unsigned => {
+                FieldLayoutValue::Signedness(self.next()?.val == Token::Signed)
+            }
+            _ => FieldLayoutValue::Int(self.parse_u64()?),
+        };
+        let hi = self.tokens[self.pos - 1].span.1;
+        Ok(Spanned {
+            val,
+            span: Span(lo, hi),
+        })
+    }
+
     fn parse_brace_list<P>(&mut self, mut p: P) -> ParseResult<Span>
     where
         P: FnMut(&mut Self) -> ParseResult<()>,
@@ -657,7 +1195,11 @@ impl Parser {
         })
     }
 
-    fn parse_type_layout<V: Clone, P>(&mut self, p: P) -> ParseResult<(V, V, V, Option<V>, Span)>
+    fn parse_type_layout<V: Clone, P>(
+        &mut self,
+        p: P,
+        extra_keys: &mut [(&str, &mut Option<V>)],
+    ) -> ParseResult<(V, V, V, Option<V>, Span)>
     where
         P: Fn(&mut Self) -> ParseResult<V>,
     {
@@ -666,16 +1208,15 @@ impl Parser {
         let mut pointer_alignment = None;
         let mut required_alignment = None;
         let mut field_alignment = None;
-        let span = self.parse_key_value_list(
-            p,
-            &mut [
-                ("size", &mut size),
-                ("alignment", &mut alignment),
-                ("required_alignment", &mut required_alignment),
-                ("pointer_alignment", &mut pointer_alignment),
-                ("field_alignment", &mut field_alignment),
-            ],
-        )?;
+        let mut keys = vec![
+            ("size", &mut size),
+            ("alignment", &mut alignment),
+            ("required_alignment", &mut required_alignment),
+            ("pointer_alignment", &mut pointer_alignment),
+            ("field_alignment", &mut field_alignment),
+        ];
+        keys.extend(extra_keys.iter_mut().map(|(k, v)| (*k, &mut **v)));
+        let span = self.parse_key_value_list(p, &mut keys)?;
         let size = match size {
             Some(s) => s,
             _ => {
@@ -742,6 +1283,17 @@ impl Parser {
         }
     }
 
+    fn parse_string(&mut self) -> ParseResult<String> {
+        let cur = self.next()?;
+        match cur.val {
+            Token::String(id) => Ok(self.strings[id].clone()),
+            _ => Err(ParseError {
+                msg: format!("Unexpected {}. Expected string literal.", cur.val),
+                span: cur.span,
+            }),
+        }
+    }
+
     fn parse_ident(&mut self) -> ParseResult<(String, Span)> {
         let cur = self.parse_token(Token::Ident(0))?;
         match cur.val {
@@ -750,6 +1302,24 @@ impl Parser {
         }
     }
 
+    /// Parses the remainder of a name whose first identifier has already been consumed.
+    ///
+    /// The declarations of imported namespaces are referenced as `namespace.name`.
+    fn parse_qualified_name(&mut self, first: usize) -> String {
+        let mut name = self.strings[first].clone();
+        while self.pos + 1 < self.tokens.len() && self.tokens[self.pos].val == Token::Dot {
+            match self.tokens[self.pos + 1].val {
+                Token::Ident(id) => {
+                    name.push('.');
+                    name.push_str(&self.strings[id]);
+                    self.pos += 2;
+                }
+                _ => break,
+            }
+        }
+        name
+    }
+
     fn next(&mut self) -> ParseResult<Spanned<Token>> {
         let t = self.peek()?;
         self.pos += 1;
@@ -768,13 +1338,116 @@ impl Parser {
     }
 }
 
+pub(crate) fn set_field_positions(fields: &mut [RecordField]) {
+    let mut i = 0;
+    for field in fields {
+        if field.name.is_some() {
+            field.pos = Some(i);
+            i += 1;
+        }
+    }
+}
+
+pub(crate) fn set_variant_field_positions(variants: &mut [RustEnumVariant]) {
+    // Fields are numbered across all variants.
+    let mut i = 0;
+    for field in variants.iter_mut().flat_map(|v| &mut v.fields) {
+        if field.name.is_some() {
+            field.pos = Some(i);
+            i += 1;
+        }
+    }
+}
+
 fn precedence(token: Token) -> usize {
     match token {
         Token::Star | Token::Div | Token::Mod => 90,
         Token::Plus | Token::Minus => 80,
+        Token::Shl | Token::Shr => 75,
         Token::EqEq | Token::NotEq | Token::Le | Token::Lt | Token::Ge | Token::Gt => 70,
+        Token::And => 66,
+        Token::Caret => 64,
+        Token::Or => 62,
         Token::AndAnd => 60,
         Token::OrOr => 50,
         _ => 0,
     }
 }
+
+type StaticTypeLayout = (
+    Option<TypeLayout>,
+    Option<EnumUnderlyingType>,
+    Option<usize>,
+);
+
+type StaticFieldLayout = (Option<FieldLayout>, Option<bool>, Option<usize>);
+
+/// A value in the layout of a field.
+#[derive(Clone)]
+enum FieldLayoutValue {
+    Int(u64),
+    Signedness(bool),
+}
+
+impl Spanned<FieldLayoutValue> {
+    fn into_u64(self) -> ParseResult<u64> {
+        match self.val {
+            FieldLayoutValue::Int(n) => Ok(n),
+            FieldLayoutValue::Signedness(_) => Err(ParseError {
+                msg: "Expected an integer".to_string(),
+                span: self.span,
+            }),
+        }
+    }
+
+    fn into_signedness(self) -> ParseResult<bool> {
+        match self.val {
+            FieldLayoutValue::Signedness(s) => Ok(s),
+            FieldLayoutValue::Int(_) => Err(ParseError {
+                msg: "Expected `signed` or `unsigned`".to_string(),
+                span: self.span,
+            }),
+        }
+    }
+}
+
+/// A value in the layout of a type.
+#[derive(Clone)]
+enum StaticLayoutValue {
+    Int(u64),
+    Type(BuiltinType),
+}
+
+impl Spanned<StaticLayoutValue> {
+    fn into_u64(self) -> ParseResult<u64> {
+        match self.val {
+            StaticLayoutValue::Int(n) => Ok(n),
+            StaticLayoutValue::Type(_) => Err(ParseError {
+                msg: "Expected an integer".to_string(),
+                span: self.span,
+            }),
+        }
+    }
+
+    fn into_enum_underlying_type(self) -> ParseResult<EnumUnderlyingType> {
+        use BuiltinType::*;
+        let signed = match self.val {
+            StaticLayoutValue::Type(ty) => match ty {
+                SignedChar | Short | Int | Long | LongLong | I8 | I16 | I32 | I64 | I128 => {
+                    Some((ty, true))
+                }
+                UnsignedChar | UnsignedShort | UnsignedInt | UnsignedLong | UnsignedLongLong
+                | Bool | U8 | U16 | U32 | U64 | U128 => Some((ty, false)),
+                _ => None,
+            },
+            StaticLayoutValue::Int(_) => None,
+        };
+        match signed {
+            Some((ty, signed)) => Ok(EnumUnderlyingType { ty, signed }),
+            None => Err(ParseError {
+                msg: "Expected an integer type with known signedness".to_string(),
+                span: self.span,
+            }),
+        }
+    }
+}
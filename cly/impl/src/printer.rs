@@ -1,17 +1,71 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 use crate::ast::{
-    Annotation, Array, Declaration, DeclarationType, Expr, ExprType, Record, RecordField, Type,
-    TypeVariant,
+    Annotation, Array, Declaration, DeclarationType, Enum, Expr, ExprType, Record, RecordField,
+    RustEnum, Type, TypeVariant,
 };
-use std::fmt::{Display, Formatter, Result};
+use crate::converter::ConversionResult;
+use crate::{enhance_declarations, Project};
+use repc_impl::layout::BuiltinType;
+use std::fmt::{Display, Formatter, Result, Write};
 
 pub struct Printer<'a> {
     input: &'a str,
     d: &'a [Declaration],
+    strip: bool,
 }
 
 pub fn printer<'a>(input: &'a str, d: &'a [Declaration]) -> Printer<'a> {
-    Printer { input, d }
+    Printer {
+        input,
+        d,
+        strip: false,
+    }
+}
+
+/// Returns a printer that removes the layouts from the input instead of replacing them.
+pub(crate) fn stripper<'a>(input: &'a str, d: &'a [Declaration]) -> Printer<'a> {
+    Printer {
+        input,
+        d,
+        strip: true,
+    }
+}
+
+/// A line of an annotated file whose layouts differ from the computed layouts.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StaleLine {
+    /// The line number starting at 1.
+    pub line: usize,
+    /// The line with the computed layouts.
+    pub expected: String,
+    /// The line with the layouts in the file.
+    pub actual: String,
+}
+
+/// Compares the layouts in the root file of `project` with `layouts`.
+///
+/// Returns the lines with stale or missing layouts. The conditions of the project must
+/// have been evaluated.
+pub fn find_stale_layouts(project: &Project, layouts: &ConversionResult) -> Vec<StaleLine> {
+    let input = project.root_input();
+    let declarations = project.root_declarations();
+    // Printing the parsed declarations normalizes the layouts in the file so that only
+    // differences in the values are reported.
+    let actual = printer(input, declarations).to_string();
+    let enhanced = enhance_declarations(declarations, layouts);
+    let expected = printer(input, &enhanced).to_string();
+    // Layouts never contain line breaks.
+    expected
+        .lines()
+        .zip(actual.lines())
+        .enumerate()
+        .filter(|(_, (e, a))| e != a)
+        .map(|(i, (e, a))| StaleLine {
+            line: i + 1,
+            expected: e.to_string(),
+            actual: a.to_string(),
+        })
+        .collect()
 }
 
 impl<'a> Display for Printer<'a> {
@@ -21,6 +75,7 @@ impl<'a> Display for Printer<'a> {
             let mut printer = Printer_ {
                 input: self.input,
                 pos,
+                strip: self.strip,
                 f,
             };
             printer.print_decl(d)?;
@@ -33,6 +88,7 @@ impl<'a> Display for Printer<'a> {
 struct Printer_<'a, 'b> {
     input: &'a str,
     pos: usize,
+    strip: bool,
     f: &'a mut Formatter<'b>,
 }
 
@@ -41,38 +97,24 @@ impl<'a, 'b> Printer_<'a, 'b> {
         match &d.ty {
             DeclarationType::Type(ty) => self.print_type(ty),
             DeclarationType::Const(c) => self.print_top_level_expr(c),
+            DeclarationType::Assert(_) => Ok(()),
         }
     }
 
     fn print_type(&mut self, t: &Type) -> Result {
         self.set_pos(t.lo)?;
-        if let Some(l) = t.layout {
-            write!(self.f, "{{ size: {}, ", l.size_bits)?;
-            if l.field_alignment_bits == l.pointer_alignment_bits {
-                write!(self.f, "alignment: {}", l.field_alignment_bits)?;
-            } else {
-                write!(
-                    self.f,
-                    "field_alignment: {}, pointer_alignment: {}",
-                    l.field_alignment_bits, l.pointer_alignment_bits,
-                )?;
-            }
-            if l.required_alignment_bits != 8 {
-                write!(
-                    self.f,
-                    ", required_alignment: {}",
-                    l.required_alignment_bits
-                )?;
-            }
-            write!(self.f, " }}")?;
+        if !self.strip {
+            write_type_layout(self.f, t)?;
         }
-        self.pos = t.layout_hi;
+        self.skip_layout(t.layout_hi);
         self.print_annotations(&t.annotations)?;
         match &t.variant {
             TypeVariant::Record(r) => self.print_record(r),
             TypeVariant::Typedef(t) => self.print_type(t),
             TypeVariant::Array(a) => self.print_array(a),
             TypeVariant::Enum(a) => self.print_enum(a),
+            TypeVariant::RustStruct(s) => self.print_record_fields(&s.fields),
+            TypeVariant::RustEnum(e) => self.print_rust_enum(e),
             _ => Ok(()),
         }
     }
@@ -84,8 +126,8 @@ impl<'a, 'b> Printer_<'a, 'b> {
         Ok(())
     }
 
-    fn print_enum(&mut self, a: &[Expr]) -> Result {
-        for a in a {
+    fn print_enum(&mut self, a: &Enum) -> Result {
+        for a in &a.variants {
             self.print_top_level_expr(a)?;
         }
         Ok(())
@@ -97,6 +139,7 @@ impl<'a, 'b> Printer_<'a, 'b> {
             Annotation::AttrPacked => Ok(()),
             Annotation::Aligned(None) => Ok(()),
             Annotation::Aligned(Some(e)) => self.print_top_level_expr(e),
+            Annotation::Alignas(e) => self.print_top_level_expr(e),
         }
     }
 
@@ -108,7 +151,21 @@ impl<'a, 'b> Printer_<'a, 'b> {
     }
 
     fn print_record(&mut self, r: &Record) -> Result {
-        for f in &r.fields {
+        self.print_record_fields(&r.fields)
+    }
+
+    fn print_rust_enum(&mut self, e: &RustEnum) -> Result {
+        for v in &e.variants {
+            self.print_record_fields(&v.fields)?;
+            if let Some(d) = &v.discriminant {
+                self.print_top_level_expr(d)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn print_record_fields(&mut self, fields: &[RecordField]) -> Result {
+        for f in fields {
             self.print_record_field(f)?;
         }
         Ok(())
@@ -116,14 +173,10 @@ impl<'a, 'b> Printer_<'a, 'b> {
 
     fn print_record_field(&mut self, f: &RecordField) -> Result {
         self.set_pos(f.lo)?;
-        if let Some(l) = f.layout {
-            write!(
-                self.f,
-                "{{ offset: {}, size: {} }}",
-                l.offset_bits, l.size_bits
-            )?;
+        if !self.strip {
+            write_field_layout(self.f, f)?;
         }
-        self.pos = f.layout_hi;
+        self.skip_layout(f.layout_hi);
         self.print_annotations(&f.annotations)?;
         self.print_type(&f.ty)?;
         if let Some(bw) = &f.bit_width {
@@ -137,16 +190,102 @@ impl<'a, 'b> Printer_<'a, 'b> {
             return Ok(());
         }
         self.set_pos(e.span.0)?;
-        if let Some(l) = e.value {
+        if let (Some(l), false) = (e.value, self.strip) {
             write!(self.f, "{{{}}}", l)?;
         }
-        self.pos = e.value_hi;
+        self.skip_layout(e.value_hi);
         Ok(())
     }
 
+    /// Skips the layout in the input that ends at `hi`.
+    ///
+    /// When stripping, the whitespace after the layout is skipped as well.
+    fn skip_layout(&mut self, hi: usize) {
+        if self.strip && hi > self.pos {
+            let rest = &self.input[hi..];
+            self.pos = hi + rest.len() - rest.trim_start_matches(&[' ', '\t'][..]).len();
+        } else {
+            self.pos = hi;
+        }
+    }
+
     fn set_pos(&mut self, pos: usize) -> Result {
         self.f.write_str(&self.input[self.pos..pos])?;
         self.pos = pos;
         Ok(())
     }
 }
+
+/// Writes the layout of `t`, e.g. `{ size: 32, alignment: 32 }`, if it is known.
+pub(crate) fn write_type_layout(f: &mut impl Write, t: &Type) -> Result {
+    if let Some(l) = t.layout {
+        write!(f, "{{ size: {}, ", l.size_bits)?;
+        if l.field_alignment_bits == l.pointer_alignment_bits {
+            write!(f, "alignment: {}", l.field_alignment_bits)?;
+        } else {
+            write!(
+                f,
+                "field_alignment: {}, pointer_alignment: {}",
+                l.field_alignment_bits, l.pointer_alignment_bits,
+            )?;
+        }
+        if l.required_alignment_bits != 8 {
+            write!(f, ", required_alignment: {}", l.required_alignment_bits)?;
+        }
+        if let TypeVariant::Enum(Enum {
+            underlying_type: Some(u),
+            ..
+        }) = &t.variant
+        {
+            write!(f, ", underlying_type: {}", builtin_type_name(u.ty))?;
+        }
+        write!(f, " }}")?;
+    }
+    Ok(())
+}
+
+/// Writes the layout of `field`, e.g. `{ offset: 0, size: 32 }`, if it is known.
+pub(crate) fn write_field_layout(f: &mut impl Write, field: &RecordField) -> Result {
+    if let Some(l) = field.layout {
+        write!(f, "{{ offset: {}, size: {}", l.offset_bits, l.size_bits)?;
+        match field.signed {
+            Some(true) => write!(f, ", signedness: signed }}")?,
+            Some(false) => write!(f, ", signedness: unsigned }}")?,
+            None => write!(f, " }}")?,
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn builtin_type_name(bi: BuiltinType) -> &'static str {
+    match bi {
+        BuiltinType::Unit => "unit",
+        BuiltinType::Bool => "bool",
+        BuiltinType::U8 => "u8",
+        BuiltinType::I8 => "i8",
+        BuiltinType::U16 => "u16",
+        BuiltinType::I16 => "i16",
+        BuiltinType::U32 => "u32",
+        BuiltinType::I32 => "i32",
+        BuiltinType::U64 => "u64",
+        BuiltinType::I64 => "i64",
+        BuiltinType::U128 => "u128",
+        BuiltinType::I128 => "i128",
+        BuiltinType::Char => "char",
+        BuiltinType::SignedChar => "signed char",
+        BuiltinType::UnsignedChar => "unsigned char",
+        BuiltinType::Short => "short",
+        BuiltinType::UnsignedShort => "unsigned short",
+        BuiltinType::Int => "int",
+        BuiltinType::UnsignedInt => "unsigned int",
+        BuiltinType::Long => "long",
+        BuiltinType::UnsignedLong => "unsigned long",
+        BuiltinType::LongLong => "long long",
+        BuiltinType::UnsignedLongLong => "unsigned long long",
+        BuiltinType::F32 => "f32",
+        BuiltinType::F64 => "f64",
+        BuiltinType::Float => "float",
+        BuiltinType::Double => "double",
+        BuiltinType::Pointer => "ptr",
+    }
+}
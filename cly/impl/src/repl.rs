@@ -0,0 +1,269 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//! An interactive session for cly.
+//!
+//! Every input is a command starting with `:`, declarations, or an expression. The
+//! layouts of declarations are printed as soon as they are entered and the declarations
+//! stay in scope for later inputs. A declaration replaces earlier declarations with the
+//! same name. Expressions are evaluated and their values are printed.
+//!
+//! An input continues on the next line while it contains unclosed brackets or ends with an
+//! operator. An empty line ends such an input.
+use crate::ast::{Declaration, DeclarationType, Span};
+use crate::converter::{ConversionResult, Warning};
+use crate::loader::load_session;
+use crate::{compute_project_layouts, enhance_declarations, parser, printer, to_span, Project};
+use anyhow::{anyhow, Result};
+use repc_impl::builder::Options;
+use repc_impl::target::Target;
+use std::env;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+const PROMPT: &str = "cly> ";
+const CONTINUATION_PROMPT: &str = "...> ";
+
+/// The maximum number of inputs that are read from the history file.
+const MAX_HISTORY: usize = 1000;
+
+const HELP: &str = "\
+Enter declarations to print their layouts or expressions to print their values.
+
+Commands:
+    :target [name]  Prints or sets the target
+    :show           Prints the layouts of all declarations
+    :reset          Removes all declarations
+    :history        Prints the previous inputs
+    :help           Prints this message
+    :quit           Exits the session
+";
+
+/// Runs an interactive session that reads inputs from `input` and writes the results to
+/// `output` until the end of `input` or until the `:quit` command.
+///
+/// If `history` is set, previous inputs are read from this file and new inputs are
+/// appended to it.
+pub fn run(
+    input: impl BufRead,
+    output: impl Write,
+    target: Target,
+    options: Options,
+    history: Option<&Path>,
+) -> Result<()> {
+    let mut input = input;
+    let mut repl = Repl {
+        output,
+        target,
+        options,
+        dir: env::current_dir().unwrap_or_default(),
+        entries: vec![],
+        history: vec![],
+        history_file: history.map(|p| p.to_owned()),
+    };
+    repl.read_history();
+    let mut text = String::new();
+    loop {
+        let prompt = match text.is_empty() {
+            true => PROMPT,
+            false => CONTINUATION_PROMPT,
+        };
+        write!(repl.output, "{}", prompt)?;
+        repl.output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            writeln!(repl.output)?;
+            break;
+        }
+        let line = line.trim_end();
+        if text.is_empty() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(command) = line.trim().strip_prefix(':') {
+                repl.add_history(line.trim());
+                if !repl.command(command)? {
+                    break;
+                }
+                continue;
+            }
+        }
+        if line.trim().is_empty() {
+            // An empty line ends an incomplete input so that errors can be reported.
+        } else {
+            text.push_str(line);
+            text.push('\n');
+            if parser::is_incomplete(text.as_bytes()) {
+                continue;
+            }
+        }
+        let entry = std::mem::take(&mut text);
+        repl.add_history(entry.trim_end());
+        repl.evaluate(entry)?;
+    }
+    repl.output.flush()?;
+    Ok(())
+}
+
+struct Repl<W> {
+    output: W,
+    target: Target,
+    options: Options,
+    /// The directory relative to which imports are resolved.
+    dir: PathBuf,
+    /// The accepted inputs that contain declarations.
+    entries: Vec<String>,
+    /// All inputs in the order in which they were entered.
+    history: Vec<String>,
+    history_file: Option<PathBuf>,
+}
+
+impl<W: Write> Repl<W> {
+    /// Executes a command. Returns `false` if the session should end.
+    fn command(&mut self, command: &str) -> Result<bool> {
+        let mut words = command.split_whitespace();
+        match (words.next().unwrap_or(""), words.next(), words.next()) {
+            ("quit" | "q", None, _) => return Ok(false),
+            ("help" | "h", None, _) => write!(self.output, "{}", HELP)?,
+            ("target", None, _) => writeln!(self.output, "{}", self.target.name())?,
+            ("target", Some(name), None) => match name.parse() {
+                Ok(t) => self.target = t,
+                Err(e) => writeln!(self.output, "{}", e)?,
+            },
+            ("show", None, _) => match self.show() {
+                Ok(output) => write!(self.output, "{}", output)?,
+                Err(e) => writeln!(self.output, "{:#}", e)?,
+            },
+            ("reset", None, _) => self.entries.clear(),
+            ("history", None, _) => {
+                for (i, input) in self.history.iter().enumerate() {
+                    let input = input.replace('\n', "\n     ");
+                    writeln!(self.output, "{:>4} {}", i + 1, input)?;
+                }
+            }
+            _ => writeln!(
+                self.output,
+                "Unknown command :{}. Type :help for a list of commands.",
+                command
+            )?,
+        }
+        Ok(true)
+    }
+
+    /// Evaluates an input that contains declarations or an expression.
+    fn evaluate(&mut self, entry: String) -> Result<()> {
+        let is_expr = parser::is_expr(entry.as_bytes());
+        let res = match is_expr {
+            true => self.evaluate_expr(&entry),
+            false => self.evaluate_declarations(&entry),
+        };
+        match res {
+            Ok(output) => {
+                write!(self.output, "{}", output)?;
+                if !is_expr {
+                    self.entries.push(entry);
+                }
+            }
+            Err(e) => writeln!(self.output, "{:#}", e)?,
+        }
+        Ok(())
+    }
+
+    fn evaluate_declarations(&self, entry: &str) -> Result<String> {
+        let (project, spans, layouts, warnings) = self.load(Some(entry), |_| Ok(()))?;
+        let span = *spans.last().unwrap();
+        let mut output = annotated(&project, &layouts, span);
+        // Warnings about earlier entries were already printed when they were entered.
+        for w in warnings {
+            if span.0 <= w.span.0 && w.span.0 < span.1 {
+                output.push_str(&format!("Warning: {}\n", w.describe_in_project(&project)));
+            }
+        }
+        Ok(output)
+    }
+
+    fn evaluate_expr(&self, entry: &str) -> Result<String> {
+        let (_, _, layouts, _) = self.load(Some(""), |project| {
+            // The expression is evaluated as an unnamed constant in the empty last entry.
+            let lo = project.input.len();
+            project.input.push_str(entry);
+            project.files.last_mut().unwrap().span.1 = project.input.len();
+            let expr = parser::parse_expr(project.input.as_bytes(), lo)
+                .map_err(|e| anyhow!("At {}: {}", to_span(&*project, e.span), e.msg))?;
+            project.declarations.push(Declaration {
+                name: String::new(),
+                span: expr.span,
+                lo: expr.span.0,
+                doc: None,
+                params: vec![],
+                condition: None,
+                ty: DeclarationType::Const(expr),
+            });
+            Ok(())
+        })?;
+        Ok(format!("{}\n", layouts.consts[""]))
+    }
+
+    /// Returns all accepted entries annotated with their layouts for the current target.
+    fn show(&self) -> Result<String> {
+        let (project, spans, layouts, _) = self.load(None, |_| Ok(()))?;
+        Ok(spans
+            .into_iter()
+            .map(|span| annotated(&project, &layouts, span))
+            .collect())
+    }
+
+    /// Loads the accepted entries followed by `entry` and computes their layouts.
+    ///
+    /// `f` is called with the project before the layouts are computed.
+    fn load(
+        &self,
+        entry: Option<&str>,
+        f: impl FnOnce(&mut Project) -> Result<()>,
+    ) -> Result<(Project, Vec<Span>, ConversionResult, Vec<Warning>)> {
+        let mut entries: Vec<_> = self.entries.iter().map(|e| &**e).collect();
+        entries.extend(entry);
+        let (mut project, spans) = load_session(&entries, &self.dir)?;
+        f(&mut project)?;
+        project.evaluate_conditions(self.target, self.options)?;
+        let (layouts, warnings) = compute_project_layouts(&project, self.target, self.options)?;
+        Ok((project, spans, layouts, warnings))
+    }
+
+    fn add_history(&mut self, input: &str) {
+        self.history.push(input.to_owned());
+        if let Some(path) = &self.history_file {
+            // The session works without a history file, so failures are ignored.
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                let _ = write!(file, "{}\n\n", input);
+            }
+        }
+    }
+
+    fn read_history(&mut self) {
+        let input = match &self.history_file {
+            Some(path) => fs::read_to_string(path).unwrap_or_default(),
+            None => return,
+        };
+        let inputs: Vec<_> = input
+            .split("\n\n")
+            .map(|i| i.trim())
+            .filter(|i| !i.is_empty())
+            .collect();
+        let skip = inputs.len().saturating_sub(MAX_HISTORY);
+        self.history = inputs[skip..].iter().map(|i| i.to_string()).collect();
+    }
+}
+
+/// Returns the entry at `span` annotated with `layouts`.
+fn annotated(project: &Project, layouts: &ConversionResult, span: Span) -> String {
+    let declarations: Vec<_> = project
+        .declarations
+        .iter()
+        .filter(|d| span.0 <= d.span.0 && d.span.0 < span.1)
+        .cloned()
+        .collect();
+    let enhanced = enhance_declarations(&declarations, layouts);
+    let printed = printer(&project.input[..span.1], &enhanced).to_string();
+    printed[span.0..].to_string()
+}
@@ -19,3 +19,47 @@ impl Display for ParseError {
 impl Error for ParseError {}
 
 pub type ParseResult<T> = std::result::Result<T, ParseError>;
+
+/// An error in the input of the converter or the loader.
+///
+/// The error is displayed as its message, if any, followed by one line of the form
+/// `At <location>: <message>` per label.
+#[derive(Debug, Clone)]
+pub struct SourceError {
+    /// The message of an error without location, e.g. a layout that cannot be computed.
+    pub msg: Option<String>,
+    /// The locations of the error. The first label describes the error unless `msg` is
+    /// set. Further labels describe its context, e.g. the instantiations of generic
+    /// declarations that caused it.
+    pub labels: Vec<Label>,
+}
+
+/// A location in the input together with a message.
+#[derive(Debug, Clone)]
+pub struct Label {
+    /// The index of the file in [`Project::files`](crate::Project::files) that contains the
+    /// location or `None` if the input is not a project.
+    pub file: Option<usize>,
+    /// The location relative to the start of the file.
+    pub span: Span,
+    pub msg: String,
+    /// The location as printed, e.g. `path:1:2 - 1:5`.
+    pub(crate) location: String,
+}
+
+impl Display for SourceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut sep = "";
+        if let Some(msg) = &self.msg {
+            f.write_str(msg)?;
+            sep = "\n";
+        }
+        for label in &self.labels {
+            write!(f, "{}At {}: {}", sep, label.location, label.msg)?;
+            sep = "\n";
+        }
+        Ok(())
+    }
+}
+
+impl Error for SourceError {}
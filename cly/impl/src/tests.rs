@@ -3,6 +3,8 @@ use anyhow::{anyhow, bail, Context, Result};
 use isnt::std_1::vec::IsntVecExt;
 use rayon::iter::IntoParallelRefIterator;
 use rayon::iter::ParallelIterator;
+use repc_impl::builder::Options;
+use repc_impl::layout::{Type, TypeLayout, TypeVariant};
 use repc_impl::target::Target;
 use std::path::Path;
 use std::sync::Mutex;
@@ -42,13 +44,25 @@ fn process_dir(dir: &Path) -> Result<bool> {
     let input_path = dir.join("input.txt");
     let input = std::fs::read_to_string(&input_path)?;
     let declarations = crate::parse(&input).context("Parsing failed")?;
+    let declarations =
+        crate::evaluate_conditions(&input, &declarations, TARGET, Default::default())?;
 
-    let actual_conversion_result = crate::compute_layouts(&input, &declarations, TARGET)?;
+    let mut actual_conversion_result = crate::compute_layouts(&input, &declarations, TARGET)?;
+    // The expected output does not contain the signedness of bit-fields.
+    for ty in actual_conversion_result.types.values_mut() {
+        strip_bit_field_signedness(ty);
+    }
 
     let expected_file = dir.join("expected.txt");
     if expected_file.exists() {
         let expected = std::fs::read_to_string(&expected_file)?;
         let expected_declarations = crate::parse(&expected)?;
+        let expected_declarations = crate::evaluate_conditions(
+            &expected,
+            &expected_declarations,
+            TARGET,
+            Default::default(),
+        )?;
         let expected_conversion_result = crate::extract_layouts(&expected, &expected_declarations)?;
 
         if actual_conversion_result == expected_conversion_result {
@@ -61,3 +75,853 @@ fn process_dir(dir: &Path) -> Result<bool> {
     std::fs::write(actual_file, crate::printer(&input, &enhanced).to_string())?;
     Ok(false)
 }
+
+fn strip_bit_field_signedness(ty: &mut Type<TypeLayout>) {
+    match &mut ty.variant {
+        TypeVariant::Typedef(dst) => strip_bit_field_signedness(dst),
+        TypeVariant::Array(a) => strip_bit_field_signedness(&mut a.element_type),
+        TypeVariant::Record(r) => r.fields.iter_mut().for_each(|f| {
+            f.signed = None;
+            strip_bit_field_signedness(&mut f.ty)
+        }),
+        TypeVariant::RustStruct(s) => s
+            .fields
+            .iter_mut()
+            .for_each(|f| strip_bit_field_signedness(&mut f.ty)),
+        TypeVariant::RustEnum(e) => e
+            .variants
+            .iter_mut()
+            .flat_map(|v| &mut v.fields)
+            .for_each(|f| strip_bit_field_signedness(&mut f.ty)),
+        TypeVariant::Builtin(_) | TypeVariant::Opaque(_) | TypeVariant::Enum(_) => {}
+    }
+}
+
+#[test]
+fn decode() -> Result<()> {
+    let input = "
+        const RED = 0
+        const GREEN = 1
+        Color = enum { RED, GREEN, }
+        A = struct {
+            c char,
+            a int:3,
+            b Color:2,
+            d [2]short,
+            e Color,
+        }
+    ";
+    let declarations = crate::parse(input)?;
+    let layouts = crate::compute_layouts(input, &declarations, TARGET)?;
+    let bytes = [
+        0x41, 0xff, 0xff, 0xff, 0x0e, 0, 0, 0, 1, 0, 0xfe, 0xff, 1, 0, 0, 0, 0xaa,
+    ];
+    let fields = crate::decode(&declarations, &layouts, TARGET, "A", &bytes)?;
+    let lines: Vec<_> = fields.iter().map(|f| f.to_string()).collect();
+    assert_eq!(
+        lines,
+        [
+            "A.c @ 0 = 65",
+            "<padding> @ 1 = ff ff ff",
+            "A.a @ 4.0:3 = -2",
+            "A.b @ 4.3:2 = 1 (GREEN)",
+            "<padding> @ 5 = 00 00 00",
+            "A.d[0] @ 8 = 1",
+            "A.d[1] @ 10 = -2",
+            "A.e @ 12 = 1 (GREEN)",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn encode() -> Result<()> {
+    let input = "
+        const RED = 0
+        const GREEN = 1
+        Color = enum { RED, GREEN, }
+        A = struct {
+            c char,
+            a int:3,
+            b Color:2,
+            d [2]short,
+            e Color,
+        }
+    ";
+    let declarations = crate::parse(input)?;
+    let layouts = crate::compute_layouts(input, &declarations, TARGET)?;
+    let values = "
+        c = 65
+        a = -2
+        b = 'GREEN'
+        d = [1]
+        e = 1
+    "
+    .parse()?;
+    let bytes = crate::encode(&declarations, &layouts, TARGET, "A", &values, 0xaa)?;
+    // The unused bits of the bit-field storage unit are padding.
+    assert_eq!(
+        bytes,
+        [0x41, 0xaa, 0xaa, 0xaa, 0xae, 0xaa, 0xaa, 0xaa, 1, 0, 0, 0, 1, 0, 0, 0]
+    );
+    let json = r#"{ "c": 65, "a": -2, "b": "GREEN", "d": [1], "e": 1 }"#;
+    assert_eq!(crate::parse_values(json)?, values);
+
+    for (values, msg) in [
+        ("c = 128", "A.c: The value 128 is out of range"),
+        ("a = 4", "A.a: The value 4 is out of range"),
+        ("d = [1, 2, 3]", "A.d: The array has only 2 elements"),
+        ("x = 1", "A: Unknown field x"),
+    ] {
+        let err = crate::encode(&declarations, &layouts, TARGET, "A", &values.parse()?, 0);
+        assert_eq!(err.unwrap_err().to_string(), msg);
+    }
+    Ok(())
+}
+
+#[test]
+fn encode_option_like_enum() -> Result<()> {
+    let input = "
+        O = repr(Rust) enum {
+            None,
+            Some { p ptr },
+        }
+        S = repr(C) struct { o O }
+    ";
+    let declarations = crate::parse(input)?;
+    let layouts = crate::compute_layouts(input, &declarations, TARGET)?;
+    let encode = |values: &str| -> Result<Vec<u8>> {
+        crate::encode(&declarations, &layouts, TARGET, "S", &values.parse()?, 0xff)
+    };
+    // The variant without fields is the null pointer.
+    assert_eq!(encode("")?, [0; 8]);
+    assert_eq!(encode("o = 'None'")?, [0; 8]);
+    let some = encode("o = { Some = { p = 0x0807060504030201 } }")?;
+    assert_eq!(some, [1, 2, 3, 4, 5, 6, 7, 8]);
+    Ok(())
+}
+
+#[test]
+fn imports() -> Result<()> {
+    let dir = Path::new("importfiles");
+    let project = crate::load(&dir.join("main.cly"))?;
+    let (layouts, _) = crate::compute_project_layouts(&project, TARGET, Default::default())?;
+    let names: Vec<_> = project
+        .root_declarations()
+        .iter()
+        .map(|d| &*d.name)
+        .collect();
+    assert_eq!(names, ["A"]);
+    assert_eq!(layouts.types["A"].layout.size_bits, 192);
+    assert_eq!(layouts.types["net.Addr"].layout.size_bits, 96);
+    assert_eq!(layouts.consts["net.PORTS"], 3);
+    // common.cly is imported into the root namespace and into the namespace `net`.
+    assert_eq!(project.files.len(), 4);
+    assert_eq!(layouts.types["net.Pair"].layout.size_bits, 64);
+
+    let err = crate::load(&dir.join("cycle_a.cly")).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "At {}:2:0 - 2:25: Circular import: {} -> {} -> {}",
+            dir.join("cycle_b.cly").display(),
+            dir.join("cycle_a.cly").display(),
+            dir.join("cycle_b.cly").display(),
+            dir.join("cycle_a.cly").display(),
+        )
+    );
+
+    let project = crate::load(&dir.join("error.cly"))?;
+    let err = crate::compute_project_layouts(&project, TARGET, Default::default()).err();
+    assert_eq!(
+        err.unwrap().to_string(),
+        format!(
+            "At {}:5:6 - 5:15: The referenced type c.Unknown is not declared",
+            dir.join("error.cly").display(),
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn conditions() -> Result<()> {
+    let input = r#"
+        @if(target_os("windows")) Handle = typedef ptr
+        @if(!target_os("windows")) Handle = typedef u16
+        A = struct {
+            h Handle,
+            @if(target_endian("big")) big u8,
+            @if(target_name("x86_64-*") && TARGET_POINTER_WIDTH == 64) x86_64 u32,
+        }
+    "#;
+    let declarations = crate::parse(input)?;
+    let size = |target| -> Result<u64> {
+        let declarations =
+            crate::evaluate_conditions(input, &declarations, target, Default::default())?;
+        let layouts = crate::compute_layouts(input, &declarations, target)?;
+        Ok(layouts.types["A"].layout.size_bits)
+    };
+    assert_eq!(size(Target::X86_64PcWindowsMsvc)?, 128);
+    assert_eq!(size(Target::X86_64UnknownLinuxGnu)?, 64);
+    assert_eq!(size(Target::S390xUnknownLinuxGnu)?, 32);
+
+    let err = crate::compute_layouts(input, &declarations, TARGET)
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "At 2:12 - 2:32: Conditions must be evaluated before the layouts are computed"
+    );
+    let input = r#"@if(target_os("plan9")) A = int"#;
+    let err = crate::evaluate_conditions(input, &crate::parse(input)?, TARGET, Default::default())
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "At 1:4 - 1:22: Unknown operating system plan9"
+    );
+
+    let matches = |pattern: &str, target| -> Result<bool> {
+        let input = format!(r#"@if(target_name("{}")) A = int"#, pattern);
+        let declarations =
+            crate::evaluate_conditions(&input, &crate::parse(&input)?, target, Default::default())?;
+        Ok(!declarations.is_empty())
+    };
+    let linux = Target::X86_64UnknownLinuxGnu;
+    assert!(matches("*", linux)?);
+    assert!(matches("x86_64-*-linux-*", linux)?);
+    assert!(matches("*-gn?", linux)?);
+    assert!(matches("x86_64-unknown-linux-gnu*", linux)?);
+    assert!(!matches("*-linux", linux)?);
+    assert!(!matches("x86_64-?", linux)?);
+    // Must not take exponential time.
+    assert!(!matches(&format!("{}b", "*n".repeat(30)), linux)?);
+
+    let input = r#"
+        E = enum { 1 }
+        @if(sizeof(E) == 1) A = int
+    "#;
+    let declarations = crate::parse(input)?;
+    let evaluate = |short_enums| -> Result<usize> {
+        let options = Options { short_enums };
+        Ok(crate::evaluate_conditions(input, &declarations, linux, options)?.len())
+    };
+    assert_eq!(evaluate(false)?, 1);
+    assert_eq!(evaluate(true)?, 2);
+    Ok(())
+}
+
+#[test]
+fn assertions() -> Result<()> {
+    let input = r#"
+        const N = 4
+        A = struct { a [N]char }
+        assert sizeof(A) == N, "A has the wrong size"
+    "#;
+    crate::compute_layouts(input, &crate::parse(input)?, TARGET)?;
+
+    let input = "const N = 4\nA = struct { a [N]char }\nassert sizeof(A) == N + 1, \"A has the wrong size\"\nassert N > 5\n";
+    let err = crate::compute_layouts(input, &crate::parse(input)?, TARGET)
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "At 3:7 - 3:25: Assertion failed: A has the wrong size
+    sizeof(A) = 4
+    N + 1 = 5
+    N = 4
+At 4:7 - 4:12: Assertion failed
+    N = 4"
+    );
+    Ok(())
+}
+
+#[test]
+fn expression_errors() -> Result<()> {
+    let err = |input: &str| -> Result<String> {
+        let declarations = crate::parse(input)?;
+        Ok(crate::compute_layouts(input, &declarations, TARGET)
+            .err()
+            .unwrap()
+            .to_string())
+    };
+    assert_eq!(
+        err("const X = 1 << 127")?,
+        "At 1:10 - 1:18: Expression overflow"
+    );
+    assert_eq!(
+        err("const X = 1 >> 128")?,
+        "At 1:15 - 1:18: Invalid shift amount 128"
+    );
+    assert_eq!(
+        err("const X = align_up(3, 0)")?,
+        "At 1:22 - 1:23: Alignment must be positive"
+    );
+    assert_eq!(
+        crate::parse("const X = foo(1, 2)").unwrap_err().to_string(),
+        "At 1:10 - 1:13: Unknown function foo. Expected `min`, `max`, or `align_up`."
+    );
+    Ok(())
+}
+
+#[test]
+fn generics() -> Result<()> {
+    let input = r#"
+        Ring(T, N) = struct { head u32, tail u32, data [N]T }
+        const CAP = 4
+        A = Ring(u64, CAP)
+        B = struct { r Ring(u8, CAP * 2), s Ring(A, 1) }
+        const O = offsetof(B, s.data[0].data[3])
+    "#;
+    let layouts = crate::compute_layouts(input, &crate::parse(input)?, TARGET)?;
+    assert!(!layouts.types.contains_key("Ring"));
+    assert_eq!(layouts.types["A"].layout.size_bits, 320);
+    assert_eq!(layouts.types["B"].layout.size_bits, 128 + 384);
+    assert_eq!(layouts.consts["O"], 16 + 8 + 8 + 24);
+
+    let input = "Ring(T, N) = struct { data [N]T }\nA = Ring(4, u8)\n";
+    let err = crate::compute_layouts(input, &crate::parse(input)?, TARGET)
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "At 1:30 - 1:31: T is a constant but must be a type\nAt 2:4 - 2:15: In the instantiation Ring(4, u8)"
+    );
+    let err = err.downcast::<crate::SourceError>()?;
+    let labels: Vec<_> = err
+        .labels
+        .iter()
+        .map(|l| (l.file, &input[l.span.0..l.span.1], &*l.msg))
+        .collect();
+    assert_eq!(
+        labels,
+        [
+            (None, "T", "T is a constant but must be a type"),
+            (None, "Ring(4, u8)", "In the instantiation Ring(4, u8)"),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn runaway_instantiations() -> Result<()> {
+    let err = |input: &'static str| {
+        // Debug builds need more than the default stack size for the maximum nesting depth.
+        let thread = std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(move || {
+                let declarations = crate::parse(input).unwrap();
+                crate::compute_layouts(input, &declarations, TARGET)
+                    .err()
+                    .unwrap()
+                    .to_string()
+            });
+        thread.unwrap().join().unwrap()
+    };
+    // Repeated instantiations at the same site are listed once.
+    assert_eq!(
+        err("L(N) = struct { next L(N + 1) }\nA = L(0)\n"),
+        "At 1:21 - 1:29: The instantiations of L are nested too deeply\n\
+         At 1:21 - 1:29: In the instantiation L(N + 1) (254 times)\n\
+         At 2:4 - 2:8: In the instantiation L(0)"
+    );
+    // Otherwise only the innermost instantiations and the outermost one are listed.
+    let msg = err("P(N) = struct { q Q(N + 1) }\nQ(N) = struct { p P(N) }\nA = P(0)\n");
+    let lines: Vec<_> = msg.lines().collect();
+    assert_eq!(lines.len(), 1 + 10 + 1);
+    assert_eq!(lines[9], "At 2:18 - 2:22: In the instantiation P(N)");
+    assert_eq!(lines[10], "(245 more instantiations)");
+    assert_eq!(lines[11], "At 3:4 - 3:8: In the instantiation P(0)");
+    Ok(())
+}
+
+#[test]
+fn lsp() -> Result<()> {
+    use crate::json::{object, Json};
+
+    let uri = "file:///tmp/lsp.cly";
+    let text = "S = struct {\n    a u8,\n    b u32,\n}\nT = [2]S\nconst N = sizeof(T)\n";
+    let position = |line: u64, character: u64| {
+        object(vec![
+            ("textDocument", object(vec![("uri", uri.into())])),
+            (
+                "position",
+                object(vec![("line", line.into()), ("character", character.into())]),
+            ),
+        ])
+    };
+    let messages = vec![
+        object(vec![
+            ("id", 1.into()),
+            ("method", "initialize".into()),
+            (
+                "params",
+                object(vec![(
+                    "initializationOptions",
+                    object(vec![("target", TARGET.name().into())]),
+                )]),
+            ),
+        ]),
+        object(vec![
+            ("method", "textDocument/didOpen".into()),
+            (
+                "params",
+                object(vec![(
+                    "textDocument",
+                    object(vec![("uri", uri.into()), ("text", text.into())]),
+                )]),
+            ),
+        ]),
+        object(vec![
+            ("id", 2.into()),
+            ("method", "textDocument/hover".into()),
+            ("params", position(2, 4)),
+        ]),
+        object(vec![
+            ("id", 3.into()),
+            ("method", "textDocument/definition".into()),
+            ("params", position(4, 7)),
+        ]),
+        object(vec![
+            ("method", "textDocument/didChange".into()),
+            (
+                "params",
+                object(vec![
+                    ("textDocument", object(vec![("uri", uri.into())])),
+                    (
+                        "contentChanges",
+                        vec![object(vec![("text", "S = struct { a X }\n".into())])].into(),
+                    ),
+                ]),
+            ),
+        ]),
+        object(vec![("id", 4.into()), ("method", "shutdown".into())]),
+        object(vec![("method", "exit".into())]),
+    ];
+    let mut input = vec![];
+    for msg in messages {
+        let msg = msg.to_string();
+        input.extend_from_slice(format!("Content-Length: {}\r\n\r\n{}", msg.len(), msg).as_bytes());
+    }
+    let mut output = vec![];
+    crate::lsp::serve(&input[..], &mut output, TARGET, Default::default())?;
+
+    let output = String::from_utf8(output)?;
+    let responses = output
+        .split("Content-Length: ")
+        .skip(1)
+        .map(|m| Json::parse(&m[m.find("\r\n\r\n").unwrap() + 4..]))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(responses.len(), 6);
+    assert_eq!(
+        responses[1].get("params").get("diagnostics"),
+        &Json::Array(vec![])
+    );
+    assert_eq!(
+        responses[2]
+            .get("result")
+            .get("contents")
+            .get("value")
+            .as_str(),
+        Some("b: { offset: 32, size: 32 } { size: 32, alignment: 32 }")
+    );
+    let range = |l1: u64, c1: u64, l2: u64, c2: u64| {
+        object(vec![
+            (
+                "start",
+                object(vec![("line", l1.into()), ("character", c1.into())]),
+            ),
+            (
+                "end",
+                object(vec![("line", l2.into()), ("character", c2.into())]),
+            ),
+        ])
+    };
+    assert_eq!(responses[3].get("result").get("range"), &range(0, 0, 0, 1));
+    let diagnostics = responses[4].get("params").get("diagnostics");
+    assert_eq!(
+        diagnostics,
+        &Json::Array(vec![object(vec![
+            ("range", range(0, 15, 0, 16)),
+            ("severity", 1.into()),
+            ("source", "cly".into()),
+            ("message", "The referenced type X is not declared".into()),
+        ])])
+    );
+    assert!(responses[5].get("result").is_null());
+    Ok(())
+}
+
+#[test]
+fn lsp_invalid_notifications() -> Result<()> {
+    use crate::json::{object, Json};
+
+    let notification = |method: &str, params: Json| {
+        object(vec![("method", method.into()), ("params", params)]).to_string()
+    };
+    let messages = vec![
+        notification(
+            "workspace/didChangeConfiguration",
+            object(vec![(
+                "settings",
+                object(vec![("cly", object(vec![("target", "bogus".into())]))]),
+            )]),
+        ),
+        notification("textDocument/didChange", object(vec![])),
+        notification("textDocument/didClose", object(vec![])),
+        "{\"method\":".to_string(),
+        object(vec![("id", 1.into()), ("method", "shutdown".into())]).to_string(),
+        object(vec![("method", "exit".into())]).to_string(),
+    ];
+    let mut input = vec![];
+    for msg in messages {
+        input.extend_from_slice(format!("Content-Length: {}\r\n\r\n{}", msg.len(), msg).as_bytes());
+    }
+    let mut output = vec![];
+    crate::lsp::serve(&input[..], &mut output, TARGET, Default::default())?;
+
+    let output = String::from_utf8(output)?;
+    let responses = output
+        .split("Content-Length: ")
+        .skip(1)
+        .map(|m| Json::parse(&m[m.find("\r\n\r\n").unwrap() + 4..]))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(responses.len(), 2);
+    assert_eq!(
+        responses[0],
+        object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("method", "window/showMessage".into()),
+            (
+                "params",
+                object(vec![
+                    ("type", 1.into()),
+                    (
+                        "message",
+                        "Invalid cly settings: Unknown target `bogus`".into()
+                    ),
+                ]),
+            ),
+        ])
+    );
+    assert!(responses[1].get("result").is_null());
+    Ok(())
+}
+
+#[test]
+fn format() -> Result<()> {
+    let input = r#"import "common.cly"   as c // the import
+const A = (1+2)*3 - (4 - 5) + -(7) + 0x1F_FF
+// before S
+
+
+S = @align(8)   @attr_packed  struct { // first line
+  a u8,   // a comment
+
+
+  @if(target_os("linux")) b [A]u32 : 3,
+  c struct {},
+  e opaque { size: 8, field_alignment: 8, pointer_alignment: 8 },
+  // last
+}
+E = repr(C, u8) enum { A, B { x u32, y u16 } = 10, C {
+  // c
+  q u8 } }
+assert (sizeof(S) == 4 ? 1 : 0) + 1 > 0, "msg"
+"#;
+    let expected = r#"import "common.cly" as c // the import
+const A = (1 + 2) * 3 - (4 - 5) + -7 + 0x1F_FF
+// before S
+
+S = @attr_packed @align(8) struct { // first line
+    a u8, // a comment
+
+    @if(target_os("linux")) b [A]u32:3,
+    c struct { },
+    e opaque { size: 8, alignment: 8 },
+    // last
+}
+E = repr(C, u8) enum {
+    A,
+    B { x u32, y u16 } = 10,
+    C {
+        // c
+        q u8,
+    },
+}
+assert (sizeof(S) == 4 ? 1 : 0) + 1 > 0, "msg"
+"#;
+    assert_eq!(crate::format(input)?, expected);
+    assert_eq!(crate::format(expected)?, expected);
+
+    // Formatting preserves the layouts.
+    for dir in std::fs::read_dir("testfiles")? {
+        let input = std::fs::read_to_string(dir?.path().join("input.txt"))?;
+        let formatted = crate::format(&input)?;
+        assert_eq!(crate::format(&formatted)?, formatted);
+        let layouts = |input: &str| -> Result<_> {
+            let declarations = crate::evaluate_conditions(
+                input,
+                &crate::parse(input)?,
+                TARGET,
+                Default::default(),
+            )?;
+            crate::compute_layouts(input, &declarations, TARGET)
+        };
+        let (expected, actual) = (layouts(&input)?, layouts(&formatted)?);
+        assert_eq!(actual.types, expected.types);
+        assert_eq!(actual.consts, expected.consts);
+    }
+    Ok(())
+}
+
+#[test]
+fn annotations() -> Result<()> {
+    let input = "S = struct {\n    a u8,\n    b [N]u32,\n}\nconst N = 2 * 2\n";
+    let mut project = crate::load_str(input, Path::new("."))?;
+    project.evaluate_conditions(TARGET, Default::default())?;
+    let (layouts, _) = crate::compute_project_layouts(&project, TARGET, Default::default())?;
+    let enhanced = crate::enhance_declarations(project.root_declarations(), &layouts);
+    let annotated = crate::printer(input, &enhanced).to_string();
+    assert_eq!(crate::strip(&annotated)?, input);
+    let load = |input: &str| -> Result<_> {
+        let mut project = crate::load_str(input, Path::new("."))?;
+        project.evaluate_conditions(TARGET, Default::default())?;
+        Ok(project)
+    };
+    assert_eq!(
+        crate::find_stale_layouts(&load(&annotated)?, &layouts),
+        vec![]
+    );
+
+    // Spaces after stripped layouts are removed.
+    assert_eq!(
+        crate::strip("A = { size: 8, alignment: 8 } u8\nconst B = {1} 1 + 0\n")?,
+        "A = u8\nconst B = 1 + 0\n"
+    );
+
+    let stale = annotated.replace("{ offset: 32, size: 128 }", "{ offset: 8, size: 128 }");
+    let expected: Vec<_> = annotated.lines().collect();
+    assert_eq!(
+        crate::find_stale_layouts(&load(&stale)?, &layouts),
+        vec![crate::StaleLine {
+            line: 3,
+            expected: expected[2].to_string(),
+            actual: stale.lines().nth(2).unwrap().to_string(),
+        }]
+    );
+    assert!(expected[2].contains("{ offset: 32, size: 128 }b"));
+    Ok(())
+}
+
+#[test]
+fn repl() -> Result<()> {
+    let input = "\
+S = struct {
+    a u8,
+    b u32,
+}
+sizeof(S) +
+    1
+T = [2]S
+S = struct { a u8 }
+sizeof(T)
+U = struct { a X }
+sizeof(U)
+:target nope
+:history
+";
+    let mut output = vec![];
+    crate::repl::run(
+        input.as_bytes(),
+        &mut output,
+        TARGET,
+        Default::default(),
+        None,
+    )?;
+    let output = String::from_utf8(output)?;
+    let expected = "\
+cly> ...> ...> ...> S = { size: 64, alignment: 32 }struct {
+    { offset: 0, size: 8 }a { size: 8, alignment: 8 }u8,
+    { offset: 32, size: 32 }b { size: 32, alignment: 32 }u32,
+}
+cly> ...> 9
+cly> T = { size: 128, alignment: 32 }[2]{ size: 64, alignment: 32 }S
+cly> S = { size: 8, alignment: 8 }struct { { offset: 0, size: 8 }a { size: 8, alignment: 8 }u8 }
+cly> 2
+cly> At 1:15 - 1:16: The referenced type X is not declared
+cly> At 1:7 - 1:8: The referenced type U is not declared
+cly> ";
+    assert_eq!(&output[..expected.len()], expected);
+    let history = output[expected.len()..].lines().collect::<Vec<_>>();
+    assert_eq!(history[1], "cly>    1 S = struct {");
+    assert_eq!(history[4], "     }");
+    assert_eq!(history[5], "   2 sizeof(S) +");
+    assert_eq!(history[6], "         1");
+    Ok(())
+}
+
+#[test]
+fn comments() -> Result<()> {
+    use crate::ast::{DeclarationType, TypeVariant};
+
+    let input = "\
+/* A block comment
+   over two lines. */
+/// A point.
+/// In two dimensions.
+S = struct {
+    /// The x coordinate.
+    x int, /* trailing */
+    //// Not a doc comment.
+    y /* inline */ int,
+}
+/// The number of points.
+const N = 1 /* one */ + 2
+";
+    let d = crate::parse(input)?;
+    assert_eq!(d[0].doc.as_deref(), Some("A point.\nIn two dimensions."));
+    assert_eq!(d[1].doc.as_deref(), Some("The number of points."));
+    let fields = match &d[0].ty {
+        DeclarationType::Type(t) => match &t.variant {
+            TypeVariant::Record(r) => &r.fields,
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    };
+    assert_eq!(fields[0].doc.as_deref(), Some("The x coordinate."));
+    assert_eq!(fields[1].doc, None);
+
+    let layouts = crate::compute_layouts(input, &d, TARGET)?;
+    assert_eq!(layouts.consts["N"], 3);
+    let enhanced = crate::enhance_declarations(&d, &layouts);
+    assert_eq!(enhanced[0].doc, d[0].doc);
+    assert_eq!(
+        crate::strip(&crate::printer(input, &enhanced).to_string())?,
+        input
+    );
+
+    let formatted = crate::format(input)?;
+    assert!(formatted.contains("/* A block comment\n   over two lines. */\n/// A point.\n"));
+    assert_eq!(crate::format(&formatted)?, formatted);
+
+    assert_eq!(
+        crate::parse("S = u8 /* x").unwrap_err().to_string(),
+        "At 1:7 - 1:9: Unterminated block comment"
+    );
+    Ok(())
+}
+
+#[test]
+fn document() -> Result<()> {
+    use crate::json::Json;
+    use crate::DocFormat;
+
+    let input = "\
+/// A point.
+S = struct {
+    /// The x | coordinate.
+    x int,
+    flags int:3,
+}
+/// The number of points.
+const N = 4
+Pair(T) = struct { a T, b T }
+";
+    let d = crate::parse(input)?;
+    let layouts = crate::compute_layouts(input, &d, TARGET)?;
+    assert_eq!(
+        crate::document(input, &d, &layouts, DocFormat::Markdown),
+        "\
+## S
+
+A point.
+
+Size: 8 bytes, alignment: 4 bytes
+
+| Field | Type | Offset | Size | Description |
+|-------|------|--------|------|-------------|
+| x | `int` | 0 | 4 | The x \\| coordinate. |
+| flags | `int` | 4.0 | :3 |  |
+
+## N
+
+The number of points.
+
+Value: 4
+
+## Pair(T)
+
+Generic type. The layout depends on the arguments.
+"
+    );
+
+    let json = Json::parse(&crate::document(input, &d, &layouts, DocFormat::Json))?;
+    let items = match json {
+        Json::Array(items) => items,
+        _ => bail!("Expected an array"),
+    };
+    assert_eq!(items.len(), 3);
+    assert_eq!(items[0].get("doc").as_str(), Some("A point."));
+    assert_eq!(items[0].get("size_bits").as_u64(), Some(64));
+    let x = match items[0].get("fields") {
+        Json::Array(fields) => &fields[0],
+        _ => bail!("Expected an array"),
+    };
+    assert_eq!(x.get("name").as_str(), Some("x"));
+    assert_eq!(x.get("doc").as_str(), Some("The x | coordinate."));
+    assert_eq!(x.get("offset_bits").as_u64(), Some(0));
+    assert_eq!(items[1].get("value").as_u64(), Some(4));
+    assert!(items[2].get("size_bits").is_null());
+    Ok(())
+}
+
+#[test]
+fn bit_field_signedness() -> Result<()> {
+    let input = "\
+E = enum { -1, 1 }
+T = typedef unsigned int
+U = typedef T
+S = struct {
+    a E:2,
+    b T:3,
+    c U:3,
+    d size_t:3,
+    e int:3,
+}
+";
+    let d = crate::parse(input)?;
+    let layouts = crate::compute_layouts(input, &d, TARGET)?;
+    let signedness =
+        |layouts: &crate::converter::ConversionResult| match &layouts.types["S"].variant {
+            TypeVariant::Record(r) => r.fields.iter().map(|f| f.signed).collect::<Vec<_>>(),
+            _ => unreachable!(),
+        };
+    assert_eq!(
+        signedness(&layouts),
+        [
+            Some(true),
+            Some(false),
+            Some(false),
+            Some(false),
+            Some(true)
+        ]
+    );
+
+    // The signedness is printed and read back from annotated files.
+    let enhanced = crate::enhance_declarations(&d, &layouts);
+    let annotated = crate::printer(input, &enhanced).to_string();
+    assert!(annotated.contains("{ offset: 0, size: 2, signedness: signed }a"));
+    assert!(annotated.contains("{ offset: 2, size: 3, signedness: unsigned }b"));
+    let annotated_declarations = crate::parse(&annotated)?;
+    let extracted = crate::extract_layouts(&annotated, &annotated_declarations)?;
+    assert_eq!(signedness(&extracted), signedness(&layouts));
+
+    assert_eq!(
+        crate::parse("S = struct { { offset: 0, size: 1, signedness: 1 }a int:1 }")
+            .unwrap_err()
+            .to_string(),
+        "At 1:47 - 1:48: Expected `signed` or `unsigned`"
+    );
+    Ok(())
+}
@@ -28,6 +28,7 @@
 //!                 annotations: vec![],
 //!                 named: true,
 //!                 bit_width: None,
+//!                 signed: None,
 //!                 ty: Type {
 //!                     layout: (),
 //!                     annotations: vec![],
@@ -39,6 +40,7 @@
 //!                 annotations: vec![Annotation::Align(Some(128))],
 //!                 named: true,
 //!                 bit_width: Some(2),
+//!                 signed: None,
 //!                 ty: Type {
 //!                     layout: (),
 //!                     annotations: vec![],
@@ -70,9 +72,21 @@
 //! println!("{:#?}", layout);
 //! ```
 
-pub use repc_impl::builder::compute_layout;
+pub use repc_impl::builder::{
+    collect_warnings, compute_layout, compute_layout_with_options, compute_layout_with_warnings,
+    Options,
+};
 
-pub use repc_impl::target::{Target, HOST_TARGET, TARGETS, TARGET_MAP};
+pub use repc_impl::target::{
+    DataModel, Endianness, ParseTargetError, Target, HOST_TARGET, TARGETS, TARGET_MAP,
+};
+
+pub use repc_impl::bitfield::{bit_field_accesses, read_field, write_field, BitFieldAccess};
+
+pub use repc_impl::flatten::{
+    leaf_fields, resolve_field_path, ArrayExpansion, FieldPathElement, LeafField, LeafFields,
+    PathField,
+};
 
 pub mod layout {
     //! Types describing the structure and layout of C types.
@@ -82,13 +96,22 @@ pub mod layout {
     //! by calling `Type::<TypeLayout>::into()`.
 
     pub use repc_impl::layout::{
-        Annotation, Array, BuiltinType, FieldLayout, Layout, Record, RecordField, RecordKind, Type,
-        TypeLayout, TypeVariant,
+        Annotation, Array, BuiltinType, Enum, EnumUnderlyingType, FieldLayout, Layout, Record,
+        RecordField, RecordKind, RustEnum, RustEnumRepr, RustEnumVariant, RustStruct,
+        RustStructRepr, Type, TypeLayout, TypeVariant,
     };
 }
 
 pub use repc_impl::result::{Error, ErrorType};
 
+pub use repc_impl::typedefs::{c_typedef, C_TYPEDEFS};
+
+pub use repc_impl::unaligned::{
+    unaligned_field_targets, unaligned_fields, UnalignedField, UnalignedFieldTargets,
+};
+
+pub use repc_impl::warning::{PathElement, Warning, WarningType};
+
 pub mod visitor {
     //! Types and functions allowing you to traverse a [`Type`](crate::layout::Type).
 
@@ -96,6 +119,7 @@ pub mod visitor {
 
     pub use repc_impl::visitor::{
         visit_annotations, visit_array, visit_builtin_type, visit_enum, visit_opaque_type,
-        visit_record, visit_record_field, visit_type, visit_typedef,
+        visit_record, visit_record_field, visit_rust_enum, visit_rust_field, visit_rust_struct,
+        visit_type, visit_typedef,
     };
 }
@@ -628,6 +628,45 @@ pub enum Target {{
     }
     writeln!(file, "        }}")?;
     writeln!(file, "    }}")?;
+    emit_property(
+        &mut file,
+        "Returns the byte order of the target.",
+        "endianness(self) -> Endianness",
+        |t| match is_big_endian(t) {
+            true => "Endianness::Big".to_string(),
+            false => "Endianness::Little".to_string(),
+        },
+    )?;
+    emit_property(
+        &mut file,
+        "Returns the size of pointers in bits.",
+        "pointer_width_bits(self) -> u64",
+        |t| pointer_width_bits(t).to_string(),
+    )?;
+    emit_property(
+        &mut file,
+        "Returns whether `char` is a signed type.",
+        "char_is_signed(self) -> bool",
+        |t| char_is_signed(t).to_string(),
+    )?;
+    emit_property(
+        &mut file,
+        "Returns the size of `wchar_t` in bits.",
+        "wchar_size_bits(self) -> u64",
+        |t| wchar_size_bits(t).to_string(),
+    )?;
+    emit_property(
+        &mut file,
+        "Returns the operating system of the target or `none` if it has no operating system.",
+        "os(self) -> &'static str",
+        |t| format!("{:?}", os(t)),
+    )?;
+    emit_property(
+        &mut file,
+        "Returns the data model of the target.",
+        "data_model(self) -> DataModel",
+        |t| format!("DataModel::{}", data_model(t)),
+    )?;
     writeln!(file, "}}")?;
     writeln!(file)?;
     writeln!(file, "/// A slice of all targets.")?;
@@ -655,6 +694,119 @@ pub enum Target {{
     Ok(())
 }
 
+fn emit_property(
+    file: &mut impl Write,
+    doc: &str,
+    signature: &str,
+    value: impl Fn(&str) -> String,
+) -> io::Result<()> {
+    writeln!(file)?;
+    writeln!(file, "    /// {}", doc)?;
+    writeln!(file, "    pub fn {} {{", signature)?;
+    writeln!(file, "        match self {{")?;
+    for target in TARGETS {
+        writeln!(
+            file,
+            "            Target::{} => {},",
+            to_camel_case(target.0),
+            value(target.0)
+        )?;
+    }
+    writeln!(file, "        }}")?;
+    writeln!(file, "    }}")
+}
+
+fn arch(target: &str) -> &str {
+    target.split('-').next().unwrap()
+}
+
+fn is_windows(target: &str) -> bool {
+    target.contains("-windows")
+}
+
+fn is_big_endian(target: &str) -> bool {
+    match arch(target) {
+        "mips" | "mips64" | "mipsisa32r6" | "mipsisa64r6" => true,
+        "powerpc" | "powerpc64" | "s390x" | "sparc" | "sparc64" | "sparcv9" => true,
+        a => a.starts_with("armeb"),
+    }
+}
+
+fn pointer_width_bits(target: &str) -> u64 {
+    if target == X86_64_UNKNOWN_LINUX_GNUX32 {
+        return 32;
+    }
+    match arch(target) {
+        "avr" | "msp430" => 16,
+        "aarch64" | "arm64" | "x86_64" | "mips64" | "mips64el" | "mipsisa64r6"
+        | "mipsisa64r6el" | "powerpc64" | "powerpc64le" | "riscv64" | "s390x" | "sparc64"
+        | "sparcv9" => 64,
+        _ => 32,
+    }
+}
+
+fn char_is_signed(target: &str) -> bool {
+    let arch = arch(target);
+    if target.contains("-apple-") || is_windows(target) {
+        return true;
+    }
+    let unsigned = arch == "aarch64"
+        || arch.starts_with("arm")
+        || arch.starts_with("thumb")
+        || arch.starts_with("powerpc")
+        || arch.starts_with("riscv")
+        || arch == "s390x";
+    !unsigned
+}
+
+fn wchar_size_bits(target: &str) -> u64 {
+    match arch(target) {
+        "avr" | "msp430" => 16,
+        _ if is_windows(target) => 16,
+        _ => 32,
+    }
+}
+
+fn os(target: &str) -> &'static str {
+    const OSES: &[(&str, &str)] = &[
+        ("android", "android"),
+        ("windows", "windows"),
+        ("macosx", "macos"),
+        ("ios", "ios"),
+        ("tvos", "tvos"),
+        ("linux", "linux"),
+        ("freebsd", "freebsd"),
+        ("netbsd", "netbsd"),
+        ("openbsd", "openbsd"),
+        ("dragonfly", "dragonfly"),
+        ("fuchsia", "fuchsia"),
+        ("haiku", "haiku"),
+        ("hermit", "hermit"),
+        ("redox", "redox"),
+        ("solaris", "solaris"),
+        ("l4re", "l4re"),
+        ("emscripten", "emscripten"),
+        ("wasi", "wasi"),
+        ("psp", "psp"),
+    ];
+    let components: Vec<_> = target.split('-').skip(1).collect();
+    for (prefix, os) in OSES {
+        if components.iter().any(|c| c.starts_with(prefix)) {
+            return os;
+        }
+    }
+    "none"
+}
+
+fn data_model(target: &str) -> &'static str {
+    match pointer_width_bits(target) {
+        16 => "Ip16",
+        64 if is_windows(target) => "Llp64",
+        64 => "Lp64",
+        _ => "Ilp32",
+    }
+}
+
 fn main() -> io::Result<()> {
     emit_targets()?;
     emit_host_target()?;
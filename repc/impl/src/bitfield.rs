@@ -0,0 +1,148 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//! Functions for reading and writing bit-fields in memory.
+use crate::layout::{Type, TypeLayout, TypeVariant};
+use crate::result::{err, ErrorType, Result};
+use crate::target::{Endianness, Target};
+use crate::util::BITS_PER_BYTE;
+
+/// Describes how a bit-field is accessed in memory.
+///
+/// A bit-field is read by loading the storage unit as an unsigned integer with the byte
+/// order of the target, shifting it to the right by `shift_bits`, and masking it with
+/// [`mask`](BitFieldAccess::mask). If the bit-field is signed, the result is then
+/// sign-extended.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BitFieldAccess {
+    /// The index of the bit-field in the fields of the record.
+    pub field_index: usize,
+    /// The offset of the storage unit from the start of the record, in bytes.
+    pub storage_offset_bytes: u64,
+    /// The size of the storage unit in bytes.
+    ///
+    /// This is usually the size of the type of the bit-field. If the bit-field crosses
+    /// the boundary of such a unit or the unit extends beyond the end of the record, which
+    /// can happen in packed records, this is the number of bytes that contain the
+    /// bit-field.
+    pub storage_size_bytes: u64,
+    /// The position of the least significant bit of the bit-field in the storage unit.
+    pub shift_bits: u64,
+    /// The width of the bit-field.
+    pub width_bits: u64,
+    /// Whether the bit-field is sign-extended when it is read.
+    pub signed: bool,
+    /// The byte order of the storage unit.
+    pub endianness: Endianness,
+}
+
+impl BitFieldAccess {
+    /// Returns the mask of the bit-field after the storage unit has been shifted.
+    pub fn mask(&self) -> u128 {
+        match self.width_bits {
+            128 => u128::MAX,
+            n => (1 << n) - 1,
+        }
+    }
+}
+
+/// Computes how the bit-fields of a record are accessed in memory.
+///
+/// `ty` must be a record or a typedef of a record whose layout has been computed for
+/// `target`. The result contains one element for every named bit-field in the record, in
+/// the order of the fields. Bit-fields of nested records are not included.
+pub fn bit_field_accesses(target: Target, ty: &Type<TypeLayout>) -> Result<Vec<BitFieldAccess>> {
+    let record = match &ty.variant {
+        TypeVariant::Record(r) => r,
+        TypeVariant::Typedef(dst) => return bit_field_accesses(target, dst),
+        _ => return Err(err(ErrorType::NotARecord)),
+    };
+    let endianness = target.endianness();
+    let mut res = vec![];
+    for (field_index, f) in record.fields.iter().enumerate() {
+        let (layout, width) = match (f.layout, f.bit_width) {
+            (Some(l), Some(w)) if f.named && w > 0 => (l, w),
+            _ => continue,
+        };
+        let offset = layout.offset_bits;
+        let mut unit = f.ty.layout.size_bits;
+        let mut start = offset - offset % unit;
+        if start + unit < offset + width || start + unit > ty.layout.size_bits {
+            start = offset - offset % BITS_PER_BYTE;
+            unit = (offset + width).div_ceil(BITS_PER_BYTE) * BITS_PER_BYTE - start;
+        }
+        let shift_bits = match endianness {
+            Endianness::Little => offset - start,
+            Endianness::Big => start + unit - offset - width,
+        };
+        res.push(BitFieldAccess {
+            field_index,
+            storage_offset_bytes: start / BITS_PER_BYTE,
+            storage_size_bytes: unit / BITS_PER_BYTE,
+            shift_bits,
+            width_bits: width,
+            signed: f.signed.unwrap_or(false),
+            endianness,
+        });
+    }
+    Ok(res)
+}
+
+/// Reads a bit-field from the bytes of a record.
+///
+/// # Panics
+///
+/// Panics if `bytes` does not contain the storage unit of the bit-field.
+pub fn read_field(access: &BitFieldAccess, bytes: &[u8]) -> i128 {
+    let storage = storage(access, bytes);
+    let mut value = 0;
+    for i in 0..access.width_bits {
+        let (byte, bit) = bit_position(access, storage.len(), i);
+        value |= ((storage[byte] >> bit) as u128 & 1) << i;
+    }
+    let unused = 128 - access.width_bits as u32;
+    match access.signed {
+        true => ((value << unused) as i128) >> unused,
+        false => value as i128,
+    }
+}
+
+/// Writes a bit-field to the bytes of a record.
+///
+/// Only the lowest `width_bits` bits of `value` are written. All other bits of `bytes` are
+/// left unchanged.
+///
+/// # Panics
+///
+/// Panics if `bytes` does not contain the storage unit of the bit-field.
+pub fn write_field(access: &BitFieldAccess, bytes: &mut [u8], value: i128) {
+    let storage = storage_mut(access, bytes);
+    let n = storage.len();
+    for i in 0..access.width_bits {
+        let (byte, bit) = bit_position(access, n, i);
+        let v = (value >> i) as u8 & 1;
+        storage[byte] = (storage[byte] & !(1 << bit)) | (v << bit);
+    }
+}
+
+fn storage<'a>(access: &BitFieldAccess, bytes: &'a [u8]) -> &'a [u8] {
+    let start = access.storage_offset_bytes as usize;
+    &bytes[start..start + access.storage_size_bytes as usize]
+}
+
+fn storage_mut<'a>(access: &BitFieldAccess, bytes: &'a mut [u8]) -> &'a mut [u8] {
+    let start = access.storage_offset_bytes as usize;
+    &mut bytes[start..start + access.storage_size_bytes as usize]
+}
+
+/// Returns the byte and the bit within the byte that contain bit `i` of the bit-field.
+///
+/// Bits are accessed one at a time because the storage unit of a bit-field in a packed
+/// record can be larger than 16 bytes, e.g. an `i128` bit-field that does not start at a
+/// byte boundary.
+fn bit_position(access: &BitFieldAccess, storage_size_bytes: usize, i: u64) -> (usize, u32) {
+    let pos = (access.shift_bits + i) as usize;
+    let byte = match access.endianness {
+        Endianness::Little => pos / 8,
+        Endianness::Big => storage_size_bytes - 1 - pos / 8,
+    };
+    (byte, (pos % 8) as u32)
+}
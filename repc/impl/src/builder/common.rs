@@ -1,9 +1,9 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 #![allow(clippy::match_like_matches_macro)]
 
-use crate::layout::{BuiltinType, Type, TypeLayout, TypeVariant};
-use crate::result::Result;
-use crate::target::Target;
+use crate::layout::{BuiltinType, EnumUnderlyingType, Layout, Type, TypeLayout, TypeVariant};
+use crate::result::{err, ErrorType, Result};
+use crate::target::{system_compiler, Compiler, Target};
 use crate::util::{MinExt, BITS_PER_BYTE};
 
 pub fn compute_builtin_type_layout(target: Target, bi: BuiltinType) -> Result<Type<TypeLayout>> {
@@ -340,6 +340,85 @@ pub fn pack_all_enums(target: Target) -> bool {
     }
 }
 
+/// Returns the range of values that can be represented by an integer type.
+///
+/// Returns `None` if the type is not an integer type.
+pub fn integer_range(target: Target, b: BuiltinType) -> Option<(i128, i128)> {
+    use BuiltinType::*;
+    let signed = match b {
+        Bool => return Some((0, 1)),
+        // The range of the unsigned 128-bit type is truncated to the range of `i128`.
+        U128 => return Some((0, i128::MAX)),
+        I128 => return Some((i128::MIN, i128::MAX)),
+        Char => target.char_is_signed(),
+        SignedChar | Short | Int | Long | LongLong | I8 | I16 | I32 | I64 => true,
+        UnsignedChar | UnsignedShort | UnsignedInt | UnsignedLong | UnsignedLongLong | U8 | U16
+        | U32 | U64 => false,
+        Unit | F32 | F64 | Float | Double | Pointer => return None,
+    };
+    let bits = builtin_type_layout(target, b).size_bits;
+    match signed {
+        true => Some((-(1 << (bits - 1)), (1 << (bits - 1)) - 1)),
+        false => Some((0, (1 << bits) - 1)),
+    }
+}
+
+/// Returns whether a bit-field of type `ty` is signed.
+///
+/// Plain `int` bit-fields are signed on all supported compilers. `char` bit-fields have the
+/// signedness of `char`, `bool` bit-fields are unsigned, and enum bit-fields have the
+/// signedness of the underlying type of the enum.
+///
+/// Returns `None` if the signedness cannot be determined, e.g. for opaque types.
+pub fn bit_field_is_signed<I: Layout>(target: Target, ty: &Type<I>) -> Option<bool> {
+    match &ty.variant {
+        TypeVariant::Builtin(bi) => integer_range(target, *bi).map(|r| r.0 < 0),
+        TypeVariant::Typedef(dst) => bit_field_is_signed(target, dst),
+        TypeVariant::Enum(e) => e.underlying_type.map(|u| u.signed),
+        _ => None,
+    }
+}
+
+/// Returns whether `ty` is `bool` or a typedef of `bool`.
+pub fn is_bool<I: Layout>(ty: &Type<I>) -> bool {
+    match &ty.variant {
+        TypeVariant::Builtin(bi) => *bi == BuiltinType::Bool,
+        TypeVariant::Typedef(dst) => is_bool(dst),
+        _ => false,
+    }
+}
+
+/// Returns whether `ty` is a pointer or a typedef of a pointer.
+pub fn is_pointer<I: Layout>(ty: &Type<I>) -> bool {
+    match &ty.variant {
+        TypeVariant::Builtin(bi) => *bi == BuiltinType::Pointer,
+        TypeVariant::Typedef(dst) => is_pointer(dst),
+        _ => false,
+    }
+}
+
+/// Returns the underlying type of an enum whose layout is the layout of `b`.
+pub fn enum_underlying_type(target: Target, b: BuiltinType) -> EnumUnderlyingType {
+    // Pre-validation ensures that the underlying type is an integer type.
+    let signed = integer_range(target, b).unwrap().0 < 0;
+    let ty = match (b, signed) {
+        (BuiltinType::Char, true) => BuiltinType::SignedChar,
+        (BuiltinType::Char, false) => BuiltinType::UnsignedChar,
+        _ => b,
+    };
+    EnumUnderlyingType { ty, signed }
+}
+
+/// Checks that the values of an enum can be represented by its fixed underlying type.
+pub fn validate_fixed_enum_values(target: Target, b: BuiltinType, values: &[i128]) -> Result<()> {
+    // Pre-validation ensures that the fixed underlying type is an integer type.
+    let (min, max) = integer_range(target, b).unwrap();
+    match values.iter().any(|&v| v < min || v > max) {
+        true => Err(err(ErrorType::EnumValueOutOfRange)),
+        false => Ok(()),
+    }
+}
+
 pub fn default_aligned_alignment(target: Target) -> u64 {
     use Target::*;
     match target {
@@ -383,3 +462,41 @@ pub fn default_aligned_alignment(target: Target) -> u64 {
         _ => 128,
     }
 }
+
+/// The effect of a `#pragma pack(N)` annotation on the fields of a record.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PragmaPackEffect {
+    /// The maximum field alignment is set to the contained value in bits.
+    MaxFieldAlignment(u64),
+    /// The maximum field alignment is removed.
+    NoMaxFieldAlignment,
+    /// The annotation is ignored and the default maximum field alignment is used.
+    Ignored,
+}
+
+/// Returns the effect of `#pragma pack(N)` on `target` where `pack_bits` is N in bits.
+pub fn pragma_pack_effect(target: Target, pack_bits: u64) -> PragmaPackEffect {
+    use PragmaPackEffect::*;
+    use Target::*;
+    if system_compiler(target) != Compiler::Msvc {
+        // N must be one of {1,2,4,8,16}. See test case 0064.
+        return match pack_bits {
+            8 | 16 | 32 | 64 | 128 => MaxFieldAlignment(pack_bits),
+            _ => Ignored,
+        };
+    }
+    // x86: N={1,2,4} set the maximum field alignment to that value.
+    // x64: N={1,2,4,8} set the maximum field alignment to that value.
+    // arm: N={1,2,4,8,16} set the maximum field alignment to that value.
+    // arm64: N={1,2,4,8} set the maximum field alignment to that value. N=16 disables the
+    //        maximum field alignment.
+    // All other N are ignored. See test case 0020.
+    match (pack_bits, target) {
+        (8 | 16 | 32, _) => MaxFieldAlignment(pack_bits),
+        (64, I586PcWindowsMsvc | I686PcWindowsMsvc | I686UnknownWindows) => Ignored,
+        (64, _) => MaxFieldAlignment(pack_bits),
+        (128, Thumbv7aPcWindowsMsvc) => MaxFieldAlignment(pack_bits),
+        (128, Aarch64PcWindowsMsvc) => NoMaxFieldAlignment,
+        _ => Ignored,
+    }
+}
@@ -1,22 +1,54 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 use std::ops::Not;
 
-use crate::layout::{Annotation, Array, BuiltinType, Record, RecordField, Type, TypeLayout};
+use crate::builder::common::{
+    builtin_type_layout, is_pointer, pragma_pack_effect, PragmaPackEffect,
+};
+use crate::layout::{
+    Annotation, Array, BuiltinType, Enum, Record, RecordField, RustEnum, RustEnumRepr, RustStruct,
+    Type, TypeLayout,
+};
 use crate::result::{err, Error, ErrorType, Result};
-use crate::target::Target;
-use crate::util::BITS_PER_BYTE;
+use crate::target::{system_compiler, Compiler, Target};
+use crate::util::{annotation_alignment, pragma_pack_value, BITS_PER_BYTE};
 use crate::visitor::{
-    visit_array, visit_builtin_type, visit_opaque_type, visit_record_field, Visitor,
+    visit_array, visit_builtin_type, visit_enum, visit_opaque_type, visit_record_field,
+    visit_rust_enum, visit_rust_field, visit_rust_struct, visit_type, visit_typedef, Visitor,
 };
+use crate::warning::{PathElement, Warning, WarningType};
 
 pub mod common;
 mod msvc;
+mod rust;
 mod sysv_like;
 
+/// Options that affect the layout of types.
+///
+/// The default options correspond to the default behavior of the system compiler of the
+/// target.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Options {
+    /// Whether enums use the smallest integer type that can represent all of their values.
+    ///
+    /// This corresponds to the `-fshort-enums` option of GCC and Clang. It has the same
+    /// effect as an `AttrPacked` annotation on every enum without a fixed underlying type.
+    /// It has no effect on MSVC targets.
+    pub short_enums: bool,
+}
+
 /// Computes the layout of a type.
 ///
 /// See the crate documentation for an example.
 pub fn compute_layout(target: Target, ty: &Type<()>) -> Result<Type<TypeLayout>> {
+    compute_layout_with_options(target, ty, Options::default())
+}
+
+/// Computes the layout of a type with non-default options.
+pub fn compute_layout_with_options(
+    target: Target,
+    ty: &Type<()>,
+    options: Options,
+) -> Result<Type<TypeLayout>> {
     pre_validate(ty)?;
     use Target::*;
     match target {
@@ -27,11 +59,39 @@ pub fn compute_layout(target: Target, ty: &Type<()>) -> Result<Type<TypeLayout>>
         | Thumbv7aPcWindowsMsvc
         | X86_64UnknownWindows
         | X86_64PcWindowsMsvc => msvc::compute_layout(target, ty),
-        I686PcWindowsGnu | X86_64PcWindowsGnu => sysv_like::mingw::compute_layout(target, ty),
-        _ => sysv_like::sysv::compute_layout(target, ty),
+        I686PcWindowsGnu | X86_64PcWindowsGnu => {
+            sysv_like::mingw::compute_layout(target, ty, options)
+        }
+        _ => sysv_like::sysv::compute_layout(target, ty, options),
     }
 }
 
+/// Computes the layout of a type and collects warnings about the input.
+///
+/// The layout is the same as the one returned by [`compute_layout`]. The warnings describe
+/// parts of the input that the compiler silently ignores or truncates.
+pub fn compute_layout_with_warnings(
+    target: Target,
+    ty: &Type<()>,
+) -> Result<(Type<TypeLayout>, Vec<Warning>)> {
+    let ty = compute_layout(target, ty)?;
+    let warnings = collect_warnings(target, &ty);
+    Ok((ty, warnings))
+}
+
+/// Collects warnings about the input of a layout computation.
+///
+/// `ty` must be the result of computing the layout of a type for `target`.
+pub fn collect_warnings(target: Target, ty: &Type<TypeLayout>) -> Vec<Warning> {
+    let mut wc = WarningCollector {
+        target,
+        path: vec![],
+        warnings: vec![],
+    };
+    wc.visit_type(ty);
+    wc.warnings
+}
+
 fn pre_validate(ty: &Type<()>) -> Result<()> {
     let mut pv = PreValidator(vec![]);
     pv.visit_type(ty);
@@ -44,6 +104,18 @@ fn pre_validate(ty: &Type<()>) -> Result<()> {
 struct PreValidator(Vec<Error>);
 
 impl Visitor<()> for PreValidator {
+    fn visit_type(&mut self, ty: &Type<()>) {
+        // _Alignas is a property of a declaration, not of a type. See [`Annotation::Alignas`].
+        if ty
+            .annotations
+            .iter()
+            .any(|a| matches!(a, Annotation::Alignas(_)))
+        {
+            self.0.push(err(ErrorType::AlignasOnType));
+        }
+        visit_type(self, ty);
+    }
+
     fn visit_annotations(&mut self, a: &[Annotation]) {
         let mut num_pragma_packed = 0;
         for a in a {
@@ -51,7 +123,7 @@ impl Visitor<()> for PreValidator {
                 Annotation::PragmaPack(_) => num_pragma_packed += 1,
                 Annotation::AttrPacked => {}
                 Annotation::Align(None) => {}
-                Annotation::Align(Some(n)) => {
+                Annotation::Align(Some(n)) | Annotation::Alignas(n) => {
                     self.validate_alignment(*n);
                 }
             }
@@ -75,13 +147,26 @@ impl Visitor<()> for PreValidator {
             _ => {}
         }
         for a in &field.annotations {
-            if let Annotation::PragmaPack(_) = a {
-                self.0.push(err(ErrorType::PragmaPackedField));
+            match a {
+                Annotation::PragmaPack(_) => self.0.push(err(ErrorType::PragmaPackedField)),
+                Annotation::Alignas(_) if field.bit_width.is_some() => {
+                    self.0.push(err(ErrorType::AlignasOnBitField))
+                }
+                _ => {}
             }
         }
         visit_record_field(self, field, rt, ty);
     }
 
+    fn visit_enum(&mut self, e: &Enum, ty: &Type<()>) {
+        if let Some(bi) = e.fixed_underlying_type {
+            if !is_integer(bi) {
+                self.0.push(err(ErrorType::InvalidEnumUnderlyingType));
+            }
+        }
+        visit_enum(self, e, ty);
+    }
+
     fn visit_array(&mut self, at: &Array<()>, ty: &Type<()>) {
         if ty.annotations.is_empty().not() {
             self.0.push(err(ErrorType::AnnotatedArray));
@@ -101,6 +186,70 @@ impl Visitor<()> for PreValidator {
         self.validate_alignment(layout.required_alignment_bits);
         visit_opaque_type(self, layout, ty);
     }
+
+    fn visit_rust_struct(&mut self, rust_struct: &RustStruct<()>, ty: &Type<()>) {
+        if ty.annotations.is_empty().not() {
+            self.0.push(err(ErrorType::AnnotatedRustType));
+        }
+        visit_rust_struct(self, rust_struct, ty);
+    }
+
+    fn visit_rust_enum(&mut self, rust_enum: &RustEnum<()>, ty: &Type<()>) {
+        if ty.annotations.is_empty().not() {
+            self.0.push(err(ErrorType::AnnotatedRustType));
+        }
+        let variants = &rust_enum.variants;
+        match rust_enum.repr {
+            RustEnumRepr::C | RustEnumRepr::Int(_) | RustEnumRepr::CInt(_)
+                if variants.is_empty() =>
+            {
+                self.0.push(err(ErrorType::EmptyRustEnum));
+            }
+            RustEnumRepr::Int(bi) | RustEnumRepr::CInt(bi) => match rust::int_range(bi) {
+                Some((min, max)) => {
+                    if variants
+                        .iter()
+                        .any(|v| v.discriminant < min || v.discriminant > max)
+                    {
+                        self.0.push(err(ErrorType::RustEnumDiscriminantOutOfRange));
+                    }
+                }
+                None => self.0.push(err(ErrorType::InvalidRustEnumRepr)),
+            },
+            RustEnumRepr::C => {}
+            RustEnumRepr::Rust => {
+                let mut num_fields: Vec<_> = variants.iter().map(|v| v.fields.len()).collect();
+                num_fields.sort_unstable();
+                if num_fields != [0, 1] {
+                    self.0.push(err(ErrorType::InvalidOptionLikeEnum));
+                } else if variants
+                    .iter()
+                    .flat_map(|v| &v.fields)
+                    .any(|f| !is_pointer(&f.ty))
+                {
+                    self.0.push(err(ErrorType::OptionLikeEnumWithoutNiche));
+                }
+            }
+        }
+        visit_rust_enum(self, rust_enum, ty);
+    }
+
+    fn visit_rust_field(&mut self, field: &RecordField<()>, ty: &Type<()>) {
+        if field.annotations.is_empty().not() {
+            self.0.push(err(ErrorType::AnnotatedRustType));
+        }
+        match (field.bit_width, field.named) {
+            (Some(_), _) => self.0.push(err(ErrorType::RustBitField)),
+            (None, false) => self.0.push(err(ErrorType::UnnamedRegularField)),
+            _ => {}
+        }
+        visit_rust_field(self, field, ty);
+    }
+}
+
+fn is_integer(bi: BuiltinType) -> bool {
+    use BuiltinType::*;
+    !matches!(bi, Unit | F32 | F64 | Float | Double | Pointer)
 }
 
 impl PreValidator {
@@ -113,3 +262,89 @@ impl PreValidator {
         }
     }
 }
+
+struct WarningCollector {
+    target: Target,
+    path: Vec<PathElement>,
+    warnings: Vec<Warning>,
+}
+
+impl WarningCollector {
+    fn warn(&mut self, kind: WarningType) {
+        self.warnings.push(Warning {
+            kind,
+            path: self.path.clone(),
+        });
+    }
+}
+
+impl Visitor<TypeLayout> for WarningCollector {
+    fn visit_record(&mut self, record: &Record<TypeLayout>, ty: &Type<TypeLayout>) {
+        if let Some(n) = pragma_pack_value(&ty.annotations) {
+            if pragma_pack_effect(self.target, n) == PragmaPackEffect::Ignored {
+                self.warn(WarningType::IgnoredPragmaPack(n));
+            }
+        }
+        // __attribute__((aligned(N))) and __declspec(align(N)) cannot decrease the alignment
+        // of a record. See test case 0065.
+        if let Some(n) = annotation_alignment(self.target, &ty.annotations) {
+            if n < ty.layout.field_alignment_bits {
+                self.warn(WarningType::IgnoredRecordAlignment {
+                    requested_bits: n,
+                    actual_bits: ty.layout.field_alignment_bits,
+                });
+            }
+        }
+        for (i, f) in record.fields.iter().enumerate() {
+            self.path.push(PathElement::Field(i));
+            self.visit_record_field(f, record, ty);
+            self.path.pop();
+        }
+    }
+
+    fn visit_rust_struct(&mut self, rust_struct: &RustStruct<TypeLayout>, ty: &Type<TypeLayout>) {
+        for (i, f) in rust_struct.fields.iter().enumerate() {
+            self.path.push(PathElement::Field(i));
+            self.visit_rust_field(f, ty);
+            self.path.pop();
+        }
+    }
+
+    fn visit_rust_enum(&mut self, rust_enum: &RustEnum<TypeLayout>, ty: &Type<TypeLayout>) {
+        for (i, v) in rust_enum.variants.iter().enumerate() {
+            for (j, f) in v.fields.iter().enumerate() {
+                self.path.push(PathElement::VariantField(i, j));
+                self.visit_rust_field(f, ty);
+                self.path.pop();
+            }
+        }
+    }
+
+    fn visit_typedef(&mut self, dst: &Type<TypeLayout>, ty: &Type<TypeLayout>) {
+        self.path.push(PathElement::TypedefTarget);
+        visit_typedef(self, dst, ty);
+        self.path.pop();
+    }
+
+    fn visit_array(&mut self, array: &Array<TypeLayout>, ty: &Type<TypeLayout>) {
+        self.path.push(PathElement::ArrayElement);
+        visit_array(self, array, ty);
+        self.path.pop();
+    }
+
+    fn visit_enum(&mut self, e: &Enum, ty: &Type<TypeLayout>) {
+        // On MSVC, the values of an enum without a fixed underlying type are silently
+        // truncated to int. See test case 0019.
+        if system_compiler(self.target) == Compiler::Msvc && e.fixed_underlying_type.is_none() {
+            let int_bits = builtin_type_layout(self.target, BuiltinType::Int).size_bits;
+            let min = -(1i128 << (int_bits - 1));
+            let max = (1i128 << (int_bits - 1)) - 1;
+            for &v in &e.variants {
+                if v < min || v > max {
+                    self.warn(WarningType::TruncatedEnumValue(v));
+                }
+            }
+        }
+        visit_enum(self, e, ty);
+    }
+}
@@ -1,16 +1,19 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 use crate::builder::common::{
-    builtin_type_layout, compute_builtin_type_layout, compute_opaque_type_layout,
+    bit_field_is_signed, builtin_type_layout, compute_builtin_type_layout,
+    compute_opaque_type_layout, enum_underlying_type, pragma_pack_effect,
+    validate_fixed_enum_values, PragmaPackEffect,
 };
+use crate::builder::rust;
 use crate::layout::{
-    Annotation, Array, BuiltinType, FieldLayout, Record, RecordField, RecordKind, Type, TypeLayout,
-    TypeVariant,
+    Annotation, Array, BuiltinType, Enum, FieldLayout, Record, RecordField, RecordKind, Type,
+    TypeLayout, TypeVariant,
 };
 use crate::result::{err, ErrorType, Result};
 use crate::target::Target;
 use crate::util::{
     align_to, annotation_alignment, is_attr_packed, pragma_pack_value, size_add, size_mul,
-    MaxAssign, MaxExt, MinAssign, MinExt, BITS_PER_BYTE,
+    validate_alignas, MaxAssign, MaxExt, MinAssign, MinExt, BITS_PER_BYTE,
 };
 
 pub fn compute_layout(target: Target, ty: &Type<()>) -> Result<Type<TypeLayout>> {
@@ -59,14 +62,26 @@ pub fn compute_layout(target: Target, ty: &Type<()>) -> Result<Type<TypeLayout>>
                 }),
             })
         }
-        TypeVariant::Enum(v) => {
+        TypeVariant::RustStruct(s) => {
+            rust::compute_struct_layout(s, |ty| compute_layout(target, ty))
+        }
+        TypeVariant::RustEnum(e) => rust::compute_enum_layout(e, |ty| compute_layout(target, ty)),
+        TypeVariant::Enum(e) => {
             // #pragma pack is ignored. See test case 0054.
             // __attribute__((aligned)) is ignored by clang. See test case 0055.
             let requested_alignment =
                 annotation_alignment(target, &ty.annotations).unwrap_or(BITS_PER_BYTE);
-            // Enums always have the base type int even if the values do not fit into int. The
-            // values are silently truncated if necessary. See test case 0019.
-            let mut layout = builtin_type_layout(target, BuiltinType::Int);
+            // Enums without a fixed underlying type always have the base type int even if the
+            // values do not fit into int. The values are silently truncated if necessary. See
+            // test case 0019.
+            let underlying_type = match e.fixed_underlying_type {
+                Some(bi) => {
+                    validate_fixed_enum_values(target, bi, &e.variants)?;
+                    bi
+                }
+                None => BuiltinType::Int,
+            };
+            let mut layout = builtin_type_layout(target, underlying_type);
             // The alignment requested by __declspec(align)) does not affect the size and therefore
             // also not the pointer alignment. See test case 0051.
             layout
@@ -76,7 +91,11 @@ pub fn compute_layout(target: Target, ty: &Type<()>) -> Result<Type<TypeLayout>>
             Ok(Type {
                 layout,
                 annotations: ty.annotations.clone(),
-                variant: TypeVariant::Enum(v.clone()),
+                variant: TypeVariant::Enum(Enum {
+                    fixed_underlying_type: e.fixed_underlying_type,
+                    variants: e.variants.clone(),
+                    underlying_type: Some(enum_underlying_type(target, underlying_type)),
+                }),
             })
         }
     }
@@ -139,31 +158,18 @@ impl<'a> RecordLayoutBuilder<'a> {
             true => Some(BITS_PER_BYTE),
             false => pragma_pack_value(annotations),
         };
-        // The effect of #pragma pack(N) depends on the target.
-        //
-        // x86: By default, there is no maximum field alignment. N={1,2,4} set the maximum field
-        //      alignment to that value. All other N activate the default.
-        // x64: By default, there is no maximum field alignment. N={1,2,4,8} set the maximum field
-        //      alignment to that value. All other N activate the default.
-        // arm: By default, the maximum field alignment is 8. N={1,2,4,8,16} set the maximum field
-        //      alignment to that value. All other N activate the default.
-        // arm64: By default, the maximum field alignment is 8. N={1,2,4,8} set the maximum field
-        //        alignment to that value. N=16 disables the maximum field alignment. All other N
-        //        activate the default.
-        //
-        // See test case 0020.
+        // By default, there is no maximum field alignment on x86 and x64 and the maximum field
+        // alignment is 8 on arm and arm64. See test case 0020.
         use Target::*;
-        let max_field_alignment_bits = match (pack_value, target) {
-            (Some(8), _) | (Some(16), _) | (Some(32), _) => pack_value,
-            (Some(64), I586PcWindowsMsvc)
-            | (Some(64), I686PcWindowsMsvc)
-            | (Some(64), I686UnknownWindows) => None,
-            (Some(64), _) => pack_value,
-            (Some(128), Thumbv7aPcWindowsMsvc) => pack_value,
-            (Some(128), _) => None,
-            (_, Thumbv7aPcWindowsMsvc) | (_, Aarch64PcWindowsMsvc) => Some(64),
+        let default_max_field_alignment_bits = match target {
+            Thumbv7aPcWindowsMsvc | Aarch64PcWindowsMsvc => Some(64),
             _ => None,
         };
+        let max_field_alignment_bits = match pack_value.map(|n| pragma_pack_effect(target, n)) {
+            Some(PragmaPackEffect::MaxFieldAlignment(n)) => Some(n),
+            Some(PragmaPackEffect::NoMaxFieldAlignment) => None,
+            Some(PragmaPackEffect::Ignored) | None => default_max_field_alignment_bits,
+        };
         // The required alignment can be increased by adding a __declspec(align)
         // annotation. See test case 0023.
         let required_alignment_bits =
@@ -238,6 +244,7 @@ impl<'a> RecordLayoutBuilder<'a> {
     fn layout_field(&mut self, field: &RecordField<()>) -> Result<()> {
         // The offset and the size of the field is based on the layout of the underlying type.
         let field_ty = compute_layout(self.target, &field.ty)?;
+        validate_alignas(&field.annotations, field_ty.layout.field_alignment_bits)?;
         let (ty_size_bits, field_alignment_bits) = {
             let layout = field_ty.layout;
             // The required alignment of the field is the maximum of the required alignment of the
@@ -278,6 +285,9 @@ impl<'a> RecordLayoutBuilder<'a> {
             annotations: field.annotations.clone(),
             named: field.named,
             bit_width: field.bit_width,
+            signed: field
+                .bit_width
+                .and_then(|_| bit_field_is_signed(self.target, &field_ty)),
             ty: field_ty,
         });
         Ok(())
@@ -343,7 +353,9 @@ impl<'a> RecordLayoutBuilder<'a> {
             // If there is an ongoing bit-field in a struct whose underlying type has the same size and
             // if there is enough space left to place this bit-field, then this bit-field is placed in
             // the ongoing bit-field and the overall layout of the struct is not affected by this
-            // bit-field. See test case 0037.
+            // bit-field. See test case 0037. Since only the sizes are compared, a `bool` bit-field
+            // shares a storage unit with `char` bit-fields but not with wider ones. See test case
+            // 0089.
             if self.kind == RecordKind::Struct {
                 if let Some(ref mut p) = &mut self.ongoing_bitfield {
                     if p.ty_size_bits == ty_size_bits && p.unused_size_bits >= width {
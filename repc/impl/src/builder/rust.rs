@@ -0,0 +1,261 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+use crate::layout::{
+    BuiltinType, Enum, FieldLayout, RecordField, RustEnum, RustEnumRepr, RustEnumVariant,
+    RustStruct, RustStructRepr, Type, TypeLayout, TypeVariant,
+};
+use crate::result::{err, ErrorType, Result};
+use crate::util::{align_to, size_add, MaxAssign, BITS_PER_BYTE};
+
+/// Returns the range of values of the integer types that can be used in the `repr`
+/// attribute of an enum.
+pub(crate) fn int_range(bi: BuiltinType) -> Option<(i128, i128)> {
+    use BuiltinType::*;
+    let range = match bi {
+        U8 => (0, u8::MAX as i128),
+        U16 => (0, u16::MAX as i128),
+        U32 => (0, u32::MAX as i128),
+        U64 => (0, u64::MAX as i128),
+        I8 => (i8::MIN as i128, i8::MAX as i128),
+        I16 => (i16::MIN as i128, i16::MAX as i128),
+        I32 => (i32::MIN as i128, i32::MAX as i128),
+        I64 => (i64::MIN as i128, i64::MAX as i128),
+        _ => return None,
+    };
+    Some(range)
+}
+
+/// Computes the layout of a Rust struct.
+///
+/// `compute` computes the layout of the field types according to the rules of the target.
+pub(super) fn compute_struct_layout(
+    s: &RustStruct<()>,
+    compute: impl Fn(&Type<()>) -> Result<Type<TypeLayout>>,
+) -> Result<Type<TypeLayout>> {
+    let tys = compute_field_types(&s.fields, &compute)?;
+    let (layout, offsets) = match s.repr {
+        RustStructRepr::C => {
+            let mut builder = StructBuilder::new();
+            let offsets = tys
+                .iter()
+                .map(|t| builder.add_field(t.layout))
+                .collect::<Result<Vec<_>>>()?;
+            (builder.layout()?, offsets)
+        }
+        RustStructRepr::Transparent => {
+            let mut non_trivial = tys.iter().filter(|t| !is_trivial(t.layout));
+            let layout = match (non_trivial.next(), non_trivial.next()) {
+                (_, Some(_)) => return Err(err(ErrorType::InvalidTransparentStruct)),
+                (Some(t), None) => t.layout,
+                (None, None) => StructBuilder::new().layout()?,
+            };
+            (layout, vec![0; tys.len()])
+        }
+    };
+    Ok(Type {
+        layout,
+        annotations: vec![],
+        variant: TypeVariant::RustStruct(RustStruct {
+            repr: s.repr,
+            fields: build_fields(&s.fields, tys, &offsets),
+        }),
+    })
+}
+
+/// Computes the layout of a Rust enum.
+///
+/// `compute` computes the layout of the tag and the field types according to the rules of
+/// the target.
+pub(super) fn compute_enum_layout(
+    e: &RustEnum<()>,
+    compute: impl Fn(&Type<()>) -> Result<Type<TypeLayout>>,
+) -> Result<Type<TypeLayout>> {
+    let tys = e
+        .variants
+        .iter()
+        .map(|v| compute_field_types(&v.fields, &compute))
+        .collect::<Result<Vec<_>>>()?;
+    let tag = match e.repr {
+        // The tag of a repr(C) enum is the C enum with the same discriminants.
+        RustEnumRepr::C => {
+            let variants = e.variants.iter().map(|v| v.discriminant).collect();
+            let tag = TypeVariant::Enum(Enum {
+                fixed_underlying_type: None,
+                variants,
+                underlying_type: None,
+            });
+            Some(compute(&unannotated(tag))?.layout)
+        }
+        RustEnumRepr::Int(bi) | RustEnumRepr::CInt(bi) => {
+            Some(compute(&unannotated(TypeVariant::Builtin(bi)))?.layout)
+        }
+        RustEnumRepr::Rust => None,
+    };
+    let mut offsets = vec![];
+    let layout = match (e.repr, tag) {
+        // The enum is a union of structs. Each struct contains the tag followed by the fields
+        // of the variant.
+        (RustEnumRepr::Int(_), Some(tag)) => {
+            let mut variants = vec![];
+            for tys in &tys {
+                let mut builder = StructBuilder::new();
+                builder.add_field(tag)?;
+                offsets.push(
+                    tys.iter()
+                        .map(|t| builder.add_field(t.layout))
+                        .collect::<Result<Vec<_>>>()?,
+                );
+                variants.push(builder.layout()?);
+            }
+            union_layout(&variants)?
+        }
+        // The enum is a struct containing the tag followed by a union of structs. Each
+        // struct contains the fields of one variant.
+        (RustEnumRepr::C, Some(tag)) | (RustEnumRepr::CInt(_), Some(tag)) => {
+            let mut variants = vec![];
+            for tys in &tys {
+                let mut builder = StructBuilder::new();
+                offsets.push(
+                    tys.iter()
+                        .map(|t| builder.add_field(t.layout))
+                        .collect::<Result<Vec<_>>>()?,
+                );
+                variants.push(builder.layout()?);
+            }
+            let mut builder = StructBuilder::new();
+            builder.add_field(tag)?;
+            let payload_offset = builder.add_field(union_layout(&variants)?)?;
+            for offsets in &mut offsets {
+                for offset in offsets {
+                    *offset += payload_offset;
+                }
+            }
+            builder.layout()?
+        }
+        // Option-like enums have the layout of their only field. Pre-validation ensures
+        // that there is exactly one field and that it is a pointer.
+        _ => {
+            let mut layout = None;
+            for tys in &tys {
+                offsets.push(vec![0; tys.len()]);
+                if let Some(t) = tys.first() {
+                    layout = Some(t.layout);
+                }
+            }
+            layout.unwrap()
+        }
+    };
+    let variants = e
+        .variants
+        .iter()
+        .zip(tys)
+        .zip(offsets)
+        .map(|((v, tys), offsets)| RustEnumVariant {
+            discriminant: v.discriminant,
+            fields: build_fields(&v.fields, tys, &offsets),
+        })
+        .collect();
+    Ok(Type {
+        layout,
+        annotations: vec![],
+        variant: TypeVariant::RustEnum(RustEnum {
+            repr: e.repr,
+            tag_layout: tag.map(|tag| FieldLayout {
+                offset_bits: 0,
+                size_bits: tag.size_bits,
+            }),
+            variants,
+        }),
+    })
+}
+
+/// Lays out fields like the fields of a `repr(C)` struct.
+struct StructBuilder {
+    size_bits: u64,
+    alignment_bits: u64,
+    required_alignment_bits: u64,
+}
+
+impl StructBuilder {
+    fn new() -> Self {
+        StructBuilder {
+            size_bits: 0,
+            alignment_bits: BITS_PER_BYTE,
+            required_alignment_bits: BITS_PER_BYTE,
+        }
+    }
+
+    /// Adds a field and returns its offset.
+    fn add_field(&mut self, layout: TypeLayout) -> Result<u64> {
+        let offset_bits = align_to(self.size_bits, layout.field_alignment_bits)?;
+        self.size_bits = size_add(offset_bits, layout.size_bits)?;
+        self.alignment_bits.assign_max(layout.field_alignment_bits);
+        self.required_alignment_bits
+            .assign_max(layout.required_alignment_bits);
+        Ok(offset_bits)
+    }
+
+    fn layout(&self) -> Result<TypeLayout> {
+        Ok(TypeLayout {
+            size_bits: align_to(self.size_bits, self.alignment_bits)?,
+            field_alignment_bits: self.alignment_bits,
+            pointer_alignment_bits: self.alignment_bits,
+            required_alignment_bits: self.required_alignment_bits,
+        })
+    }
+}
+
+/// Returns the layout of a `repr(C)` union with fields of the given layouts.
+fn union_layout(layouts: &[TypeLayout]) -> Result<TypeLayout> {
+    let mut builder = StructBuilder::new();
+    for l in layouts {
+        builder.size_bits.assign_max(l.size_bits);
+        builder.alignment_bits.assign_max(l.field_alignment_bits);
+        builder
+            .required_alignment_bits
+            .assign_max(l.required_alignment_bits);
+    }
+    builder.layout()
+}
+
+/// Returns whether the layout is the layout of a zero-sized type with alignment 1.
+fn is_trivial(layout: TypeLayout) -> bool {
+    layout.size_bits == 0 && layout.field_alignment_bits == BITS_PER_BYTE
+}
+
+fn unannotated(variant: TypeVariant<()>) -> Type<()> {
+    Type {
+        layout: (),
+        annotations: vec![],
+        variant,
+    }
+}
+
+fn compute_field_types(
+    fields: &[RecordField<()>],
+    compute: impl Fn(&Type<()>) -> Result<Type<TypeLayout>>,
+) -> Result<Vec<Type<TypeLayout>>> {
+    fields.iter().map(|f| compute(&f.ty)).collect()
+}
+
+fn build_fields(
+    fields: &[RecordField<()>],
+    tys: Vec<Type<TypeLayout>>,
+    offsets: &[u64],
+) -> Vec<RecordField<TypeLayout>> {
+    fields
+        .iter()
+        .zip(tys)
+        .zip(offsets)
+        .map(|((f, ty), &offset_bits)| RecordField {
+            layout: Some(FieldLayout {
+                offset_bits,
+                size_bits: ty.layout.size_bits,
+            }),
+            annotations: vec![],
+            named: f.named,
+            bit_width: None,
+            signed: None,
+            ty,
+        })
+        .collect()
+}
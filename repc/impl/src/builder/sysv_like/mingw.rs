@@ -1,14 +1,21 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
+use crate::builder::common::{bit_field_is_signed, is_bool};
 use crate::builder::sysv_like::{Dialect, RecordLayoutBuilder};
+use crate::builder::Options;
 use crate::layout::{FieldLayout, RecordField, RecordKind, Type, TypeLayout};
 use crate::result::{err, ErrorType, Result};
 use crate::target::Target;
 use crate::util::{
-    align_to, annotation_alignment, is_attr_packed, size_add, MaxAssign, MinAssign, BITS_PER_BYTE,
+    align_to, annotation_alignment, is_attr_packed, size_add, validate_alignas, MaxAssign,
+    MinAssign, BITS_PER_BYTE,
 };
 
-pub(crate) fn compute_layout(target: Target, ty: &Type<()>) -> Result<Type<TypeLayout>> {
-    super::compute_layout(target, ty, Dialect::Mingw)
+pub(crate) fn compute_layout(
+    target: Target,
+    ty: &Type<()>,
+    options: Options,
+) -> Result<Type<TypeLayout>> {
+    super::compute_layout(target, ty, Dialect::Mingw, options)
 }
 
 pub(super) struct OngoingBitfield {
@@ -31,7 +38,13 @@ pub(super) fn layout_fields(
 }
 
 fn layout_field(rlb: &mut RecordLayoutBuilder, field: &RecordField<()>) -> Result<()> {
-    let ty = compute_layout(rlb.target, &field.ty)?;
+    let ty = compute_layout(rlb.target, &field.ty, rlb.options)?;
+    validate_alignas(&field.annotations, ty.layout.field_alignment_bits)?;
+    // GCC rejects `bool` bit-fields that are wider than one bit. See test case 0089 for
+    // bool bit-fields of width one.
+    if is_bool(&ty) && field.bit_width > Some(1) {
+        return Err(err(ErrorType::OversizedBitfield));
+    }
     let annotation_alignment_bits =
         annotation_alignment(rlb.target, &field.annotations).unwrap_or(BITS_PER_BYTE);
     // __attribute__((packed)) on the record is identical to __attribute__((packed)) on each
@@ -98,6 +111,9 @@ fn layout_field(rlb: &mut RecordLayoutBuilder, field: &RecordField<()>) -> Resul
         annotations: field.annotations.clone(),
         named: field.named,
         bit_width: field.bit_width,
+        signed: field
+            .bit_width
+            .and_then(|_| bit_field_is_signed(rlb.target, &ty)),
         ty,
     });
     Ok(())
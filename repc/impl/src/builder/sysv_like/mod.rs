@@ -1,13 +1,17 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 use crate::builder::common::{
     apply_alignment_override, builtin_type_layout, compute_builtin_type_layout,
-    compute_opaque_type_layout, pack_all_enums,
+    compute_opaque_type_layout, enum_underlying_type, pack_all_enums, pragma_pack_effect,
+    validate_fixed_enum_values, PragmaPackEffect,
 };
+use crate::builder::rust;
 use crate::builder::sysv_like::mingw::OngoingBitfield;
+use crate::builder::Options;
 use crate::layout::{
-    Annotation, Array, BuiltinType, Record, RecordField, RecordKind, Type, TypeLayout, TypeVariant,
+    Annotation, Array, BuiltinType, Enum, Record, RecordField, RecordKind, Type, TypeLayout,
+    TypeVariant,
 };
-use crate::result::Result;
+use crate::result::{err, ErrorType, Result};
 use crate::target::{system_compiler, Compiler, Target};
 use crate::util::{
     align_to, annotation_alignment, is_attr_packed, pragma_pack_value, size_mul, BITS_PER_BYTE,
@@ -22,18 +26,29 @@ enum Dialect {
     Mingw,
 }
 
-fn compute_layout(target: Target, ty: &Type<()>, dialect: Dialect) -> Result<Type<TypeLayout>> {
+fn compute_layout(
+    target: Target,
+    ty: &Type<()>,
+    dialect: Dialect,
+    options: Options,
+) -> Result<Type<TypeLayout>> {
     match &ty.variant {
         TypeVariant::Builtin(bi) => compute_builtin_type_layout(target, *bi),
         TypeVariant::Opaque(layout) => compute_opaque_type_layout(*layout),
         TypeVariant::Record(r) => {
-            compute_record_layout(dialect, target, r.kind, &ty.annotations, &r.fields)
+            compute_record_layout(dialect, target, options, r.kind, &ty.annotations, &r.fields)
+        }
+        TypeVariant::Enum(e) => compute_enum_layout(target, options, e, &ty.annotations),
+        TypeVariant::RustStruct(s) => {
+            rust::compute_struct_layout(s, |ty| compute_layout(target, ty, dialect, options))
+        }
+        TypeVariant::RustEnum(e) => {
+            rust::compute_enum_layout(e, |ty| compute_layout(target, ty, dialect, options))
         }
-        TypeVariant::Enum(v) => compute_enum_layout(target, v, &ty.annotations),
         TypeVariant::Typedef(dst) => {
             // #pragma pack and __attribute__((packed)) are ignored on typedefs.
             // See test case 0088.
-            let dst_ty = compute_layout(target, dst, dialect)?;
+            let dst_ty = compute_layout(target, dst, dialect, options)?;
             let max_alignment = annotation_alignment(target, &ty.annotations);
             // __attribute__((aligned(N))) sets the field alignment to N even if N is smaller
             // than the alignment of the underlying type. See test case 0046.
@@ -44,7 +59,17 @@ fn compute_layout(target: Target, ty: &Type<()>, dialect: Dialect) -> Result<Typ
             })
         }
         TypeVariant::Array(a) => {
-            let ety = compute_layout(target, &a.element_type, dialect)?;
+            let ety = compute_layout(target, &a.element_type, dialect, options)?;
+            // GCC rejects arrays whose element type is aligned to more than its size, even if
+            // the element type is itself an array. Clang accepts them. See test cases 0008
+            // and 0017.
+            let el = ety.layout;
+            if system_compiler(target) == Compiler::Gcc
+                && el.size_bits != 0
+                && el.field_alignment_bits > el.size_bits
+            {
+                return Err(err(ErrorType::OveralignedArrayElement));
+            }
             Ok(Type {
                 layout: TypeLayout {
                     // The size of an array is the size of the underlying type multiplied by the
@@ -74,6 +99,7 @@ fn compute_layout(target: Target, ty: &Type<()>, dialect: Dialect) -> Result<Typ
 
 struct RecordLayoutBuilder {
     target: Target,
+    options: Options,
     // The alignment of this record.
     alignment_bits: u64,
     // The size of the record. This might not be a multiple of 8 if the record contains bit-fields.
@@ -94,6 +120,7 @@ struct RecordLayoutBuilder {
 fn compute_record_layout(
     dialect: Dialect,
     target: Target,
+    options: Options,
     kind: RecordKind,
     annotations: &[Annotation],
     fields: &[RecordField<()>],
@@ -101,9 +128,8 @@ fn compute_record_layout(
     let attr_packed = is_attr_packed(annotations);
     // Pre-validation ensures that there is at most one #pragma pack annotation.
     let pragma_pack_value = pragma_pack_value(annotations);
-    // #pragma pack(N) is ignored if N is not one of {1,2,4,8,16}. See test case 0064.
-    let max_field_alignment_bits = match pragma_pack_value {
-        Some(8) | Some(16) | Some(32) | Some(64) | Some(128) => pragma_pack_value,
+    let max_field_alignment_bits = match pragma_pack_value.map(|n| pragma_pack_effect(target, n)) {
+        Some(PragmaPackEffect::MaxFieldAlignment(n)) => Some(n),
         _ => None,
     };
     // An alignment annotation on the record increases the overall alignment of the record.
@@ -111,6 +137,7 @@ fn compute_record_layout(
     let alignment_bits = annotation_alignment(target, annotations).unwrap_or(BITS_PER_BYTE);
     let mut rlb = RecordLayoutBuilder {
         target,
+        options,
         alignment_bits,
         size_bits: 0,
         attr_packed,
@@ -142,38 +169,22 @@ fn compute_record_layout(
 
 fn compute_enum_layout(
     target: Target,
-    v: &[i128],
+    options: Options,
+    e: &Enum,
     annotations: &[Annotation],
 ) -> Result<Type<TypeLayout>> {
     // #pragma pack is ignored on enums. See test case 0061.
 
-    // A packed enum has minimum size 1 byte. On some targets, all enums have an implicit
-    // packed attribute. Otherwise the minimum size is the size of `int`. See test case 0060.
-    let mut required_size = match is_attr_packed(annotations) || pack_all_enums(target) {
-        true => BITS_PER_BYTE,
-        false => builtin_type_layout(target, BuiltinType::Int).size_bits,
-    };
-    // The size of the enum is the size of the smallest integer type whose size is at least
-    // as large as the minimum size and which can represent all variants. See test case 0062.
-    for &v in v {
-        let (v, offset) = if v < 0 { (!v, 1) } else { (v, 0) };
-        let required = 128 - v.leading_zeros() as u64 + offset;
-        while required > required_size {
-            required_size *= 2;
+    let underlying_type = match e.fixed_underlying_type {
+        // An enum with a fixed underlying type has the layout of that type. Packed attributes
+        // and -fshort-enums do not apply.
+        Some(bi) => {
+            validate_fixed_enum_values(target, bi, &e.variants)?;
+            bi
         }
-    }
-    let candidates = [
-        BuiltinType::Char,
-        BuiltinType::Short,
-        BuiltinType::Int,
-        BuiltinType::Long,
-        BuiltinType::LongLong,
-    ];
-    let layout = candidates
-        .iter()
-        .map(|ty| builtin_type_layout(target, *ty))
-        .find(|l| l.size_bits >= required_size)
-        .unwrap_or_else(|| builtin_type_layout(target, BuiltinType::I128));
+        None => select_underlying_type(target, options, &e.variants, annotations),
+    };
+    let layout = builtin_type_layout(target, underlying_type);
     // Clang respects __attribute__((aligned)) on enums. The behavior is the same
     // as the behavior on typedefs. See test case 0063.
     let max_alignment = match system_compiler(target) {
@@ -183,6 +194,55 @@ fn compute_enum_layout(
     Ok(Type {
         layout: apply_alignment_override(layout, max_alignment),
         annotations: annotations.to_vec(),
-        variant: TypeVariant::Enum(v.to_vec()),
+        variant: TypeVariant::Enum(Enum {
+            fixed_underlying_type: e.fixed_underlying_type,
+            variants: e.variants.clone(),
+            underlying_type: Some(enum_underlying_type(target, underlying_type)),
+        }),
     })
 }
+
+fn select_underlying_type(
+    target: Target,
+    options: Options,
+    variants: &[i128],
+    annotations: &[Annotation],
+) -> BuiltinType {
+    // A packed enum has minimum size 1 byte. On some targets, all enums have an implicit
+    // packed attribute. -fshort-enums has the same effect. Otherwise the minimum size is the
+    // size of `int`. See test case 0060.
+    let packed = is_attr_packed(annotations) || pack_all_enums(target) || options.short_enums;
+    let mut required_size = match packed {
+        true => BITS_PER_BYTE,
+        false => builtin_type_layout(target, BuiltinType::Int).size_bits,
+    };
+    // The underlying type is unsigned unless one of the values is negative. In a signed
+    // type, all values require an additional sign bit. See test case 0093.
+    let signed = variants.iter().any(|&v| v < 0);
+    // The size of the enum is the size of the smallest integer type whose size is at least
+    // as large as the minimum size and which can represent all variants. See test case 0062.
+    for &v in variants {
+        let v = if v < 0 { !v } else { v };
+        let required = 128 - v.leading_zeros() as u64 + signed as u64;
+        while required > required_size {
+            required_size *= 2;
+        }
+    }
+    use BuiltinType::*;
+    let candidates = match signed {
+        true => [SignedChar, Short, Int, Long, LongLong, I128],
+        false => [
+            UnsignedChar,
+            UnsignedShort,
+            UnsignedInt,
+            UnsignedLong,
+            UnsignedLongLong,
+            U128,
+        ],
+    };
+    candidates
+        .iter()
+        .copied()
+        .find(|&ty| builtin_type_layout(target, ty).size_bits >= required_size)
+        .unwrap_or(candidates[candidates.len() - 1])
+}
@@ -1,19 +1,25 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 use crate::builder::common::{
-    ignore_non_zero_sized_bitfield_type_alignment, ignore_zero_sized_bitfield_type_alignmont,
-    min_zero_width_bitfield_alignment, unnamed_field_affects_record_alignment,
+    bit_field_is_signed, ignore_non_zero_sized_bitfield_type_alignment,
+    ignore_zero_sized_bitfield_type_alignmont, is_bool, min_zero_width_bitfield_alignment,
+    unnamed_field_affects_record_alignment,
 };
 use crate::builder::sysv_like::{Dialect, RecordLayoutBuilder};
+use crate::builder::Options;
 use crate::layout::{FieldLayout, RecordField, RecordKind, Type, TypeLayout};
 use crate::result::{err, ErrorType, Result};
 use crate::target::{system_compiler, Compiler, Target};
 use crate::util::{
-    align_to, annotation_alignment, is_attr_packed, size_add, MaxAssign, MinAssign, MinExt,
-    BITS_PER_BYTE,
+    align_to, annotation_alignment, is_attr_packed, size_add, validate_alignas, MaxAssign,
+    MinAssign, MinExt, BITS_PER_BYTE,
 };
 
-pub(crate) fn compute_layout(target: Target, ty: &Type<()>) -> Result<Type<TypeLayout>> {
-    super::compute_layout(target, ty, Dialect::Sysv)
+pub(crate) fn compute_layout(
+    target: Target,
+    ty: &Type<()>,
+    options: Options,
+) -> Result<Type<TypeLayout>> {
+    super::compute_layout(target, ty, Dialect::Sysv, options)
 }
 
 pub(super) fn layout_fields(
@@ -27,7 +33,13 @@ pub(super) fn layout_fields(
 }
 
 fn layout_field(rlb: &mut RecordLayoutBuilder, field: &RecordField<()>) -> Result<()> {
-    let ty = super::compute_layout(rlb.target, &field.ty, Dialect::Sysv)?;
+    let ty = super::compute_layout(rlb.target, &field.ty, Dialect::Sysv, rlb.options)?;
+    validate_alignas(&field.annotations, ty.layout.field_alignment_bits)?;
+    // GCC and Clang reject `bool` bit-fields that are wider than one bit. See test case
+    // 0089 for bool bit-fields of width one.
+    if is_bool(&ty) && field.bit_width > Some(1) {
+        return Err(err(ErrorType::OversizedBitfield));
+    }
     let layout = match field.bit_width {
         Some(size_bits) => layout_bit_field(
             rlb,
@@ -43,6 +55,9 @@ fn layout_field(rlb: &mut RecordLayoutBuilder, field: &RecordField<()>) -> Resul
         annotations: field.annotations.clone(),
         named: field.named,
         bit_width: field.bit_width,
+        signed: field
+            .bit_width
+            .and_then(|_| bit_field_is_signed(rlb.target, &ty)),
         ty,
     });
     Ok(())
@@ -0,0 +1,292 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//! Functions for locating the fields of a type by their paths.
+//!
+//! The leaf fields of a type are the builtin types, enums, opaque types, and bit-fields
+//! contained in it. [`leaf_fields`] enumerates them together with their absolute offsets,
+//! and [`resolve_field_path`] computes the absolute offset of a single field.
+use crate::layout::{BuiltinType, RecordField, Type, TypeLayout, TypeVariant};
+use crate::result::{err, ErrorType, Result};
+
+/// An element of the path from a type to one of the fields contained in it.
+///
+/// Typedefs are transparent. For example, if a field has a typedef of a record as its
+/// type, [`FieldPathElement::Field`] selects a field of that record.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FieldPathElement {
+    /// The field with the given index in a record or Rust struct.
+    ///
+    /// The index counts all fields, including unnamed bit-fields.
+    Field(usize),
+    /// The field of a variant of a Rust enum.
+    ///
+    /// The first index is the index of the variant, the second index is the index of the
+    /// field within the variant.
+    VariantField(usize, usize),
+    /// The element with the given index in an array.
+    Index(u64),
+    /// All elements of an array.
+    ///
+    /// The offset of a field at such a path is the offset of the field in the first element.
+    /// The field in the element with index `i` is `i * stride_bits` bits further.
+    Elements {
+        /// The number of elements in the array or `None` for flexible arrays.
+        num_elements: Option<u64>,
+        /// The distance between two consecutive elements in bits.
+        stride_bits: u64,
+    },
+}
+
+/// Controls how [`leaf_fields`] expands arrays.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ArrayExpansion {
+    /// Every element of an array is expanded separately and its fields have a
+    /// [`FieldPathElement::Index`] in their paths.
+    ///
+    /// Flexible arrays have no elements in this mode.
+    Elements,
+    /// The elements of an array are expanded once and their fields have a
+    /// [`FieldPathElement::Elements`] in their paths.
+    Strided,
+}
+
+/// A field at a path in a type.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PathField<'a> {
+    /// The offset of the field from the start of the root type in bits.
+    pub offset_bits: u64,
+    /// The size of the field in bits.
+    ///
+    /// For bit-fields, this is the width of the bit-field.
+    pub size_bits: u64,
+    /// Whether the field is a bit-field.
+    pub bit_field: bool,
+    /// The type of the field.
+    pub ty: &'a Type<TypeLayout>,
+}
+
+/// A leaf field produced by [`leaf_fields`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LeafField<'a> {
+    /// The path from the root type to the field.
+    pub path: Vec<FieldPathElement>,
+    /// The location of the field.
+    pub field: PathField<'a>,
+    /// The builtin type of the field.
+    ///
+    /// For enums, this is the underlying type of the enum. For opaque types, this is
+    /// `None`.
+    pub builtin_type: Option<BuiltinType>,
+}
+
+/// Returns the field at `path` in `ty`.
+///
+/// `ty` must be the result of computing the layout of a type. The array index of a
+/// [`FieldPathElement::Index`] can be equal to the number of elements of the array. Such a
+/// path refers to the end of the array.
+pub fn resolve_field_path<'a>(
+    ty: &'a Type<TypeLayout>,
+    path: &[FieldPathElement],
+) -> Result<PathField<'a>> {
+    let mut field = root(ty);
+    for &pe in path {
+        field = child(field, pe)?;
+    }
+    Ok(field)
+}
+
+/// Returns an iterator over the leaf fields of `ty`.
+///
+/// `ty` must be the result of computing the layout of a type. The fields are produced in
+/// the order in which they appear in the type. The fields of unions and of the variants of
+/// Rust enums overlap. Unnamed bit-fields are skipped.
+pub fn leaf_fields(ty: &Type<TypeLayout>, arrays: ArrayExpansion) -> LeafFields<'_> {
+    LeafFields {
+        arrays,
+        stack: vec![Work::Field(vec![], root(ty))],
+    }
+}
+
+/// An iterator over the leaf fields of a type.
+///
+/// This struct is created by [`leaf_fields`].
+pub struct LeafFields<'a> {
+    arrays: ArrayExpansion,
+    stack: Vec<Work<'a>>,
+}
+
+enum Work<'a> {
+    Field(Vec<FieldPathElement>, PathField<'a>),
+    Elements {
+        path: Vec<FieldPathElement>,
+        array: PathField<'a>,
+        index: u64,
+        num_elements: u64,
+    },
+}
+
+impl<'a> Iterator for LeafFields<'a> {
+    type Item = LeafField<'a>;
+
+    fn next(&mut self) -> Option<LeafField<'a>> {
+        while let Some(work) = self.stack.pop() {
+            let (path, field) = match work {
+                Work::Field(path, field) => (path, field),
+                Work::Elements {
+                    path,
+                    array,
+                    index,
+                    num_elements,
+                } => {
+                    if index + 1 < num_elements {
+                        self.stack.push(Work::Elements {
+                            path: path.clone(),
+                            array,
+                            index: index + 1,
+                            num_elements,
+                        });
+                    }
+                    let pe = FieldPathElement::Index(index);
+                    (with(&path, pe), child(array, pe).unwrap())
+                }
+            };
+            let builtin_type = match &strip_typedefs(field.ty).variant {
+                TypeVariant::Builtin(bi) => Some(*bi),
+                TypeVariant::Enum(e) => e.underlying_type.map(|u| u.ty),
+                TypeVariant::Opaque(_) => None,
+                TypeVariant::Record(r) => {
+                    self.push_fields(&path, field, &r.fields, FieldPathElement::Field);
+                    continue;
+                }
+                TypeVariant::RustStruct(s) => {
+                    self.push_fields(&path, field, &s.fields, FieldPathElement::Field);
+                    continue;
+                }
+                TypeVariant::RustEnum(e) => {
+                    for (i, v) in e.variants.iter().enumerate().rev() {
+                        self.push_fields(&path, field, &v.fields, |j| {
+                            FieldPathElement::VariantField(i, j)
+                        });
+                    }
+                    continue;
+                }
+                TypeVariant::Array(a) => {
+                    match self.arrays {
+                        ArrayExpansion::Elements => match a.num_elements {
+                            Some(n) if n > 0 => self.stack.push(Work::Elements {
+                                path,
+                                array: field,
+                                index: 0,
+                                num_elements: n,
+                            }),
+                            _ => {}
+                        },
+                        ArrayExpansion::Strided => {
+                            let pe = FieldPathElement::Elements {
+                                num_elements: a.num_elements,
+                                stride_bits: a.element_type.layout.size_bits,
+                            };
+                            let element = child(field, pe).unwrap();
+                            self.stack.push(Work::Field(with(&path, pe), element));
+                        }
+                    }
+                    continue;
+                }
+                TypeVariant::Typedef(_) => unreachable!(),
+            };
+            return Some(LeafField {
+                path,
+                field,
+                builtin_type,
+            });
+        }
+        None
+    }
+}
+
+impl<'a> LeafFields<'a> {
+    fn push_fields(
+        &mut self,
+        path: &[FieldPathElement],
+        parent: PathField<'a>,
+        fields: &[RecordField<TypeLayout>],
+        pe: impl Fn(usize) -> FieldPathElement,
+    ) {
+        // The stack is processed from the back, so the fields are pushed in reverse order.
+        for i in (0..fields.len()).rev() {
+            if let Ok(field) = child(parent, pe(i)) {
+                self.stack.push(Work::Field(with(path, pe(i)), field));
+            }
+        }
+    }
+}
+
+fn root(ty: &Type<TypeLayout>) -> PathField<'_> {
+    PathField {
+        offset_bits: 0,
+        size_bits: ty.layout.size_bits,
+        bit_field: false,
+        ty,
+    }
+}
+
+fn child(parent: PathField<'_>, pe: FieldPathElement) -> Result<PathField<'_>> {
+    use FieldPathElement::*;
+    let (fields, i) = match (pe, &strip_typedefs(parent.ty).variant) {
+        (Field(i), TypeVariant::Record(r)) => (&r.fields, i),
+        (Field(i), TypeVariant::RustStruct(s)) => (&s.fields, i),
+        (VariantField(i, j), TypeVariant::RustEnum(e)) => match e.variants.get(i) {
+            Some(v) => (&v.fields, j),
+            None => return Err(err(ErrorType::FieldIndexOutOfBounds)),
+        },
+        (Field(_), _) | (VariantField(..), _) => return Err(err(ErrorType::NotARecord)),
+        (Index(_), TypeVariant::Array(a)) | (Elements { .. }, TypeVariant::Array(a)) => {
+            let index = match pe {
+                Index(i) => i,
+                _ => 0,
+            };
+            if a.num_elements.is_some_and(|n| index > n) {
+                return Err(err(ErrorType::ArrayIndexOutOfBounds));
+            }
+            let ty = &*a.element_type;
+            let offset_bits = ty
+                .layout
+                .size_bits
+                .checked_mul(index)
+                .and_then(|o| o.checked_add(parent.offset_bits))
+                .ok_or_else(|| err(ErrorType::OffsetOverflow))?;
+            return Ok(PathField {
+                offset_bits,
+                size_bits: ty.layout.size_bits,
+                bit_field: false,
+                ty,
+            });
+        }
+        (Index(_), _) | (Elements { .. }, _) => return Err(err(ErrorType::NotAnArray)),
+    };
+    let field = fields
+        .get(i)
+        .ok_or_else(|| err(ErrorType::FieldIndexOutOfBounds))?;
+    // Unnamed bit-fields do not have a layout.
+    let layout = field
+        .layout
+        .ok_or_else(|| err(ErrorType::FieldIndexOutOfBounds))?;
+    Ok(PathField {
+        offset_bits: parent.offset_bits + layout.offset_bits,
+        size_bits: layout.size_bits,
+        bit_field: field.bit_width.is_some(),
+        ty: &field.ty,
+    })
+}
+
+fn strip_typedefs(mut ty: &Type<TypeLayout>) -> &Type<TypeLayout> {
+    while let TypeVariant::Typedef(dst) = &ty.variant {
+        ty = dst;
+    }
+    ty
+}
+
+fn with(path: &[FieldPathElement], pe: FieldPathElement) -> Vec<FieldPathElement> {
+    let mut path = path.to_vec();
+    path.push(pe);
+    path
+}
@@ -56,6 +56,16 @@ pub enum Annotation {
     /// If the argument is `None`, it corresponds to `__attribute__((aligned))`. On MSVC
     /// targets, the behavior is the behavior of Clang.
     Align(Option<u64>),
+    /// The `Alignas` annotation.
+    ///
+    /// The argument must be a power of two and at least 8. It is the corresponding C
+    /// argument but in bits instead of bytes.
+    ///
+    /// This corresponds to `_Alignas(n/8)` in C. Unlike `Align`, this annotation can only
+    /// be applied to fields that are not bit-fields and it can never reduce the alignment of
+    /// a field, not even in a packed record. Otherwise it has the same effect as
+    /// `Align(Some(n))` on the field.
+    Alignas(u64),
 }
 
 /// A collection of types encoding the layout of a type.
@@ -69,7 +79,7 @@ pub trait Layout {
 }
 
 /// The computed layout of a type.
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
 pub struct TypeLayout {
     /// The size of the type in bits.
     ///
@@ -139,9 +149,7 @@ pub enum TypeVariant<I: Layout> {
     /// An array.
     Array(Array<I>),
     /// An enum.
-    ///
-    /// The vector contains the values of the variants.
-    Enum(Vec<i128>),
+    Enum(Enum),
     /// An opaque type.
     ///
     /// This does not correspond to anything in C. It is useful if the layout of a nested
@@ -149,6 +157,10 @@ pub enum TypeVariant<I: Layout> {
     /// substituting a type for the opaque type with the same layout leads to the same
     /// layout for the containing type.
     Opaque(I::OpaqueLayout),
+    /// A Rust struct with a `repr` attribute.
+    RustStruct(RustStruct<I>),
+    /// A Rust enum whose variants can have fields.
+    RustEnum(RustEnum<I>),
 }
 
 impl<I: Layout> TypeVariant<I> {
@@ -172,6 +184,22 @@ impl<I: Layout> TypeVariant<I> {
             }),
             TypeVariant::Opaque(l) => TypeVariant::Opaque(l.into()),
             TypeVariant::Enum(v) => TypeVariant::Enum(v),
+            TypeVariant::RustStruct(s) => TypeVariant::RustStruct(RustStruct {
+                repr: s.repr,
+                fields: s.fields.into_iter().map(|v| v.into()).collect(),
+            }),
+            TypeVariant::RustEnum(e) => TypeVariant::RustEnum(RustEnum {
+                repr: e.repr,
+                tag_layout: e.tag_layout.map(|v| v.into()),
+                variants: e
+                    .variants
+                    .into_iter()
+                    .map(|v| RustEnumVariant {
+                        discriminant: v.discriminant,
+                        fields: v.fields.into_iter().map(|v| v.into()).collect(),
+                    })
+                    .collect(),
+            }),
         }
     }
 }
@@ -203,6 +231,7 @@ impl<I: Layout> TypeVariant<I> {
 ///                 annotations: vec!(Annotation::Align(Some(128))),
 ///                 named: true,
 ///                 bit_width: None,
+///                 signed: None,
 ///                 ty: Type {
 ///                     layout: (),
 ///                     annotations: vec!(),
@@ -214,6 +243,7 @@ impl<I: Layout> TypeVariant<I> {
 ///                 annotations: vec!(),
 ///                 named: false,
 ///                 bit_width: Some(1),
+///                 signed: None,
 ///                 ty: Type {
 ///                     layout: (),
 ///                     annotations: vec!(),
@@ -232,6 +262,205 @@ pub struct Record<I: Layout> {
     pub fields: Vec<RecordField<I>>,
 }
 
+/// An enum.
+///
+/// # Example
+///
+/// ```c
+/// enum E : unsigned char {
+///     A = 1,
+///     B = 255,
+/// };
+/// ```
+///
+/// ```
+/// # use repc_impl::layout::{Enum, BuiltinType};
+/// Enum {
+///     fixed_underlying_type: Some(BuiltinType::UnsignedChar),
+///     variants: vec![1, 255],
+///     underlying_type: None,
+/// };
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Enum {
+    /// The fixed underlying type of the enum, if any.
+    ///
+    /// This corresponds to `enum E : T` in C23 and C++11. The type must be an integer type
+    /// and all values must be representable in it. If this is `None`, the compiler selects
+    /// the underlying type based on the values of the variants.
+    pub fixed_underlying_type: Option<BuiltinType>,
+    /// The values of the variants.
+    pub variants: Vec<i128>,
+    /// The underlying type selected by the compiler.
+    ///
+    /// This field is ignored by `compute_layout`. In the computed layout, it contains the
+    /// integer type whose layout the enum has. It is `None` in types whose layout was not
+    /// computed by this crate.
+    pub underlying_type: Option<EnumUnderlyingType>,
+}
+
+/// The underlying type of an enum.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct EnumUnderlyingType {
+    /// The integer type whose layout and range of values the enum has.
+    ///
+    /// This is never `Char`. If the fixed underlying type of the enum is `Char`, this is
+    /// either `SignedChar` or `UnsignedChar` depending on the target.
+    pub ty: BuiltinType,
+    /// Whether the underlying type is signed.
+    ///
+    /// The values of enum bit-fields are sign-extended if and only if this is `true`.
+    pub signed: bool,
+}
+
+/// A Rust struct.
+///
+/// The fields must be named regular fields without annotations.
+///
+/// # Example
+///
+/// ```rust
+/// #[repr(transparent)]
+/// struct S(*const u8, ());
+/// ```
+///
+/// ```
+/// # use repc_impl::layout::{RustStruct, RustStructRepr, RecordField, Type, TypeVariant, BuiltinType};
+/// let field = |bi| RecordField {
+///     layout: None,
+///     annotations: vec!(),
+///     named: true,
+///     bit_width: None,
+///     signed: None,
+///     ty: Type {
+///         layout: (),
+///         annotations: vec!(),
+///         variant: TypeVariant::Builtin(bi),
+///     },
+/// };
+/// RustStruct::<()> {
+///     repr: RustStructRepr::Transparent,
+///     fields: vec![field(BuiltinType::Pointer), field(BuiltinType::Unit)],
+/// };
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RustStruct<I: Layout> {
+    /// The `repr` attribute of the struct.
+    pub repr: RustStructRepr,
+    /// The fields of the struct.
+    pub fields: Vec<RecordField<I>>,
+}
+
+/// The `repr` attribute of a Rust struct.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RustStructRepr {
+    /// `#[repr(C)]`
+    ///
+    /// The fields are laid out in order like the fields of a C struct without annotations.
+    /// Unlike in C, a struct without fields has size 0 on all targets.
+    C,
+    /// `#[repr(transparent)]`
+    ///
+    /// The struct has the same layout as its only field that is not a zero-sized type with
+    /// alignment 1.
+    Transparent,
+}
+
+/// A Rust enum.
+///
+/// The layout of enums with fields is defined in
+/// [RFC 2195](https://rust-lang.github.io/rfcs/2195-really-tagged-unions.html).
+///
+/// # Example
+///
+/// ```rust
+/// #[repr(C, u8)]
+/// enum E {
+///     A(u32),
+///     B = 5,
+/// }
+/// ```
+///
+/// ```
+/// # use repc_impl::layout::{RustEnum, RustEnumRepr, RustEnumVariant, RecordField, Type, TypeVariant, BuiltinType};
+/// RustEnum::<()> {
+///     repr: RustEnumRepr::CInt(BuiltinType::U8),
+///     tag_layout: None,
+///     variants: vec![
+///         RustEnumVariant {
+///             discriminant: 0,
+///             fields: vec![RecordField {
+///                 layout: None,
+///                 annotations: vec!(),
+///                 named: true,
+///                 bit_width: None,
+///                 signed: None,
+///                 ty: Type {
+///                     layout: (),
+///                     annotations: vec!(),
+///                     variant: TypeVariant::Builtin(BuiltinType::U32),
+///                 },
+///             }],
+///         },
+///         RustEnumVariant {
+///             discriminant: 5,
+///             fields: vec![],
+///         },
+///     ],
+/// };
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RustEnum<I: Layout> {
+    /// The `repr` attribute of the enum.
+    pub repr: RustEnumRepr,
+    /// The layout of the tag.
+    ///
+    /// This is `None` in the input and for enums without tag.
+    pub tag_layout: Option<I::FieldLayout>,
+    /// The variants of the enum.
+    pub variants: Vec<RustEnumVariant<I>>,
+}
+
+/// A variant of a Rust enum.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RustEnumVariant<I: Layout> {
+    /// The discriminant of the variant.
+    pub discriminant: i128,
+    /// The fields of the variant.
+    ///
+    /// The fields must be named regular fields without annotations. Their offsets are
+    /// relative to the start of the enum.
+    pub fields: Vec<RecordField<I>>,
+}
+
+/// The `repr` attribute of a Rust enum.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RustEnumRepr {
+    /// `#[repr(C)]`
+    ///
+    /// The tag is a C enum containing the discriminants. It is followed by a union of the
+    /// variants.
+    C,
+    /// `#[repr(T)]`
+    ///
+    /// The enum is a union of the variants. Each variant starts with a tag of type `T`.
+    /// `T` must be one of the integer types from `U8` to `I64`.
+    Int(BuiltinType),
+    /// `#[repr(C, T)]`
+    ///
+    /// Like `C` except that the tag has type `T`.
+    /// `T` must be one of the integer types from `U8` to `I64`.
+    CInt(BuiltinType),
+    /// No `repr` attribute.
+    ///
+    /// The layout of such enums is only defined for option-like enums: enums with two
+    /// variants, one without fields and one with a single field. The type of the field
+    /// must not have the value 0, e.g., `&T` or `NonNull<T>`. The enum then has the same
+    /// layout as the field. This cannot be checked since the builtin `Pointer` type does
+    /// not distinguish between nullable and non-nullable pointers.
+    Rust,
+}
+
 /// An array.
 ///
 /// This corresponds to the type of `T var[N]` in C.
@@ -281,6 +510,12 @@ pub struct RecordField<I: Layout> {
     ///
     /// The field is recognized as a bit-field if and only if this is `Some`.
     pub bit_width: Option<u64>,
+    /// If this is a bit-field, whether the bit-field is signed.
+    ///
+    /// A signed bit-field is sign-extended when it is read. This is ignored when computing
+    /// the layout of a type and set for every bit-field in the computed layout unless the type
+    /// of the bit-field is opaque.
+    pub signed: Option<bool>,
     /// The type of the field.
     pub ty: Type<I>,
 }
@@ -298,6 +533,7 @@ impl<I: Layout> RecordField<I> {
             annotations: self.annotations,
             named: self.named,
             bit_width: self.bit_width,
+            signed: self.signed,
             ty: self.ty.into(),
         }
     }
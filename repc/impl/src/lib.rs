@@ -2,11 +2,16 @@
 #![deny(unreachable_patterns)]
 #![deny(non_snake_case)]
 
+pub mod bitfield;
 pub mod builder;
+pub mod flatten;
 pub mod layout;
 pub mod result;
 pub mod target;
 #[cfg(test)]
 mod tests;
+pub mod typedefs;
+pub mod unaligned;
 pub mod util;
 pub mod visitor;
+pub mod warning;
@@ -66,6 +66,66 @@ pub enum ErrorType {
     ///
     /// Fields cannot have `PragmaPack` annotations.
     PragmaPackedField,
+    /// A type has an `Alignas` annotation.
+    ///
+    /// `Alignas` annotations can only be applied to fields.
+    AlignasOnType,
+    /// A bit-field has an `Alignas` annotation.
+    ///
+    /// `Alignas` annotations cannot be applied to bit-fields.
+    AlignasOnBitField,
+    /// An `Alignas` annotation is smaller than the alignment of the type of the field.
+    ///
+    /// Unlike `Align` annotations, `Alignas` annotations cannot reduce the alignment of a
+    /// field.
+    AlignasReducesAlignment,
+    /// The element type of an array has an alignment larger than its size.
+    ///
+    /// GCC rejects such arrays unless the element type is itself an array. This usually
+    /// happens when a typedef of a small type has an `Align` annotation. You probably want
+    /// to annotate a typedef of the array instead.
+    OveralignedArrayElement,
+    /// A Rust struct, a Rust enum, or one of their fields was annotated.
+    ///
+    /// The layout of Rust types is determined by their `repr` attribute only.
+    AnnotatedRustType,
+    /// A Rust struct or enum contains a bit-field.
+    RustBitField,
+    /// The integer type in the `repr` attribute of a Rust enum is not one of the types from
+    /// `U8` to `I64`.
+    InvalidRustEnumRepr,
+    /// A Rust enum with a `repr` attribute has no variants.
+    EmptyRustEnum,
+    /// The discriminant of a Rust enum does not fit into the integer type in the `repr`
+    /// attribute.
+    RustEnumDiscriminantOutOfRange,
+    /// A Rust enum without `repr` attribute is not option-like.
+    ///
+    /// Option-like enums have two variants, one without fields and one with a single field.
+    InvalidOptionLikeEnum,
+    /// The field of an option-like Rust enum is not a pointer.
+    ///
+    /// The field-less variant can only be stored in the null value of a pointer. Other
+    /// types with niches are not supported.
+    OptionLikeEnumWithoutNiche,
+    /// A `repr(transparent)` struct has more than one field that is not a zero-sized type
+    /// with alignment 1.
+    InvalidTransparentStruct,
+    /// The fixed underlying type of an enum is not an integer type.
+    InvalidEnumUnderlyingType,
+    /// The value of a variant of an enum cannot be represented by its fixed underlying type.
+    EnumValueOutOfRange,
+    /// The type is not a record or a typedef of a record.
+    NotARecord,
+    /// The type is not an array or a typedef of an array.
+    NotAnArray,
+    /// A field path refers to a field or variant that does not exist or to an unnamed
+    /// bit-field.
+    FieldIndexOutOfBounds,
+    /// A field path refers to an array element past the end of the array.
+    ArrayIndexOutOfBounds,
+    /// The offset of a field cannot be represented in `u64`.
+    OffsetOverflow,
 }
 
 impl Display for ErrorType {
@@ -88,6 +148,41 @@ impl Display for ErrorType {
             OversizedBitfield => {
                 "The width of a bit-field cannot be larger than the width of the underlying type"
             }
+            AlignasOnType => "Only fields can have alignas annotations",
+            AlignasOnBitField => "Bit-fields cannot have alignas annotations",
+            AlignasReducesAlignment => {
+                "An alignas annotation cannot reduce the alignment of a field"
+            }
+            OveralignedArrayElement => {
+                "The alignment of an array element cannot be larger than its size"
+            }
+            AnnotatedRustType => "Rust types and their fields cannot have annotations",
+            RustBitField => "Rust types cannot contain bit-fields",
+            InvalidRustEnumRepr => "The repr of a Rust enum must be an integer type from u8 to i64",
+            EmptyRustEnum => "A Rust enum with a repr attribute must have at least one variant",
+            RustEnumDiscriminantOutOfRange => {
+                "The discriminant of a Rust enum does not fit into the repr type"
+            }
+            InvalidOptionLikeEnum => {
+                "A Rust enum without repr attribute must have two variants, one without fields \
+                 and one with a single field"
+            }
+            OptionLikeEnumWithoutNiche => {
+                "The field of a Rust enum without repr attribute must be a pointer"
+            }
+            InvalidTransparentStruct => {
+                "A transparent struct can have at most one field that is not a zero-sized type \
+                 with alignment 1"
+            }
+            InvalidEnumUnderlyingType => "The underlying type of an enum must be an integer type",
+            EnumValueOutOfRange => {
+                "The value of an enum variant does not fit into the underlying type"
+            }
+            NotARecord => "The type is not a record",
+            NotAnArray => "The type is not an array",
+            FieldIndexOutOfBounds => "The type has no such named field",
+            ArrayIndexOutOfBounds => "The array index is out of bounds",
+            OffsetOverflow => "The offset in bits overflows u64",
         };
         f.write_str(s)
     }
@@ -1,4 +1,8 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Compiler {
     Msvc,
@@ -6,8 +10,162 @@ pub enum Compiler {
     Clang,
 }
 
+/// The byte order of a target.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+/// The sizes of `int`, `long`, and pointers on a target.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DataModel {
+    /// `int` and pointers have 16 bits, `long` has 32 bits.
+    Ip16,
+    /// `int`, `long`, and pointers have 32 bits.
+    Ilp32,
+    /// `int` has 32 bits, `long` and pointers have 64 bits.
+    Lp64,
+    /// `int` and `long` have 32 bits, pointers have 64 bits.
+    Llp64,
+}
+
 include!(concat!(env!("OUT_DIR"), "/targets.rs"));
 
 include!(concat!(env!("OUT_DIR"), "/host.rs"));
 
 include!(concat!(env!("OUT_DIR"), "/target_map.rs"));
+
+/// GCC spellings of targets that differ from the LLVM names.
+const GCC_TARGETS: &[(&str, Target)] = &[
+    ("i686-w64-mingw32", Target::I686PcWindowsGnu),
+    ("x86_64-w64-mingw32", Target::X86_64PcWindowsGnu),
+];
+
+impl FromStr for Target {
+    type Err = ParseTargetError;
+
+    /// Parses a target.
+    ///
+    /// This accepts the LLVM names returned by [`Target::name`], rustc targets, the GCC
+    /// names of the MinGW targets, and common variations of these names such as
+    /// `x86_64-linux-gnu`, `amd64-unknown-freebsd`, or `arm64-apple-darwin`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        let candidates = normalized_target_names(&s);
+        for candidate in &candidates {
+            if let Some(t) = lookup_target(candidate) {
+                return Ok(t);
+            }
+        }
+        Err(ParseTargetError {
+            suggestions: suggest_targets(&candidates),
+            target: s,
+        })
+    }
+}
+
+fn lookup_target(s: &str) -> Option<Target> {
+    let llvm = TARGETS.iter().copied().find(|t| t.name() == s);
+    let rustc = || TARGET_MAP.iter().find(|t| t.0 == s).map(|t| t.1);
+    let gcc = || GCC_TARGETS.iter().find(|t| t.0 == s).map(|t| t.1);
+    llvm.or_else(rustc).or_else(gcc)
+}
+
+/// Returns `s` followed by variations of `s` that might be known targets.
+fn normalized_target_names(s: &str) -> Vec<String> {
+    let mut components: Vec<&str> = s.split('-').collect();
+    components[0] = match components[0] {
+        "amd64" => "x86_64",
+        "arm64" => "aarch64",
+        arch => arch,
+    };
+    // Version numbers as in `x86_64-apple-macosx10.15` or `aarch64-linux-android21`.
+    for c in &mut components[1..] {
+        *c = c.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    }
+    if components.get(2) == Some(&"darwin") {
+        components[2] = "macosx";
+    }
+    let mut res = vec![s.to_string(), components.join("-")];
+    if components.len() > 1 {
+        for vendor in &["unknown", "pc"] {
+            let mut c = components.clone();
+            c.insert(1, vendor);
+            res.push(c.join("-"));
+        }
+    }
+    res
+}
+
+/// Returns the known targets closest to any of the normalized names of the input.
+fn suggest_targets(normalized: &[String]) -> Vec<Target> {
+    let names = TARGETS
+        .iter()
+        .map(|t| (t.name(), *t))
+        .chain(TARGET_MAP.iter().copied())
+        .chain(GCC_TARGETS.iter().copied());
+    let distance = |name| {
+        normalized
+            .iter()
+            .map(|s| (edit_distance(s, name), s.len()))
+            .min()
+            .unwrap()
+    };
+    let mut candidates: Vec<_> = names
+        .map(|(name, t)| (distance(name), t))
+        .filter(|&((d, len), _)| d <= len.max(9) / 3)
+        .collect();
+    candidates.sort_by_key(|c| c.0);
+    let mut res: Vec<Target> = vec![];
+    for (_, t) in candidates {
+        if !res.contains(&t) && res.len() < 5 {
+            res.push(t);
+        }
+    }
+    res
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.as_bytes();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.bytes().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let sub = prev[j] + (ca != cb) as usize;
+            cur.push(sub.min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// An error returned when parsing a [`Target`] fails.
+#[derive(Clone, Debug)]
+pub struct ParseTargetError {
+    target: String,
+    suggestions: Vec<Target>,
+}
+
+impl ParseTargetError {
+    /// Returns known targets whose names are similar to the input.
+    pub fn suggestions(&self) -> &[Target] {
+        &self.suggestions
+    }
+}
+
+impl Display for ParseTargetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown target `{}`", self.target)?;
+        if let Some((first, rest)) = self.suggestions.split_first() {
+            write!(f, ". Did you mean `{}`", first.name())?;
+            for t in rest {
+                write!(f, ", `{}`", t.name())?;
+            }
+            write!(f, "?")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseTargetError {}
@@ -1,9 +1,19 @@
-use crate::builder::compute_layout;
+use crate::bitfield::{bit_field_accesses, read_field, write_field, BitFieldAccess};
+use crate::builder::{
+    compute_layout, compute_layout_with_options, compute_layout_with_warnings, Options,
+};
+use crate::flatten::{
+    leaf_fields, resolve_field_path, ArrayExpansion, FieldPathElement, LeafField,
+};
 use crate::layout::{
-    Annotation, Array, BuiltinType, Record, RecordField, RecordKind, Type, TypeLayout, TypeVariant,
+    Annotation, Array, BuiltinType, Enum, EnumUnderlyingType, Record, RecordField, RecordKind,
+    RustEnum, RustEnumRepr, RustEnumVariant, RustStruct, RustStructRepr, Type, TypeLayout,
+    TypeVariant,
 };
 use crate::result::ErrorType;
-use crate::target::Target;
+use crate::target::{Endianness, Target};
+use crate::unaligned::{unaligned_field_targets, unaligned_fields, UnalignedField};
+use crate::warning::{PathElement, WarningType};
 
 #[test]
 fn annotated_builtin() {
@@ -237,6 +247,7 @@ fn named_zero_sized_bit_field() {
                 annotations: vec![],
                 named: true,
                 bit_width: Some(0),
+                signed: None,
                 ty: Type {
                     layout: (),
                     annotations: vec![],
@@ -261,6 +272,7 @@ fn unnamed_regular_field() {
                 annotations: vec![],
                 named: false,
                 bit_width: None,
+                signed: None,
                 ty: Type {
                     layout: (),
                     annotations: vec![],
@@ -285,6 +297,7 @@ fn oversized_bitfield() {
                 annotations: vec![],
                 named: true,
                 bit_width: Some(64),
+                signed: None,
                 ty: Type {
                     layout: (),
                     annotations: vec![],
@@ -309,6 +322,7 @@ fn pragma_packed_field() {
                 annotations: vec![Annotation::PragmaPack(8)],
                 named: true,
                 bit_width: None,
+                signed: None,
                 ty: Type {
                     layout: (),
                     annotations: vec![],
@@ -320,3 +334,1201 @@ fn pragma_packed_field() {
     let err = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap_err();
     assert!(matches!(err.kind(), ErrorType::PragmaPackedField));
 }
+
+#[test]
+fn warning_ignored_pragma_pack() {
+    let ty = Type::<()> {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Typedef(Box::new(Type {
+            layout: (),
+            annotations: vec![Annotation::PragmaPack(24)],
+            variant: TypeVariant::Record(Record {
+                kind: RecordKind::Struct,
+                fields: vec![],
+            }),
+        })),
+    };
+    for &target in &[Target::X86_64UnknownLinuxGnu, Target::X86_64PcWindowsMsvc] {
+        let (_, warnings) = compute_layout_with_warnings(target, &ty).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind(), WarningType::IgnoredPragmaPack(24));
+        assert_eq!(warnings[0].path(), &[PathElement::TypedefTarget]);
+    }
+}
+
+#[test]
+fn warning_ignored_pragma_pack_msvc() {
+    let ty = |pack_bits| Type::<()> {
+        layout: (),
+        annotations: vec![Annotation::PragmaPack(pack_bits)],
+        variant: TypeVariant::Record(Record {
+            kind: RecordKind::Struct,
+            fields: vec![],
+        }),
+    };
+    let cases = [
+        (64, Target::I586PcWindowsMsvc, true),
+        (64, Target::I686PcWindowsMsvc, true),
+        (64, Target::X86_64PcWindowsMsvc, false),
+        (64, Target::I686UnknownLinuxGnu, false),
+        (128, Target::I686PcWindowsMsvc, true),
+        (128, Target::X86_64PcWindowsMsvc, true),
+        (128, Target::Thumbv7aPcWindowsMsvc, false),
+        (128, Target::Aarch64PcWindowsMsvc, false),
+        (128, Target::X86_64UnknownLinuxGnu, false),
+    ];
+    for &(pack_bits, target, ignored) in &cases {
+        let (_, warnings) = compute_layout_with_warnings(target, &ty(pack_bits)).unwrap();
+        let expected = match ignored {
+            true => vec![WarningType::IgnoredPragmaPack(pack_bits)],
+            false => vec![],
+        };
+        let actual: Vec<_> = warnings.iter().map(|w| w.kind()).collect();
+        assert_eq!(actual, expected, "{} {}", pack_bits, target.name());
+    }
+}
+
+#[test]
+fn ignored_pragma_pack_msvc_layout() {
+    // An ignored #pragma pack(N) has the same effect as no annotation.
+    let ty = |annotations| Type::<()> {
+        layout: (),
+        annotations,
+        variant: TypeVariant::Record(Record {
+            kind: RecordKind::Struct,
+            fields: vec![RecordField {
+                layout: None,
+                annotations: vec![],
+                named: true,
+                bit_width: None,
+                signed: None,
+                ty: Type {
+                    layout: (),
+                    annotations: vec![],
+                    variant: TypeVariant::Builtin(BuiltinType::Double),
+                },
+            }],
+        }),
+    };
+    let target = Target::I686PcWindowsMsvc;
+    let packed = compute_layout(target, &ty(vec![Annotation::PragmaPack(64)])).unwrap();
+    let unpacked = compute_layout(target, &ty(vec![])).unwrap();
+    assert_eq!(packed.layout, unpacked.layout);
+}
+
+#[test]
+fn warning_truncated_enum_value() {
+    let ty = Type::<()> {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Enum(Enum {
+            fixed_underlying_type: None,
+            variants: vec![1, 0xffff_0fff_ffff],
+            underlying_type: None,
+        }),
+    };
+    let (_, warnings) = compute_layout_with_warnings(Target::X86_64PcWindowsMsvc, &ty).unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(
+        warnings[0].kind(),
+        WarningType::TruncatedEnumValue(0xffff_0fff_ffff)
+    );
+    let (_, warnings) = compute_layout_with_warnings(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn warning_ignored_record_alignment() {
+    let ty = Type::<()> {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Record(Record {
+            kind: RecordKind::Struct,
+            fields: vec![RecordField {
+                layout: None,
+                annotations: vec![],
+                named: true,
+                bit_width: None,
+                signed: None,
+                ty: Type {
+                    layout: (),
+                    annotations: vec![Annotation::Align(Some(16))],
+                    variant: TypeVariant::Record(Record {
+                        kind: RecordKind::Struct,
+                        fields: vec![RecordField {
+                            layout: None,
+                            annotations: vec![],
+                            named: true,
+                            bit_width: None,
+                            signed: None,
+                            ty: Type {
+                                layout: (),
+                                annotations: vec![],
+                                variant: TypeVariant::Builtin(BuiltinType::Int),
+                            },
+                        }],
+                    }),
+                },
+            }],
+        }),
+    };
+    for &target in &[Target::X86_64UnknownLinuxGnu, Target::X86_64PcWindowsMsvc] {
+        let (_, warnings) = compute_layout_with_warnings(target, &ty).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].kind(),
+            WarningType::IgnoredRecordAlignment {
+                requested_bits: 16,
+                actual_bits: 32,
+            }
+        );
+        assert_eq!(warnings[0].path(), &[PathElement::Field(0)]);
+    }
+}
+
+fn record(
+    annotations: Vec<Annotation>,
+    fields: Vec<(Vec<Annotation>, Option<u64>, BuiltinType)>,
+) -> Type<()> {
+    Type::<()> {
+        layout: (),
+        annotations,
+        variant: TypeVariant::Record(Record {
+            kind: RecordKind::Struct,
+            fields: fields
+                .into_iter()
+                .map(|(annotations, bit_width, bi)| RecordField {
+                    layout: None,
+                    annotations,
+                    named: true,
+                    bit_width,
+                    signed: None,
+                    ty: Type {
+                        layout: (),
+                        annotations: vec![],
+                        variant: TypeVariant::Builtin(bi),
+                    },
+                })
+                .collect(),
+        }),
+    }
+}
+
+#[test]
+fn alignas_on_type() {
+    let ty = record(vec![Annotation::Alignas(64)], vec![]);
+    let err = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap_err();
+    assert!(matches!(err.kind(), ErrorType::AlignasOnType));
+}
+
+#[test]
+fn alignas_on_bit_field() {
+    let ty = record(
+        vec![],
+        vec![(vec![Annotation::Alignas(64)], Some(3), BuiltinType::Int)],
+    );
+    let err = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap_err();
+    assert!(matches!(err.kind(), ErrorType::AlignasOnBitField));
+}
+
+#[test]
+fn alignas_reduces_alignment() {
+    let ty = record(
+        vec![Annotation::AttrPacked],
+        vec![(vec![Annotation::Alignas(16)], None, BuiltinType::Int)],
+    );
+    for &target in &[
+        Target::X86_64UnknownLinuxGnu,
+        Target::X86_64PcWindowsGnu,
+        Target::X86_64PcWindowsMsvc,
+    ] {
+        let err = compute_layout(target, &ty).unwrap_err();
+        assert!(matches!(err.kind(), ErrorType::AlignasReducesAlignment));
+    }
+}
+
+#[test]
+fn alignas_in_packed_record() {
+    let ty = record(
+        vec![Annotation::AttrPacked],
+        vec![
+            (vec![], None, BuiltinType::Char),
+            (vec![Annotation::Alignas(64)], None, BuiltinType::Int),
+        ],
+    );
+    for &target in &[
+        Target::X86_64UnknownLinuxGnu,
+        Target::X86_64PcWindowsGnu,
+        Target::X86_64PcWindowsMsvc,
+    ] {
+        let ty = compute_layout(target, &ty).unwrap();
+        assert_eq!(ty.layout.size_bits, 128);
+        assert_eq!(ty.layout.field_alignment_bits, 64);
+        match &ty.variant {
+            TypeVariant::Record(r) => {
+                assert_eq!(r.fields[1].layout.unwrap().offset_bits, 64);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[test]
+fn overaligned_array_element() {
+    let ty = Type::<()> {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Array(Array {
+            element_type: Box::new(Type {
+                layout: (),
+                annotations: vec![Annotation::Align(Some(64))],
+                variant: TypeVariant::Typedef(Box::new(Type {
+                    layout: (),
+                    annotations: vec![],
+                    variant: TypeVariant::Builtin(BuiltinType::Int),
+                })),
+            }),
+            num_elements: Some(2),
+        }),
+    };
+    for &target in &[Target::X86_64UnknownLinuxGnu, Target::X86_64PcWindowsGnu] {
+        let err = compute_layout(target, &ty).unwrap_err();
+        assert!(matches!(err.kind(), ErrorType::OveralignedArrayElement));
+    }
+    for &target in &[Target::X86_64AppleMacosx, Target::X86_64PcWindowsMsvc] {
+        let ty = compute_layout(target, &ty).unwrap();
+        assert_eq!(ty.layout.size_bits, 64);
+    }
+    // typedef char Small[2] __attribute__((aligned(4))); Small s[3];
+    let ty = Type::<()> {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Array(Array {
+            element_type: Box::new(Type {
+                layout: (),
+                annotations: vec![Annotation::Align(Some(32))],
+                variant: TypeVariant::Typedef(Box::new(Type {
+                    layout: (),
+                    annotations: vec![],
+                    variant: TypeVariant::Array(Array {
+                        element_type: Box::new(Type {
+                            layout: (),
+                            annotations: vec![],
+                            variant: TypeVariant::Builtin(BuiltinType::Char),
+                        }),
+                        num_elements: Some(2),
+                    }),
+                })),
+            }),
+            num_elements: Some(3),
+        }),
+    };
+    let err = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap_err();
+    assert!(matches!(err.kind(), ErrorType::OveralignedArrayElement));
+}
+
+fn rust_fields(tys: Vec<TypeVariant<()>>) -> Vec<RecordField<()>> {
+    tys.into_iter()
+        .map(|variant| RecordField {
+            layout: None,
+            annotations: vec![],
+            named: true,
+            bit_width: None,
+            signed: None,
+            ty: Type {
+                layout: (),
+                annotations: vec![],
+                variant,
+            },
+        })
+        .collect()
+}
+
+fn rust_enum(repr: RustEnumRepr, variants: Vec<(i128, Vec<TypeVariant<()>>)>) -> Type<()> {
+    Type::<()> {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::RustEnum(RustEnum {
+            repr,
+            tag_layout: None,
+            variants: variants
+                .into_iter()
+                .map(|(discriminant, tys)| RustEnumVariant {
+                    discriminant,
+                    fields: rust_fields(tys),
+                })
+                .collect(),
+        }),
+    }
+}
+
+fn variant_field_offsets(ty: &Type<TypeLayout>) -> Vec<Vec<u64>> {
+    match &ty.variant {
+        TypeVariant::RustEnum(e) => e
+            .variants
+            .iter()
+            .map(|v| {
+                v.fields
+                    .iter()
+                    .map(|f| f.layout.unwrap().offset_bits)
+                    .collect()
+            })
+            .collect(),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn rust_transparent_struct() {
+    let ty = Type::<()> {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::RustStruct(RustStruct {
+            repr: RustStructRepr::Transparent,
+            fields: rust_fields(vec![
+                TypeVariant::Array(Array {
+                    element_type: Box::new(Type {
+                        layout: (),
+                        annotations: vec![],
+                        variant: TypeVariant::Builtin(BuiltinType::U8),
+                    }),
+                    num_elements: Some(0),
+                }),
+                TypeVariant::Builtin(BuiltinType::Pointer),
+            ]),
+        }),
+    };
+    for &target in &[Target::X86_64UnknownLinuxGnu, Target::X86_64PcWindowsMsvc] {
+        let layout = compute_layout(target, &ty).unwrap().layout;
+        assert_eq!(layout.size_bits, 64);
+        assert_eq!(layout.field_alignment_bits, 64);
+    }
+}
+
+#[test]
+fn invalid_rust_transparent_struct() {
+    let ty = Type::<()> {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::RustStruct(RustStruct {
+            repr: RustStructRepr::Transparent,
+            fields: rust_fields(vec![
+                TypeVariant::Builtin(BuiltinType::U8),
+                TypeVariant::Builtin(BuiltinType::Pointer),
+            ]),
+        }),
+    };
+    let err = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap_err();
+    assert!(matches!(err.kind(), ErrorType::InvalidTransparentStruct));
+}
+
+#[test]
+fn rust_int_enum() {
+    let ty = rust_enum(
+        RustEnumRepr::Int(BuiltinType::U8),
+        vec![
+            (0, vec![]),
+            (1, vec![TypeVariant::Builtin(BuiltinType::U32)]),
+            (2, vec![TypeVariant::Builtin(BuiltinType::U8)]),
+        ],
+    );
+    let ty = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+    assert_eq!(ty.layout.size_bits, 64);
+    assert_eq!(ty.layout.field_alignment_bits, 32);
+    assert_eq!(variant_field_offsets(&ty), vec![vec![], vec![32], vec![8]]);
+}
+
+#[test]
+fn rust_c_int_enum() {
+    let ty = rust_enum(
+        RustEnumRepr::CInt(BuiltinType::U8),
+        vec![
+            (0, vec![]),
+            (1, vec![TypeVariant::Builtin(BuiltinType::U32)]),
+            (2, vec![TypeVariant::Builtin(BuiltinType::U8)]),
+        ],
+    );
+    let ty = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+    assert_eq!(ty.layout.size_bits, 64);
+    assert_eq!(variant_field_offsets(&ty), vec![vec![], vec![32], vec![32]]);
+}
+
+#[test]
+fn rust_option_like_enum() {
+    let ty = rust_enum(
+        RustEnumRepr::Rust,
+        vec![
+            (0, vec![]),
+            (1, vec![TypeVariant::Builtin(BuiltinType::Pointer)]),
+        ],
+    );
+    let layout = compute_layout(Target::X86_64UnknownLinuxGnu, &ty)
+        .unwrap()
+        .layout;
+    assert_eq!(layout.size_bits, 64);
+    let ty = rust_enum(
+        RustEnumRepr::Rust,
+        vec![
+            (0, vec![TypeVariant::Builtin(BuiltinType::Pointer)]),
+            (1, vec![TypeVariant::Builtin(BuiltinType::Pointer)]),
+        ],
+    );
+    let err = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap_err();
+    assert!(matches!(err.kind(), ErrorType::InvalidOptionLikeEnum));
+
+    // Only pointers have a niche that can store the field-less variant.
+    let ty = rust_enum(
+        RustEnumRepr::Rust,
+        vec![
+            (0, vec![]),
+            (1, vec![TypeVariant::Builtin(BuiltinType::U32)]),
+        ],
+    );
+    let err = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap_err();
+    assert!(matches!(err.kind(), ErrorType::OptionLikeEnumWithoutNiche));
+    let pointer_typedef = TypeVariant::Typedef(Box::new(Type {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Builtin(BuiltinType::Pointer),
+    }));
+    let ty = rust_enum(
+        RustEnumRepr::Rust,
+        vec![(0, vec![]), (1, vec![pointer_typedef])],
+    );
+    let layout = compute_layout(Target::X86_64UnknownLinuxGnu, &ty)
+        .unwrap()
+        .layout;
+    assert_eq!(layout.size_bits, 64);
+}
+
+#[test]
+fn rust_enum_discriminant_out_of_range() {
+    let ty = rust_enum(RustEnumRepr::Int(BuiltinType::U8), vec![(256, vec![])]);
+    let err = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorType::RustEnumDiscriminantOutOfRange
+    ));
+}
+
+fn enum_type(fixed_underlying_type: Option<BuiltinType>, variants: Vec<i128>) -> Type<()> {
+    Type::<()> {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Enum(Enum {
+            fixed_underlying_type,
+            variants,
+            underlying_type: None,
+        }),
+    }
+}
+
+fn underlying_type(ty: &Type<TypeLayout>) -> Option<BuiltinType> {
+    match &ty.variant {
+        TypeVariant::Enum(e) => e.underlying_type.map(|u| u.ty),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn fixed_enum_underlying_type() {
+    let ty = enum_type(Some(BuiltinType::UnsignedChar), vec![1, 255]);
+    for &target in &[
+        Target::X86_64UnknownLinuxGnu,
+        Target::X86_64PcWindowsGnu,
+        Target::X86_64PcWindowsMsvc,
+    ] {
+        let ty = compute_layout(target, &ty).unwrap();
+        assert_eq!(ty.layout.size_bits, 8);
+        assert_eq!(underlying_type(&ty), Some(BuiltinType::UnsignedChar));
+    }
+}
+
+#[test]
+fn short_enums() {
+    let ty = enum_type(None, vec![1, 255]);
+    let options = Options { short_enums: true };
+    let layout = compute_layout_with_options(Target::ArmUnknownLinuxGnueabi, &ty, options).unwrap();
+    assert_eq!(layout.layout.size_bits, 8);
+    assert_eq!(underlying_type(&layout), Some(BuiltinType::UnsignedChar));
+    let layout = compute_layout(Target::ArmUnknownLinuxGnueabi, &ty).unwrap();
+    assert_eq!(layout.layout.size_bits, 32);
+    assert_eq!(underlying_type(&layout), Some(BuiltinType::UnsignedInt));
+    // MSVC does not support short enums.
+    let layout = compute_layout_with_options(Target::X86_64PcWindowsMsvc, &ty, options).unwrap();
+    assert_eq!(layout.layout.size_bits, 32);
+    // Short enums do not affect enums with a fixed underlying type.
+    let ty = enum_type(Some(BuiltinType::Int), vec![1]);
+    let layout = compute_layout_with_options(Target::ArmUnknownLinuxGnueabi, &ty, options).unwrap();
+    assert_eq!(layout.layout.size_bits, 32);
+}
+
+#[test]
+fn packed_enum_sign_bit() {
+    // In a signed type, positive values also require the sign bit. `{ -1, 200 }` does not
+    // fit into `signed char`. See test case 0093.
+    let packed = |variants| {
+        let mut ty = enum_type(None, variants);
+        ty.annotations = vec![Annotation::AttrPacked];
+        ty
+    };
+    for (variants, size_bits, bi) in [
+        (vec![-1, 200], 16, BuiltinType::Short),
+        (vec![-1, 127], 8, BuiltinType::SignedChar),
+        (vec![0, 200], 8, BuiltinType::UnsignedChar),
+        (vec![-1, 40000], 32, BuiltinType::Int),
+    ] {
+        let ty = compute_layout(Target::X86_64UnknownLinuxGnu, &packed(variants)).unwrap();
+        assert_eq!(ty.layout.size_bits, size_bits);
+        assert_eq!(underlying_type(&ty), Some(bi));
+    }
+    let options = Options { short_enums: true };
+    let ty = enum_type(None, vec![-1, 200]);
+    let ty = compute_layout_with_options(Target::X86_64UnknownLinuxGnu, &ty, options).unwrap();
+    assert_eq!(ty.layout.size_bits, 16);
+}
+
+#[test]
+fn invalid_enum_underlying_type() {
+    let ty = enum_type(Some(BuiltinType::Pointer), vec![0]);
+    let err = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap_err();
+    assert!(matches!(err.kind(), ErrorType::InvalidEnumUnderlyingType));
+}
+
+#[test]
+fn enum_value_out_of_range() {
+    let ty = enum_type(Some(BuiltinType::Char), vec![-1]);
+    // `char` is signed on x86 and unsigned on ARM.
+    assert!(compute_layout(Target::X86_64UnknownLinuxGnu, &ty).is_ok());
+    let err = compute_layout(Target::ArmUnknownLinuxGnueabi, &ty).unwrap_err();
+    assert!(matches!(err.kind(), ErrorType::EnumValueOutOfRange));
+    let ty = enum_type(Some(BuiltinType::Short), vec![1 << 15]);
+    let err = compute_layout(Target::X86_64PcWindowsMsvc, &ty).unwrap_err();
+    assert!(matches!(err.kind(), ErrorType::EnumValueOutOfRange));
+}
+
+#[test]
+fn enum_underlying_type_signedness() {
+    let selected = |target, ty: &Type<()>| match compute_layout(target, ty).unwrap().variant {
+        TypeVariant::Enum(e) => e.underlying_type.unwrap(),
+        _ => unreachable!(),
+    };
+    let ty = enum_type(None, vec![-1, 1]);
+    assert_eq!(
+        selected(Target::X86_64UnknownLinuxGnu, &ty),
+        EnumUnderlyingType {
+            ty: BuiltinType::Int,
+            signed: true,
+        }
+    );
+    let ty = enum_type(None, vec![1, 1 << 32]);
+    assert_eq!(
+        selected(Target::X86_64UnknownLinuxGnu, &ty),
+        EnumUnderlyingType {
+            ty: BuiltinType::UnsignedLong,
+            signed: false,
+        }
+    );
+    // A fixed `char` is reported as the `char` type with the same signedness.
+    let ty = enum_type(Some(BuiltinType::Char), vec![1]);
+    assert_eq!(
+        selected(Target::ArmUnknownLinuxGnueabi, &ty),
+        EnumUnderlyingType {
+            ty: BuiltinType::UnsignedChar,
+            signed: false,
+        }
+    );
+    assert_eq!(
+        selected(Target::X86_64PcWindowsMsvc, &ty),
+        EnumUnderlyingType {
+            ty: BuiltinType::SignedChar,
+            signed: true,
+        }
+    );
+}
+
+#[test]
+fn bit_field_access() {
+    let ty = record(
+        vec![],
+        vec![
+            (vec![], Some(3), BuiltinType::Int),
+            (vec![], Some(5), BuiltinType::UnsignedInt),
+            (vec![], None, BuiltinType::Char),
+            (vec![], Some(20), BuiltinType::Int),
+        ],
+    );
+    let ty = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+    let accesses = bit_field_accesses(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+    assert_eq!(accesses.len(), 3);
+    assert_eq!(
+        accesses[0],
+        BitFieldAccess {
+            field_index: 0,
+            storage_offset_bytes: 0,
+            storage_size_bytes: 4,
+            shift_bits: 0,
+            width_bits: 3,
+            signed: true,
+            endianness: Endianness::Little,
+        }
+    );
+    assert_eq!(accesses[1].shift_bits, 3);
+    assert_eq!(accesses[1].mask(), 0x1f);
+    assert!(!accesses[1].signed);
+    assert_eq!(accesses[2].field_index, 3);
+    assert_eq!(accesses[2].storage_offset_bytes, 4);
+    assert_eq!(accesses[2].shift_bits, 0);
+
+    let mut bytes = [0u8; 8];
+    write_field(&accesses[0], &mut bytes, -2);
+    write_field(&accesses[1], &mut bytes, 31);
+    write_field(&accesses[2], &mut bytes, -300);
+    assert_eq!(bytes, [0xfe, 0, 0, 0, 0xd4, 0xfe, 0x0f, 0]);
+    assert_eq!(read_field(&accesses[0], &bytes), -2);
+    assert_eq!(read_field(&accesses[1], &bytes), 31);
+    assert_eq!(read_field(&accesses[2], &bytes), -300);
+}
+
+#[test]
+fn big_endian_bit_field_access() {
+    let ty = record(
+        vec![],
+        vec![
+            (vec![], Some(3), BuiltinType::UnsignedInt),
+            (vec![], Some(5), BuiltinType::UnsignedInt),
+        ],
+    );
+    let ty = compute_layout(Target::PowerpcUnknownLinuxGnu, &ty).unwrap();
+    let accesses = bit_field_accesses(Target::PowerpcUnknownLinuxGnu, &ty).unwrap();
+    assert_eq!(accesses[0].endianness, Endianness::Big);
+    // The first bit-field occupies the most significant bits of the storage unit.
+    assert_eq!(accesses[0].shift_bits, 29);
+    assert_eq!(accesses[1].shift_bits, 24);
+    let mut bytes = [0u8; 4];
+    write_field(&accesses[0], &mut bytes, 5);
+    write_field(&accesses[1], &mut bytes, 1);
+    assert_eq!(bytes, [0xa1, 0, 0, 0]);
+    assert_eq!(read_field(&accesses[0], &bytes), 5);
+    assert_eq!(read_field(&accesses[1], &bytes), 1);
+}
+
+#[test]
+fn packed_bit_field_access() {
+    let ty = record(
+        vec![Annotation::AttrPacked],
+        vec![
+            (vec![], None, BuiltinType::Char),
+            (vec![], Some(16), BuiltinType::Int),
+        ],
+    );
+    let ty = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+    assert_eq!(ty.layout.size_bits, 24);
+    let accesses = bit_field_accesses(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+    // The storage unit of an int would extend beyond the end of the record.
+    assert_eq!(accesses[0].storage_offset_bytes, 1);
+    assert_eq!(accesses[0].storage_size_bytes, 2);
+    assert_eq!(accesses[0].shift_bits, 0);
+    let mut bytes = [0u8; 3];
+    write_field(&accesses[0], &mut bytes, 0x1234);
+    assert_eq!(bytes, [0, 0x34, 0x12]);
+}
+
+#[test]
+fn wide_packed_bit_field_access() {
+    let ty = record(
+        vec![Annotation::AttrPacked],
+        vec![
+            (vec![], Some(7), BuiltinType::U8),
+            (vec![], Some(127), BuiltinType::I128),
+        ],
+    );
+    let ty = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+    assert_eq!(ty.layout.size_bits, 136);
+    let accesses = bit_field_accesses(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+    // The storage unit of b spans 17 bytes.
+    assert_eq!(accesses[1].storage_offset_bytes, 0);
+    assert_eq!(accesses[1].storage_size_bytes, 17);
+    assert_eq!(accesses[1].shift_bits, 7);
+    let mut bytes = [0u8; 17];
+    write_field(&accesses[0], &mut bytes, 0x55);
+    write_field(&accesses[1], &mut bytes, -5);
+    assert_eq!(read_field(&accesses[0], &bytes), 0x55);
+    assert_eq!(read_field(&accesses[1], &bytes), -5);
+    let min = -(1i128 << 126);
+    write_field(&accesses[1], &mut bytes, min);
+    assert_eq!(read_field(&accesses[1], &bytes), min);
+    assert_eq!(bytes[0], 0x55);
+    assert_eq!(bytes[16], 0x20);
+}
+
+#[test]
+fn unnamed_bit_field_access() {
+    let bit_field = |named, width| RecordField {
+        layout: None,
+        annotations: vec![],
+        named,
+        bit_width: Some(width),
+        signed: None,
+        ty: Type {
+            layout: (),
+            annotations: vec![],
+            variant: TypeVariant::Builtin(BuiltinType::Int),
+        },
+    };
+    let ty = Type::<()> {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Record(Record {
+            kind: RecordKind::Struct,
+            fields: vec![bit_field(true, 3), bit_field(false, 5), bit_field(true, 4)],
+        }),
+    };
+    let ty = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+    let accesses = bit_field_accesses(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+    // The unnamed bit-field only occupies bits 3 to 7.
+    assert_eq!(accesses.len(), 2);
+    assert_eq!(accesses[0].field_index, 0);
+    assert_eq!(accesses[1].field_index, 2);
+    assert_eq!(accesses[1].shift_bits, 8);
+}
+
+#[test]
+fn bit_field_access_not_a_record() {
+    let ty = enum_type(None, vec![0]);
+    let ty = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+    let err = bit_field_accesses(Target::X86_64UnknownLinuxGnu, &ty).unwrap_err();
+    assert!(matches!(err.kind(), ErrorType::NotARecord));
+}
+
+fn field_signedness(ty: &Type<TypeLayout>) -> Vec<Option<bool>> {
+    match &ty.variant {
+        TypeVariant::Record(r) => r.fields.iter().map(|f| f.signed).collect(),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn bit_field_signedness() {
+    let ty = record(
+        vec![],
+        vec![
+            (vec![], Some(3), BuiltinType::Int),
+            (vec![], Some(3), BuiltinType::UnsignedInt),
+            (vec![], Some(3), BuiltinType::Char),
+            (vec![], Some(1), BuiltinType::Bool),
+            (vec![], None, BuiltinType::Int),
+        ],
+    );
+    for (target, char_signed) in [
+        (Target::X86_64UnknownLinuxGnu, true),
+        (Target::X86_64PcWindowsGnu, true),
+        (Target::X86_64PcWindowsMsvc, true),
+        (Target::Aarch64UnknownLinuxGnu, false),
+    ] {
+        let ty = compute_layout(target, &ty).unwrap();
+        assert_eq!(
+            field_signedness(&ty),
+            [
+                Some(true),
+                Some(false),
+                Some(char_signed),
+                Some(false),
+                None
+            ],
+            "{}",
+            target.name(),
+        );
+    }
+
+    // Enum bit-fields have the signedness of the underlying type of the enum.
+    let ty = Type::<()> {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Record(Record {
+            kind: RecordKind::Struct,
+            fields: vec![RecordField {
+                layout: None,
+                annotations: vec![],
+                named: true,
+                bit_width: Some(1),
+                signed: None,
+                ty: enum_type(None, vec![0, 1]),
+            }],
+        }),
+    };
+    let gcc = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+    assert_eq!(field_signedness(&gcc), [Some(false)]);
+    let msvc = compute_layout(Target::X86_64PcWindowsMsvc, &ty).unwrap();
+    assert_eq!(field_signedness(&msvc), [Some(true)]);
+}
+
+#[test]
+fn wide_bool_bit_field() {
+    let ty = record(vec![], vec![(vec![], Some(2), BuiltinType::Bool)]);
+    for target in [Target::X86_64UnknownLinuxGnu, Target::X86_64PcWindowsGnu] {
+        let err = compute_layout(target, &ty).unwrap_err();
+        assert!(matches!(err.kind(), ErrorType::OversizedBitfield));
+    }
+    let ty = compute_layout(Target::X86_64PcWindowsMsvc, &ty).unwrap();
+    assert_eq!(field_signedness(&ty), [Some(false)]);
+}
+
+#[test]
+fn bool_bit_field_storage_units() {
+    let ty = record(
+        vec![],
+        vec![
+            (vec![], Some(3), BuiltinType::Int),
+            (vec![], Some(1), BuiltinType::Bool),
+            (vec![], Some(2), BuiltinType::Char),
+            (vec![], Some(1), BuiltinType::Bool),
+        ],
+    );
+    // MSVC places a `bool` bit-field in a new one-byte storage unit after an `int` bit-field
+    // but shares the storage unit with `char` bit-fields.
+    for (target, offsets, size_bits) in [
+        (Target::X86_64PcWindowsMsvc, [0, 32, 33, 35], 64),
+        (Target::X86_64UnknownLinuxGnu, [0, 3, 4, 6], 32),
+    ] {
+        let ty = compute_layout(target, &ty).unwrap();
+        let actual: Vec<_> = match &ty.variant {
+            TypeVariant::Record(r) => r
+                .fields
+                .iter()
+                .map(|f| f.layout.unwrap().offset_bits)
+                .collect(),
+            _ => unreachable!(),
+        };
+        assert_eq!(actual, offsets, "{}", target.name());
+        assert_eq!(ty.layout.size_bits, size_bits, "{}", target.name());
+    }
+}
+
+#[test]
+fn target_metadata() {
+    use crate::builder::common::builtin_type_layout;
+    use crate::target::{DataModel, TARGETS};
+    for &target in TARGETS {
+        let size = |bi| builtin_type_layout(target, bi).size_bits;
+        let pointer = size(BuiltinType::Pointer);
+        assert_eq!(target.pointer_width_bits(), pointer, "{:?}", target);
+        let model = match (size(BuiltinType::Int), size(BuiltinType::Long), pointer) {
+            (16, 32, 16) => DataModel::Ip16,
+            (32, 32, 32) => DataModel::Ilp32,
+            (32, 64, 64) => DataModel::Lp64,
+            (32, 32, 64) => DataModel::Llp64,
+            sizes => panic!("{:?}: {:?}", target, sizes),
+        };
+        assert_eq!(target.data_model(), model, "{:?}", target);
+    }
+    assert_eq!(Target::S390xUnknownLinuxGnu.endianness(), Endianness::Big);
+    assert!(!Target::S390xUnknownLinuxGnu.char_is_signed());
+    assert_eq!(Target::X86_64PcWindowsMsvc.wchar_size_bits(), 16);
+    assert_eq!(Target::X86_64PcWindowsMsvc.data_model(), DataModel::Llp64);
+    assert_eq!(
+        Target::X86_64UnknownLinuxGnux32.data_model(),
+        DataModel::Ilp32
+    );
+    assert_eq!(Target::Aarch64AppleMacosx.wchar_size_bits(), 32);
+    assert!(Target::Aarch64AppleMacosx.char_is_signed());
+    assert_eq!(Target::X86_64PcWindowsGnu.os(), "windows");
+    assert_eq!(Target::ArmLinuxAndroideabi.os(), "android");
+    assert_eq!(Target::Armv6UnknownNetbsdelfEabihf.os(), "netbsd");
+    assert_eq!(Target::Arm64AppleIosMacabi.os(), "ios");
+    assert_eq!(Target::Thumbv7mNoneEabi.os(), "none");
+}
+
+#[test]
+fn parse_target() {
+    let parse = |s: &str| s.parse::<Target>().unwrap();
+    assert_eq!(
+        parse("x86_64-unknown-linux-gnu"),
+        Target::X86_64UnknownLinuxGnu
+    );
+    assert_eq!(
+        parse("x86_64-unknown-linux-gnux32"),
+        Target::X86_64UnknownLinuxGnux32
+    );
+    assert_eq!(parse("aarch64-apple-darwin"), Target::Aarch64AppleMacosx);
+    assert_eq!(parse("x86_64-w64-mingw32"), Target::X86_64PcWindowsGnu);
+    assert_eq!(parse("i686-w64-mingw32"), Target::I686PcWindowsGnu);
+    assert_eq!(parse("x86_64-linux-gnu"), Target::X86_64UnknownLinuxGnu);
+    assert_eq!(parse("x86_64-windows-msvc"), Target::X86_64PcWindowsMsvc);
+    assert_eq!(parse("arm64-apple-darwin"), Target::Aarch64AppleMacosx);
+    assert_eq!(parse("arm64-apple-ios"), Target::Arm64AppleIos);
+    assert_eq!(parse("amd64-unknown-freebsd"), Target::X86_64UnknownFreebsd);
+    assert_eq!(parse("x86_64-apple-macosx10.15"), Target::X86_64AppleMacosx);
+    assert_eq!(parse("X86_64-PC-Windows-MSVC"), Target::X86_64PcWindowsMsvc);
+    for &target in crate::target::TARGETS {
+        assert_eq!(parse(target.name()), target);
+    }
+    for &(rustc, target) in crate::target::TARGET_MAP {
+        assert_eq!(parse(rustc), target);
+    }
+
+    let err = "x86_64-unknown-linux-gnuu".parse::<Target>().unwrap_err();
+    assert_eq!(err.suggestions()[0], Target::X86_64UnknownLinuxGnu);
+    assert!(err
+        .to_string()
+        .contains("Did you mean `x86_64-unknown-linux-gnu`"));
+    let err = "x86_64-linux-gnx".parse::<Target>().unwrap_err();
+    assert_eq!(err.suggestions()[0], Target::X86_64UnknownLinuxGnu);
+    let err = "foo".parse::<Target>().unwrap_err();
+    assert!(err.suggestions().is_empty());
+    assert_eq!(err.to_string(), "Unknown target `foo`");
+}
+
+#[test]
+fn c_typedefs() {
+    use crate::typedefs::{c_typedef, C_TYPEDEFS};
+    let layout = |target, name| {
+        let ty = c_typedef(target, name).unwrap();
+        let l = compute_layout(target, &ty).unwrap().layout;
+        (l.size_bits, l.field_alignment_bits)
+    };
+    for &target in crate::target::TARGETS {
+        for name in C_TYPEDEFS {
+            let (size, _) = layout(target, name);
+            assert_ne!(size, 0, "{:?} {}", target, name);
+        }
+        assert_eq!(layout(target, "size_t").0, target.pointer_width_bits());
+        assert_eq!(layout(target, "wchar_t").0, target.wchar_size_bits());
+    }
+    assert!(c_typedef(Target::X86_64UnknownLinuxGnu, "foo_t").is_none());
+    assert_eq!(layout(Target::X86_64PcWindowsMsvc, "wchar_t"), (16, 16));
+    assert_eq!(layout(Target::X86_64UnknownLinuxGnu, "wchar_t"), (32, 32));
+    assert_eq!(layout(Target::I686UnknownLinuxGnu, "time_t"), (32, 32));
+    assert_eq!(layout(Target::I686UnknownLinuxMusl, "time_t"), (64, 32));
+    assert_eq!(layout(Target::I686PcWindowsMsvc, "time_t"), (64, 64));
+    assert_eq!(layout(Target::I686UnknownLinuxGnu, "off_t"), (32, 32));
+    assert_eq!(
+        layout(Target::X86_64UnknownLinuxGnu, "max_align_t"),
+        (256, 128)
+    );
+    assert_eq!(
+        layout(Target::I686UnknownLinuxGnu, "max_align_t"),
+        (384, 128)
+    );
+    assert_eq!(layout(Target::I686UnknownFreebsd, "max_align_t"), (192, 64));
+    assert_eq!(layout(Target::X86_64PcWindowsMsvc, "max_align_t"), (64, 64));
+    assert_eq!(layout(Target::Aarch64AppleMacosx, "max_align_t"), (128, 64));
+}
+
+#[test]
+fn unaligned_packed_fields() {
+    let builtin = |bi| Type::<()> {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Builtin(bi),
+    };
+    let inner = record(vec![], vec![(vec![], None, BuiltinType::Int)]);
+    let ty = Type::<()> {
+        layout: (),
+        annotations: vec![Annotation::AttrPacked],
+        variant: TypeVariant::Record(Record {
+            kind: RecordKind::Struct,
+            fields: vec![
+                builtin(BuiltinType::Char),
+                builtin(BuiltinType::Short),
+                inner,
+            ]
+            .into_iter()
+            .map(|ty| RecordField {
+                layout: None,
+                annotations: vec![],
+                named: true,
+                bit_width: None,
+                signed: None,
+                ty,
+            })
+            .collect(),
+        }),
+    };
+    let layout = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+    assert_eq!(
+        unaligned_fields(&layout),
+        [
+            UnalignedField {
+                path: vec![PathElement::Field(1)],
+                offset_bits: 8,
+                alignment_bits: 16,
+            },
+            UnalignedField {
+                path: vec![PathElement::Field(2)],
+                offset_bits: 24,
+                alignment_bits: 32,
+            },
+            UnalignedField {
+                path: vec![PathElement::Field(2), PathElement::Field(0)],
+                offset_bits: 24,
+                alignment_bits: 32,
+            },
+        ]
+    );
+
+    let reports = unaligned_field_targets(&ty, Options::default());
+    assert_eq!(reports.len(), 3);
+    for report in &reports {
+        assert!(report.targets.contains(&Target::X86_64UnknownLinuxGnu));
+        // All types have an alignment of 1 on AVR.
+        assert!(!report.targets.contains(&Target::AvrUnknownUnknown));
+    }
+}
+
+#[test]
+fn unaligned_array_elements() {
+    // The second element of the array starts at offset 3.
+    let element = record(
+        vec![Annotation::AttrPacked],
+        vec![
+            (vec![], None, BuiltinType::Short),
+            (vec![], None, BuiltinType::Char),
+        ],
+    );
+    let ty = Type::<()> {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Array(Array {
+            element_type: Box::new(element),
+            num_elements: Some(1000),
+        }),
+    };
+    let layout = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+    assert_eq!(
+        unaligned_fields(&layout),
+        [UnalignedField {
+            path: vec![PathElement::ArrayElement, PathElement::Field(0)],
+            offset_bits: 24,
+            alignment_bits: 16,
+        }]
+    );
+}
+
+#[test]
+fn unaligned_field_on_strict_alignment_targets() {
+    // The second field starts at offset 4 on targets where `long` has 4 bytes. This is
+    // unaligned wherever `long long` has an alignment of 8 bytes.
+    let ty = record(
+        vec![Annotation::AttrPacked],
+        vec![
+            (vec![], None, BuiltinType::Long),
+            (vec![], None, BuiltinType::LongLong),
+        ],
+    );
+    let reports = unaligned_field_targets(&ty, Options::default());
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].path, [PathElement::Field(1)]);
+    let targets = &reports[0].targets;
+    assert!(targets.contains(&Target::Thumbv7emNoneEabi));
+    assert!(targets.contains(&Target::MipsUnknownLinuxGnu));
+    assert!(!targets.contains(&Target::X86_64UnknownLinuxGnu));
+    // `long long` has an alignment of 4 bytes on i686.
+    assert!(!targets.contains(&Target::I686UnknownLinuxGnu));
+}
+
+fn field_paths(ty: &Type<TypeLayout>, arrays: ArrayExpansion) -> Vec<(Vec<FieldPathElement>, u64)> {
+    leaf_fields(ty, arrays)
+        .map(|f| (f.path, f.field.offset_bits))
+        .collect()
+}
+
+#[test]
+fn flatten_fields() {
+    use FieldPathElement::*;
+    // struct { int a:3; int :0; struct { short s; char bits[3]; } b[2]; }
+    let builtin = |bi| Type {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Builtin(bi),
+    };
+    let array = |ty, n| Type {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Array(Array {
+            element_type: Box::new(ty),
+            num_elements: Some(n),
+        }),
+    };
+    let field = |named, bit_width, ty| RecordField {
+        layout: None,
+        annotations: vec![],
+        named,
+        bit_width,
+        signed: None,
+        ty,
+    };
+    let structure = |fields| Type::<()> {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Record(Record {
+            kind: RecordKind::Struct,
+            fields,
+        }),
+    };
+    let inner = structure(vec![
+        field(true, None, builtin(BuiltinType::Short)),
+        field(true, None, array(builtin(BuiltinType::Char), 3)),
+    ]);
+    let ty = structure(vec![
+        field(true, Some(3), builtin(BuiltinType::Int)),
+        field(false, Some(0), builtin(BuiltinType::Int)),
+        field(true, None, array(inner, 2)),
+    ]);
+    let ty = compute_layout(Target::X86_64UnknownLinuxGnu, &ty).unwrap();
+
+    let leaves: Vec<_> = leaf_fields(&ty, ArrayExpansion::Elements).collect();
+    assert_eq!(leaves.len(), 9);
+    assert_eq!(leaves[0].path, [Field(0)]);
+    assert!(leaves[0].field.bit_field);
+    assert_eq!(leaves[0].field.size_bits, 3);
+    assert_eq!(leaves[0].builtin_type, Some(BuiltinType::Int));
+    assert_eq!(
+        field_paths(&ty, ArrayExpansion::Elements)[1..],
+        [
+            (vec![Field(2), Index(0), Field(0)], 32),
+            (vec![Field(2), Index(0), Field(1), Index(0)], 48),
+            (vec![Field(2), Index(0), Field(1), Index(1)], 56),
+            (vec![Field(2), Index(0), Field(1), Index(2)], 64),
+            (vec![Field(2), Index(1), Field(0)], 80),
+            (vec![Field(2), Index(1), Field(1), Index(0)], 96),
+            (vec![Field(2), Index(1), Field(1), Index(1)], 104),
+            (vec![Field(2), Index(1), Field(1), Index(2)], 112),
+        ]
+    );
+
+    let outer = Elements {
+        num_elements: Some(2),
+        stride_bits: 48,
+    };
+    let bits = Elements {
+        num_elements: Some(3),
+        stride_bits: 8,
+    };
+    assert_eq!(
+        field_paths(&ty, ArrayExpansion::Strided),
+        [
+            (vec![Field(0)], 0),
+            (vec![Field(2), outer, Field(0)], 32),
+            (vec![Field(2), outer, Field(1), bits], 48),
+        ]
+    );
+    let last: LeafField = leaf_fields(&ty, ArrayExpansion::Strided).last().unwrap();
+    assert_eq!(last.builtin_type, Some(BuiltinType::Char));
+    assert_eq!(last.field.size_bits, 8);
+
+    let f = resolve_field_path(&ty, &[Field(2), Index(1), Field(1), Index(2)]).unwrap();
+    assert_eq!(f.offset_bits, 112);
+    let f = resolve_field_path(&ty, &[Field(2), Index(1)]).unwrap();
+    assert_eq!((f.offset_bits, f.size_bits), (80, 48));
+    let err = |path: &[FieldPathElement]| resolve_field_path(&ty, path).unwrap_err().kind();
+    assert!(matches!(err(&[Field(1)]), ErrorType::FieldIndexOutOfBounds));
+    assert!(matches!(err(&[Field(3)]), ErrorType::FieldIndexOutOfBounds));
+    assert!(matches!(err(&[Index(0)]), ErrorType::NotAnArray));
+    assert!(matches!(err(&[Field(0), Field(0)]), ErrorType::NotARecord));
+    assert!(matches!(
+        err(&[Field(2), Index(3)]),
+        ErrorType::ArrayIndexOutOfBounds
+    ));
+}
@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//! Definitions of common typedefs from the C standard library.
+//!
+//! The definitions follow the default C library and compiler settings of each target. For
+//! example, `off_t` is 32 bits wide on 32-bit glibc targets because glibc uses 32-bit file
+//! offsets unless `_FILE_OFFSET_BITS=64` is defined.
+use crate::layout::{
+    Annotation, BuiltinType, Record, RecordField, RecordKind, Type, TypeLayout, TypeVariant,
+};
+use crate::target::{system_compiler, Compiler, DataModel, Target};
+
+/// The names of all typedefs defined by [`c_typedef`].
+pub const C_TYPEDEFS: &[&str] = &[
+    "size_t",
+    "ssize_t",
+    "ptrdiff_t",
+    "intptr_t",
+    "uintptr_t",
+    "wchar_t",
+    "char16_t",
+    "char32_t",
+    "max_align_t",
+    "off_t",
+    "time_t",
+];
+
+/// Returns the definition of the C typedef `name` on `target`.
+///
+/// Returns `None` if `name` is not one of the names in [`C_TYPEDEFS`]. The returned type is
+/// a typedef of a builtin type or, in the case of `max_align_t`, a typedef of a record.
+pub fn c_typedef(target: Target, name: &str) -> Option<Type<()>> {
+    use BuiltinType::*;
+    let model = target.data_model();
+    let (signed, unsigned) = match model {
+        DataModel::Lp64 => (Long, UnsignedLong),
+        DataModel::Llp64 => (LongLong, UnsignedLongLong),
+        DataModel::Ilp32 | DataModel::Ip16 => (Int, UnsignedInt),
+    };
+    let bi = match name {
+        "size_t" | "uintptr_t" => unsigned,
+        "ssize_t" | "ptrdiff_t" | "intptr_t" => signed,
+        "wchar_t" => wchar_type(target),
+        "char16_t" => UnsignedShort,
+        "char32_t" => match model {
+            DataModel::Ip16 => UnsignedLong,
+            _ => UnsignedInt,
+        },
+        "off_t" => match model {
+            DataModel::Ilp32 if has_64_bit_off_t(target) => LongLong,
+            _ => Long,
+        },
+        "time_t" => match model {
+            DataModel::Llp64 => LongLong,
+            DataModel::Ilp32 if !has_32_bit_time_t(target) => LongLong,
+            _ => Long,
+        },
+        "max_align_t" => return Some(typedef(max_align_t(target))),
+        _ => return None,
+    };
+    Some(typedef(builtin(bi)))
+}
+
+fn arch(target: Target) -> &'static str {
+    target.name().split('-').next().unwrap()
+}
+
+fn is_apple(target: Target) -> bool {
+    target.name().contains("-apple-")
+}
+
+fn is_windows(target: Target) -> bool {
+    target.name().contains("-windows")
+}
+
+fn is_x86(target: Target) -> bool {
+    matches!(arch(target), "i386" | "i586" | "i686")
+}
+
+fn wchar_type(target: Target) -> BuiltinType {
+    let arch = arch(target);
+    match target.wchar_size_bits() {
+        16 if is_windows(target) => BuiltinType::UnsignedShort,
+        16 => BuiltinType::Int,
+        _ if is_apple(target) => BuiltinType::Int,
+        _ if arch == "aarch64" || arch.starts_with("arm") || arch.starts_with("thumb") => {
+            BuiltinType::UnsignedInt
+        }
+        _ => BuiltinType::Int,
+    }
+}
+
+fn has_64_bit_off_t(target: Target) -> bool {
+    let name = target.name();
+    is_apple(target)
+        || name.contains("bsd")
+        || name.contains("musl")
+        || name.contains("haiku")
+        || name.contains("gnux32")
+        || arch(target) == "wasm32"
+}
+
+fn has_32_bit_time_t(target: Target) -> bool {
+    let name = target.name();
+    (name.contains("-linux-gnu") && !name.contains("gnux32"))
+        || name.contains("uclibc")
+        || name.contains("android")
+        || name.contains("haiku")
+        || is_apple(target)
+        || target == Target::I686UnknownFreebsd
+}
+
+/// Returns the size and alignment of `long double` in bits.
+fn long_double_layout(target: Target) -> (u64, u64) {
+    let arch = arch(target);
+    match arch {
+        "x86_64" | "riscv32" | "riscv64" | "wasm32" | "sparc64" | "sparcv9" => (128, 128),
+        "i386" | "i586" | "i686" if is_apple(target) => (128, 128),
+        "i386" | "i586" | "i686" if target.name().contains("android") => (64, 32),
+        "i386" | "i586" | "i686" => (96, 32),
+        "aarch64" | "arm64" if is_apple(target) => (64, 64),
+        "aarch64" | "arm64" => (128, 128),
+        "mips64" | "mips64el" | "mipsisa64r6" | "mipsisa64r6el" => (128, 128),
+        "powerpc" if target.name().contains("musl") => (64, 64),
+        "powerpc" | "powerpc64" | "powerpc64le" => (128, 128),
+        "s390x" | "sparc" => (128, 64),
+        "avr" => (32, 8),
+        "msp430" => (64, 16),
+        _ => (64, 64),
+    }
+}
+
+fn max_align_t(target: Target) -> Type<()> {
+    if system_compiler(target) == Compiler::Msvc {
+        return builtin(BuiltinType::Double);
+    }
+    // The C libraries define `max_align_t` as a struct containing a `long long` and a
+    // `long double`, each aligned to its preferred alignment. On i386, the preferred
+    // alignment of `long long` is larger than its alignment in records and GCC adds a
+    // `__float128` member.
+    let mut fields = vec![
+        field(builtin(BuiltinType::LongLong)),
+        field(opaque(long_double_layout(target))),
+    ];
+    if is_x86(target) {
+        fields[0].annotations.push(Annotation::Align(Some(64)));
+        if system_compiler(target) == Compiler::Gcc {
+            fields.push(field(opaque((128, 128))));
+        }
+    }
+    Type {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Record(Record {
+            kind: RecordKind::Struct,
+            fields,
+        }),
+    }
+}
+
+fn typedef(ty: Type<()>) -> Type<()> {
+    Type {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Typedef(Box::new(ty)),
+    }
+}
+
+fn builtin(bi: BuiltinType) -> Type<()> {
+    Type {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Builtin(bi),
+    }
+}
+
+fn opaque((size_bits, alignment_bits): (u64, u64)) -> Type<()> {
+    Type {
+        layout: (),
+        annotations: vec![],
+        variant: TypeVariant::Opaque(TypeLayout {
+            size_bits,
+            field_alignment_bits: alignment_bits,
+            pointer_alignment_bits: alignment_bits,
+            required_alignment_bits: 8,
+        }),
+    }
+}
+
+fn field(ty: Type<()>) -> RecordField<()> {
+    RecordField {
+        layout: None,
+        annotations: vec![],
+        named: true,
+        bit_width: None,
+        signed: None,
+        ty,
+    }
+}
@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//! Functions for finding fields that are not aligned to the alignment of their type.
+//!
+//! Packed records can place fields at offsets that are not multiples of the natural
+//! alignment of their types. Taking a reference to such a field is undefined behavior in
+//! Rust, and accessing it through a pointer traps on targets that require aligned memory
+//! accesses.
+use crate::builder::{compute_layout_with_options, Options};
+use crate::layout::{RecordField, Type, TypeLayout, TypeVariant};
+use crate::target::{Target, TARGETS};
+use crate::warning::PathElement;
+
+/// A field whose offset is not a multiple of the alignment of its type.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnalignedField {
+    /// The path from the root type to the field.
+    ///
+    /// The last element of the path is either [`PathElement::Field`] or
+    /// [`PathElement::VariantField`].
+    pub path: Vec<PathElement>,
+    /// The offset of the field from the start of the root type in bits.
+    ///
+    /// If the field is contained in an array, this is the offset of the first unaligned
+    /// occurrence of the field.
+    pub offset_bits: u64,
+    /// The pointer alignment of the type of the field in bits.
+    pub alignment_bits: u64,
+}
+
+/// A field that is not aligned to the alignment of its type on some targets.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnalignedFieldTargets {
+    /// The path from the root type to the field.
+    pub path: Vec<PathElement>,
+    /// The targets on which the field is not aligned.
+    pub targets: Vec<Target>,
+}
+
+/// Returns all fields in `ty` whose offsets are not multiples of the pointer alignment of
+/// their types.
+///
+/// Nested records, arrays, and typedefs are searched recursively. Offsets are computed
+/// relative to the start of `ty`, so a field that is aligned within its record can still be
+/// reported if the record itself is placed at an unaligned offset. Bit-fields are ignored
+/// because they cannot be referenced.
+pub fn unaligned_fields(ty: &Type<TypeLayout>) -> Vec<UnalignedField> {
+    let mut uc = UnalignedFieldCollector {
+        path: vec![],
+        fields: vec![],
+    };
+    uc.visit_type(ty, 0);
+    uc.fields
+}
+
+/// Computes the layout of `ty` for every target in [`TARGETS`] and returns the fields that
+/// are not aligned on at least one of them.
+///
+/// The fields are returned in the order in which they first appear. Targets on which the
+/// layout of `ty` cannot be computed are ignored.
+pub fn unaligned_field_targets(ty: &Type<()>, options: Options) -> Vec<UnalignedFieldTargets> {
+    let mut res: Vec<UnalignedFieldTargets> = vec![];
+    for &target in TARGETS {
+        let layout = match compute_layout_with_options(target, ty, options) {
+            Ok(l) => l,
+            Err(_) => continue,
+        };
+        for field in unaligned_fields(&layout) {
+            match res.iter_mut().find(|f| f.path == field.path) {
+                Some(f) => f.targets.push(target),
+                None => res.push(UnalignedFieldTargets {
+                    path: field.path,
+                    targets: vec![target],
+                }),
+            }
+        }
+    }
+    res
+}
+
+struct UnalignedFieldCollector {
+    path: Vec<PathElement>,
+    fields: Vec<UnalignedField>,
+}
+
+impl UnalignedFieldCollector {
+    fn visit_type(&mut self, ty: &Type<TypeLayout>, offset_bits: u64) {
+        match &ty.variant {
+            TypeVariant::Record(r) => {
+                for (i, f) in r.fields.iter().enumerate() {
+                    self.visit_field(PathElement::Field(i), f, offset_bits);
+                }
+            }
+            TypeVariant::RustStruct(s) => {
+                for (i, f) in s.fields.iter().enumerate() {
+                    self.visit_field(PathElement::Field(i), f, offset_bits);
+                }
+            }
+            TypeVariant::RustEnum(e) => {
+                for (i, v) in e.variants.iter().enumerate() {
+                    for (j, f) in v.fields.iter().enumerate() {
+                        self.visit_field(PathElement::VariantField(i, j), f, offset_bits);
+                    }
+                }
+            }
+            TypeVariant::Typedef(dst) => {
+                self.path.push(PathElement::TypedefTarget);
+                self.visit_type(dst, offset_bits);
+                self.path.pop();
+            }
+            TypeVariant::Array(a) => {
+                // The offsets of the elements modulo the largest alignment in the element
+                // type repeat after `period` elements. Visiting more elements cannot find
+                // additional unaligned fields. Flexible arrays are treated as if they had
+                // arbitrarily many elements.
+                let size = a.element_type.layout.size_bits;
+                let max_alignment = max_alignment_bits(&a.element_type);
+                let period = max_alignment / gcd(size, max_alignment);
+                let num = a.num_elements.map_or(period, |n| n.min(period));
+                self.path.push(PathElement::ArrayElement);
+                for i in 0..num {
+                    self.visit_type(&a.element_type, offset_bits + i * size);
+                }
+                self.path.pop();
+            }
+            TypeVariant::Builtin(_) | TypeVariant::Opaque(_) | TypeVariant::Enum(_) => {}
+        }
+    }
+
+    fn visit_field(&mut self, pe: PathElement, field: &RecordField<TypeLayout>, offset_bits: u64) {
+        let layout = match field.layout {
+            Some(l) if field.bit_width.is_none() => l,
+            _ => return,
+        };
+        let offset_bits = offset_bits + layout.offset_bits;
+        let alignment_bits = field.ty.layout.pointer_alignment_bits;
+        self.path.push(pe);
+        if !offset_bits.is_multiple_of(alignment_bits)
+            && self.fields.iter().all(|f| f.path != self.path)
+        {
+            self.fields.push(UnalignedField {
+                path: self.path.clone(),
+                offset_bits,
+                alignment_bits,
+            });
+        }
+        self.visit_type(&field.ty, offset_bits);
+        self.path.pop();
+    }
+}
+
+/// Returns the largest pointer alignment of `ty` and all types contained in it.
+fn max_alignment_bits(ty: &Type<TypeLayout>) -> u64 {
+    let nested = match &ty.variant {
+        TypeVariant::Record(r) => r.fields.iter().map(|f| max_alignment_bits(&f.ty)).max(),
+        TypeVariant::RustStruct(s) => s.fields.iter().map(|f| max_alignment_bits(&f.ty)).max(),
+        TypeVariant::RustEnum(e) => e
+            .variants
+            .iter()
+            .flat_map(|v| &v.fields)
+            .map(|f| max_alignment_bits(&f.ty))
+            .max(),
+        TypeVariant::Typedef(dst) => Some(max_alignment_bits(dst)),
+        TypeVariant::Array(a) => Some(max_alignment_bits(&a.element_type)),
+        TypeVariant::Builtin(_) | TypeVariant::Opaque(_) | TypeVariant::Enum(_) => None,
+    };
+    nested.map_or(ty.layout.pointer_alignment_bits, |n| {
+        n.max(ty.layout.pointer_alignment_bits)
+    })
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
@@ -147,13 +147,31 @@ pub(crate) fn is_attr_packed(a: &[Annotation]) -> bool {
 pub(crate) fn annotation_alignment(target: Target, annotations: &[Annotation]) -> Option<u64> {
     let mut max = None;
     for a in annotations {
-        if let Annotation::Align(n) = a {
-            max.assign_max(n.unwrap_or_else(|| default_aligned_alignment(target)));
+        match a {
+            Annotation::Align(n) => {
+                max.assign_max(n.unwrap_or_else(|| default_aligned_alignment(target)))
+            }
+            Annotation::Alignas(n) => max.assign_max(*n),
+            _ => {}
         }
     }
     max
 }
 
+/// Checks that the `Alignas` annotations on a field do not reduce its alignment.
+///
+/// `ty_alignment_bits` is the field alignment of the type of the field.
+pub(crate) fn validate_alignas(annotations: &[Annotation], ty_alignment_bits: u64) -> Result<()> {
+    for a in annotations {
+        if let Annotation::Alignas(n) = a {
+            if *n < ty_alignment_bits {
+                return Err(err(ErrorType::AlignasReducesAlignment));
+            }
+        }
+    }
+    Ok(())
+}
+
 pub(crate) fn pragma_pack_value(a: &[Annotation]) -> Option<u64> {
     for a in a {
         if let Annotation::PragmaPack(n) = a {
@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 use crate::layout::{
-    Annotation, Array, BuiltinType, Layout, Record, RecordField, Type, TypeVariant,
+    Annotation, Array, BuiltinType, Enum, Layout, Record, RecordField, RustEnum, RustStruct, Type,
+    TypeVariant,
 };
 
 /// This trait represents a visitor that walks through a [`Type`].
@@ -11,14 +12,14 @@ use crate::layout::{
 /// # Example
 ///
 /// ```
-/// # use repc_impl::layout::{Layout, Type};
+/// # use repc_impl::layout::{Enum, Layout, Type};
 /// # use repc_impl::visitor::{Visitor, visit_enum};
 /// struct Impl;
 ///
 /// impl<I: Layout> Visitor<I> for Impl {
-///     fn visit_enum(&mut self, variants: &[i128], ty: &Type<I>) {
-///         println!("Variants: {:?}", variants);
-///         visit_enum(self, variants, ty)
+///     fn visit_enum(&mut self, e: &Enum, ty: &Type<I>) {
+///         println!("Variants: {:?}", e.variants);
+///         visit_enum(self, e, ty)
 ///     }
 /// }
 /// ```
@@ -64,8 +65,23 @@ pub trait Visitor<I: Layout> {
     }
 
     /// Called for enums.
-    fn visit_enum(&mut self, variants: &[i128], ty: &Type<I>) {
-        visit_enum(self, variants, ty);
+    fn visit_enum(&mut self, e: &Enum, ty: &Type<I>) {
+        visit_enum(self, e, ty);
+    }
+
+    /// Called for Rust structs.
+    fn visit_rust_struct(&mut self, rust_struct: &RustStruct<I>, ty: &Type<I>) {
+        visit_rust_struct(self, rust_struct, ty);
+    }
+
+    /// Called for Rust enums.
+    fn visit_rust_enum(&mut self, rust_enum: &RustEnum<I>, ty: &Type<I>) {
+        visit_rust_enum(self, rust_enum, ty);
+    }
+
+    /// Called for the fields of Rust structs and the fields of variants of Rust enums.
+    fn visit_rust_field(&mut self, field: &RecordField<I>, ty: &Type<I>) {
+        visit_rust_field(self, field, ty);
     }
 }
 
@@ -79,6 +95,8 @@ pub fn visit_type<I: Layout>(visitor: &mut (impl Visitor<I> + ?Sized), ty: &Type
         TypeVariant::Array(at) => visitor.visit_array(at, ty),
         TypeVariant::Opaque(l) => visitor.visit_opaque_type(*l, ty),
         TypeVariant::Enum(l) => visitor.visit_enum(l, ty),
+        TypeVariant::RustStruct(s) => visitor.visit_rust_struct(s, ty),
+        TypeVariant::RustEnum(e) => visitor.visit_rust_enum(e, ty),
     }
 }
 
@@ -162,13 +180,44 @@ pub fn visit_opaque_type<I: Layout>(
 }
 
 /// The default implementation of `Visitor::visit_enum`.
-pub fn visit_enum<I: Layout>(
+pub fn visit_enum<I: Layout>(visitor: &mut (impl Visitor<I> + ?Sized), e: &Enum, ty: &Type<I>) {
+    let _ = visitor;
+    let _ = e;
+    let _ = ty;
+    // nothing
+}
+
+/// The default implementation of `Visitor::visit_rust_struct`.
+pub fn visit_rust_struct<I: Layout>(
     visitor: &mut (impl Visitor<I> + ?Sized),
-    variants: &[i128],
+    rust_struct: &RustStruct<I>,
+    ty: &Type<I>,
+) {
+    for f in &rust_struct.fields {
+        visitor.visit_rust_field(f, ty);
+    }
+}
+
+/// The default implementation of `Visitor::visit_rust_enum`.
+pub fn visit_rust_enum<I: Layout>(
+    visitor: &mut (impl Visitor<I> + ?Sized),
+    rust_enum: &RustEnum<I>,
+    ty: &Type<I>,
+) {
+    for v in &rust_enum.variants {
+        for f in &v.fields {
+            visitor.visit_rust_field(f, ty);
+        }
+    }
+}
+
+/// The default implementation of `Visitor::visit_rust_field`.
+pub fn visit_rust_field<I: Layout>(
+    visitor: &mut (impl Visitor<I> + ?Sized),
+    field: &RecordField<I>,
     ty: &Type<I>,
 ) {
-    let _ = visitor;
-    let _ = variants;
     let _ = ty;
-    // nothing
+    visitor.visit_annotations(&field.annotations);
+    visitor.visit_type(&field.ty);
 }
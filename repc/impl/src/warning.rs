@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+use crate::util::BITS_PER_BYTE;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// A warning produced by this crate.
+///
+/// Warnings describe inputs that are valid but whose effect is probably not what was
+/// intended, e.g., annotations that are silently ignored by the compiler.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Warning {
+    pub(crate) kind: WarningType,
+    pub(crate) path: Vec<PathElement>,
+}
+
+impl Warning {
+    /// Returns the type of the warning.
+    pub fn kind(&self) -> WarningType {
+        self.kind.clone()
+    }
+
+    /// Returns the path from the root type to the type the warning applies to.
+    ///
+    /// The path is empty if the warning applies to the root type itself.
+    pub fn path(&self) -> &[PathElement] {
+        &self.path
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.kind, f)
+    }
+}
+
+/// An element of the path from a type to one of the types contained in it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PathElement {
+    /// The type of the field with the given index in a record.
+    ///
+    /// The index counts all fields, including unnamed bit-fields.
+    Field(usize),
+    /// The target type of a typedef.
+    TypedefTarget,
+    /// The element type of an array.
+    ArrayElement,
+    /// The type of a field in a variant of a Rust enum.
+    ///
+    /// The first index is the index of the variant, the second index is the index of the
+    /// field within the variant.
+    VariantField(usize, usize),
+}
+
+/// The type of a warning produced by this crate.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum WarningType {
+    /// A `PragmaPack` annotation was ignored.
+    ///
+    /// The values of `N` that `#pragma pack(N)` accepts depend on the target. On GCC and
+    /// Clang targets, `N` must be one of 1, 2, 4, 8, and 16. On MSVC targets, 8 is ignored
+    /// on x86 and 16 is only accepted on arm and arm64. Otherwise the annotation has no
+    /// effect. The contained value is the argument of the annotation in bits.
+    IgnoredPragmaPack(u64),
+    /// The value of an enum variant was truncated.
+    ///
+    /// On MSVC targets, the underlying type of an enum is always `int`. Values that do not
+    /// fit into `int` are silently truncated. The contained value is the value given in
+    /// the input.
+    TruncatedEnumValue(i128),
+    /// An `Align` annotation on a record was ignored.
+    ///
+    /// The alignment requested by the annotation is smaller than the alignment the record
+    /// has without the annotation. Such annotations cannot reduce the alignment of a record.
+    IgnoredRecordAlignment {
+        /// The alignment requested by the annotation in bits.
+        requested_bits: u64,
+        /// The actual alignment of the record in bits.
+        actual_bits: u64,
+    },
+}
+
+impl Display for WarningType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use WarningType::*;
+        match self {
+            IgnoredPragmaPack(n) => write!(
+                f,
+                "pragma_pack({}) is ignored because the target does not support this argument",
+                Bytes(*n)
+            ),
+            TruncatedEnumValue(v) => write!(
+                f,
+                "The enum value {} does not fit into the underlying type and is truncated",
+                v
+            ),
+            IgnoredRecordAlignment {
+                requested_bits,
+                actual_bits,
+            } => write!(
+                f,
+                "align({}) is ignored because the record already has alignment {}",
+                Bytes(*requested_bits),
+                Bytes(*actual_bits)
+            ),
+        }
+    }
+}
+
+struct Bytes(u64);
+
+impl Display for Bytes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 % BITS_PER_BYTE {
+            0 => write!(f, "{}", self.0 / BITS_PER_BYTE),
+            _ => write!(f, "{} bits", self.0),
+        }
+    }
+}
@@ -2,8 +2,8 @@
 use anyhow::bail;
 use anyhow::Result;
 use cly_impl::ast::{
-    Annotation, Array, BinaryExprType, BuiltinExpr, Declaration, DeclarationType, Expr, ExprType,
-    Record, RecordField, Type, TypeExprType, TypeVariant, UnaryExprType,
+    Annotation, Array, BinaryExprType, BuiltinExpr, Declaration, DeclarationType, Enum, Expr,
+    ExprType, Record, RecordField, Type, TypeExprType, TypeVariant, UnaryExprType,
 };
 use repc_impl::layout::{BuiltinType, RecordKind};
 use repc_impl::target::Compiler;
@@ -40,8 +40,12 @@ impl Generator {
     fn generate(&mut self, d: &[Declaration]) -> Result<()> {
         for d in d {
             match &d.ty {
+                DeclarationType::Type(_) if !d.params.is_empty() => {
+                    bail!("cannot emit generic declarations")
+                }
                 DeclarationType::Type(t) => self.emit_type_decl(&d.name, t)?,
                 DeclarationType::Const(c) => self.emit_const(&d.name, c)?,
+                DeclarationType::Assert(_) => {}
             }
         }
         Ok(())
@@ -68,6 +72,9 @@ impl Generator {
         self.stack
             .push(mem::replace(&mut self.current, String::new()));
         let annotations = get_unique_annotations(&t.annotations)?;
+        if !annotations.alignas.is_empty() {
+            bail!("alignas can only be used on fields");
+        }
         if let Some(p) = annotations.pragma_pack {
             write!(self.current, "#pragma pack(")?;
             self.emit_expr(p)?;
@@ -89,6 +96,9 @@ impl Generator {
             TypeVariant::Opaque(_) => bail!("opaque types cannot be declared"),
             TypeVariant::Name(..) => bail!("names cannot be declared"),
             TypeVariant::Enum(r) => self.emit_enum(name, &annotations, r)?,
+            TypeVariant::RustStruct(_) | TypeVariant::RustEnum(_) => {
+                bail!("Rust types cannot be declared")
+            }
         }
         let id = self.generate_id();
         writeln!(self.current, "{} var{};", name, id)?;
@@ -160,9 +170,15 @@ impl Generator {
         Ok(())
     }
 
-    fn emit_enum(&mut self, n: &str, a: &Annotations, e: &[Expr]) -> Result<()> {
-        writeln!(self.current, "typedef enum {{")?;
-        for e in e.iter() {
+    fn emit_enum(&mut self, n: &str, a: &Annotations, e: &Enum) -> Result<()> {
+        write!(self.current, "typedef enum ")?;
+        if let Some(bi) = e.fixed_underlying_type {
+            write!(self.current, ": ")?;
+            self.emit_builtin_type(bi)?;
+            write!(self.current, " ")?;
+        }
+        writeln!(self.current, "{{")?;
+        for e in e.variants.iter() {
             let idx = self.generate_id();
             write!(self.current, "    F{} = ", idx)?;
             self.emit_expr(e)?;
@@ -250,6 +266,11 @@ impl Generator {
             bail!("pragma pack cannot be used on fields");
         }
         write!(self.current, "    ")?;
+        for a in &annotations.alignas {
+            write!(self.current, "_Alignas(")?;
+            self.emit_expr(a)?;
+            write!(self.current, ") ")?;
+        }
         if self.compiler == Compiler::Msvc {
             if let Some(a) = annotations.align {
                 match a {
@@ -280,10 +301,37 @@ impl Generator {
                 let s = match k {
                     UnaryExprType::Neg => "-",
                     UnaryExprType::Not => "!",
+                    UnaryExprType::BitNot => "~",
                 };
                 write!(self.current, "{}", s)?;
                 self.emit_expr(v)?;
             }
+            ExprType::Binary(k @ (BinaryExprType::Min | BinaryExprType::Max), l, r) => {
+                let op = match k {
+                    BinaryExprType::Min => "<",
+                    _ => ">",
+                };
+                write!(self.current, "((")?;
+                self.emit_expr(l)?;
+                write!(self.current, ") {} (", op)?;
+                self.emit_expr(r)?;
+                write!(self.current, ") ? (")?;
+                self.emit_expr(l)?;
+                write!(self.current, ") : (")?;
+                self.emit_expr(r)?;
+                write!(self.current, "))")?;
+            }
+            ExprType::Binary(BinaryExprType::AlignUp, l, r) => {
+                write!(self.current, "((((")?;
+                self.emit_expr(l)?;
+                write!(self.current, ") + (")?;
+                self.emit_expr(r)?;
+                write!(self.current, ") - 1) / (")?;
+                self.emit_expr(r)?;
+                write!(self.current, ")) * (")?;
+                self.emit_expr(r)?;
+                write!(self.current, "))")?;
+            }
             ExprType::Binary(k, l, r) => {
                 self.emit_expr(l)?;
                 let s = match k {
@@ -300,23 +348,47 @@ impl Generator {
                     BinaryExprType::Le => "<=",
                     BinaryExprType::Gt => ">",
                     BinaryExprType::Ge => ">=",
+                    BinaryExprType::BitAnd => "&",
+                    BinaryExprType::BitOr => "|",
+                    BinaryExprType::BitXor => "^",
+                    BinaryExprType::Shl => "<<",
+                    BinaryExprType::Shr => ">>",
+                    BinaryExprType::Min | BinaryExprType::Max | BinaryExprType::AlignUp => {
+                        unreachable!()
+                    }
                 };
                 write!(self.current, " {} ", s)?;
                 self.emit_expr(r)?;
             }
             ExprType::TypeExpr(k, t) => {
-                write!(self.current, "(sizeof(")?;
+                let op = match k {
+                    TypeExprType::Sizeof | TypeExprType::SizeofBits => "sizeof",
+                    TypeExprType::Alignof | TypeExprType::AlignofBits => "_Alignof",
+                    _ => bail!("cannot emit pointer and required alignments"),
+                };
+                write!(self.current, "({}(", op)?;
                 self.emit_type_name(t)?;
                 write!(self.current, ")")?;
-                if *k == TypeExprType::SizeofBits {
+                if let TypeExprType::SizeofBits | TypeExprType::AlignofBits = k {
                     write!(self.current, "*8")?;
                 }
                 write!(self.current, ")")?;
             }
+            ExprType::Conditional(c, t, e) => {
+                write!(self.current, "(")?;
+                self.emit_expr(c)?;
+                write!(self.current, " ? ")?;
+                self.emit_expr(t)?;
+                write!(self.current, " : ")?;
+                self.emit_expr(e)?;
+                write!(self.current, ")")?;
+            }
             ExprType::Builtin(bi) => match bi {
                 BuiltinExpr::BitsPerByte => write!(self.current, "8")?,
+                BuiltinExpr::TargetPointerWidth => write!(self.current, "(sizeof(void *) * 8)")?,
             },
             ExprType::Offsetof(_, _, _) => bail!("cannot emit offsetof"),
+            ExprType::Target(..) => bail!("cannot emit target properties"),
         }
         Ok(())
     }
@@ -324,7 +396,8 @@ impl Generator {
     fn emit_type_name(&mut self, ty: &Type) -> Result<()> {
         use TypeVariant::*;
         match &ty.variant {
-            Name(n, _) => write!(self.current, "{}", n)?,
+            Name(n, args, _) if args.is_empty() => write!(self.current, "{}", n)?,
+            Name(..) => bail!("cannot emit generic types"),
             Builtin(bi) => self.emit_builtin_type(*bi)?,
             _ => {
                 let name = format!("unnamed_type_{}", self.generate_id());
@@ -345,12 +418,14 @@ impl Generator {
 
 struct Annotations<'a> {
     align: Option<Option<&'a Expr>>,
+    alignas: Vec<&'a Expr>,
     attr_packed: bool,
     pragma_pack: Option<&'a Expr>,
 }
 
 fn get_unique_annotations(a: &[Annotation]) -> Result<Annotations> {
     let mut align = None;
+    let mut alignas = vec![];
     let mut attr_packed = false;
     let mut pragma_pack = None;
     for a in a {
@@ -368,10 +443,12 @@ fn get_unique_annotations(a: &[Annotation]) -> Result<Annotations> {
                 }
                 align = Some(n.as_ref().map(|n| &**n));
             }
+            Annotation::Alignas(n) => alignas.push(&**n),
         }
     }
     Ok(Annotations {
         align,
+        alignas,
         attr_packed,
         pragma_pack,
     })
@@ -8,10 +8,12 @@ use cly_impl::ast;
 use cly_impl::ast::Declaration;
 use cly_impl::converter::{Computer, ConversionResult, Convert};
 use gimli::{
-    Attribute, AttributeValue, DW_AT_bit_size, DW_AT_byte_size, DW_AT_data_bit_offset,
-    DW_AT_data_member_location, DW_AT_name, DW_AT_type, DW_TAG_member, DW_TAG_pointer_type,
-    DW_TAG_structure_type, DW_TAG_typedef, DW_TAG_union_type, DebuggingInformationEntry,
-    EndianRcSlice, EntriesTree, EvaluationResult, Location, RunTimeEndian, SectionId,
+    Attribute, AttributeValue, DW_ATE_boolean, DW_ATE_signed, DW_ATE_signed_char, DW_ATE_unsigned,
+    DW_ATE_unsigned_char, DW_AT_bit_size, DW_AT_byte_size, DW_AT_data_bit_offset,
+    DW_AT_data_member_location, DW_AT_encoding, DW_AT_name, DW_AT_type, DW_TAG_enumeration_type,
+    DW_TAG_member, DW_TAG_pointer_type, DW_TAG_structure_type, DW_TAG_typedef, DW_TAG_union_type,
+    DebuggingInformationEntry, DwAte, EndianRcSlice, EntriesTree, EvaluationResult, Location,
+    RunTimeEndian, SectionId,
 };
 use object::{Object, ObjectSection};
 use repc_impl::builder::common::builtin_type_layout;
@@ -32,6 +34,8 @@ struct Converter<'a> {
     target: Target,
     offset_fields: HashMap<usize, Vec<Field>>,
     typedefs: HashMap<usize, usize>,
+    enum_underlying_types: HashMap<usize, usize>,
+    encodings: HashMap<usize, DwAte>,
     name_offsets: HashMap<String, usize>,
     offset_names: HashMap<usize, String>,
     offset_sizes: HashMap<usize, u64>,
@@ -46,6 +50,18 @@ impl<'a> Converter<'a> {
         offset
     }
 
+    fn is_signed(&self, offset: usize) -> Option<bool> {
+        let offset = self.traverse_typedefs(offset);
+        if let Some(underlying) = self.enum_underlying_types.get(&offset) {
+            return self.is_signed(*underlying);
+        }
+        match *self.encodings.get(&offset)? {
+            DW_ATE_signed | DW_ATE_signed_char => Some(true),
+            DW_ATE_unsigned | DW_ATE_unsigned_char | DW_ATE_boolean => Some(false),
+            _ => None,
+        }
+    }
+
     fn get_record_fields(&self, name: &str) -> &[Field] {
         let offset = *self.name_offsets.get(name).unwrap();
         let offset = self.traverse_typedefs(offset);
@@ -69,10 +85,10 @@ impl<'a> Convert for Converter<'a> {
     fn extract_type(&self, ty: &ast::Type) -> Result<TypeLayout> {
         use ast::TypeVariant::*;
         let name = match &ty.variant {
-            Name(n, _) => n,
+            Name(n, ..) => n,
             Record(_) | Typedef(_) | Array(_) | Enum(_) => self.type_id_names.get(&ty.id).unwrap(),
             Builtin(bi) => return Ok(builtin_type_layout(self.target, *bi)),
-            Opaque(_) => unreachable!(),
+            Opaque(_) | RustStruct(_) | RustEnum(_) => unreachable!(),
         };
         let required_alignment = self.get_second_field_offset(name, "required_alignment");
         let alignment = self.get_second_field_offset(name, "alignment");
@@ -108,6 +124,16 @@ impl<'a> Convert for Converter<'a> {
             size_bits,
         })
     }
+
+    fn extract_signedness(&self, field: &ast::RecordField, fpos: usize) -> Result<Option<bool>> {
+        let name = self.type_id_names.get(&field.parent_id).unwrap();
+        let dwarf_field = &self.get_record_fields(name)[fpos];
+        Ok(self.is_signed(dwarf_field.type_offset))
+    }
+
+    fn target(&self) -> Option<Target> {
+        Some(self.target)
+    }
 }
 
 pub(crate) fn convert(
@@ -173,6 +199,8 @@ pub(crate) fn convert(
 
     let mut offset_fields = HashMap::new();
     let mut typedefs = HashMap::new();
+    let mut enum_underlying_types = HashMap::new();
+    let mut encodings = HashMap::new();
     let mut name_offsets = HashMap::new();
     let mut offset_names = HashMap::new();
     let mut offset_sizes = HashMap::new();
@@ -227,6 +255,12 @@ pub(crate) fn convert(
             if entry_tag == DW_TAG_typedef {
                 typedefs.insert(offset, type_offset(&entry));
             }
+            if entry_tag == DW_TAG_enumeration_type && entry.attr(DW_AT_type)?.is_some() {
+                enum_underlying_types.insert(offset, type_offset(&entry));
+            }
+            if let Some(AttributeValue::Encoding(encoding)) = entry.attr_value(DW_AT_encoding)? {
+                encodings.insert(offset, encoding);
+            }
             if entry_tag == DW_TAG_structure_type || entry_tag == DW_TAG_union_type {
                 let mut children = node.children();
                 let mut fields = vec![];
@@ -262,6 +296,8 @@ pub(crate) fn convert(
         target,
         offset_fields,
         typedefs,
+        enum_underlying_types,
+        encodings,
         name_offsets,
         offset_names,
         offset_sizes,
@@ -105,6 +105,8 @@ fn process_target(
         return Ok(());
     }
     eprintln!("generating {}", expected_file.display());
+    let declarations =
+        &cly_impl::evaluate_conditions(input, declarations, target, Default::default())?;
     let (code, ids) = c::generate(&declarations, system_compiler(target))?;
     let tmpdir = tempdir::TempDir::new("")?;
     let c_file = tmpdir.path().join("test.c");
@@ -14,6 +14,7 @@ struct Converter<'a> {
     target: Target,
     records: HashMap<String, u32>,
     bitfields: HashMap<u32, BitfieldType>,
+    enum_underlying_types: HashMap<u32, u32>,
     fields: HashMap<u32, FieldList<'a>>,
     ids: &'a HashMap<usize, String>,
     pdb_index_names: HashMap<u32, String>,
@@ -35,6 +36,22 @@ impl<'a> Converter<'a> {
         }
     }
 
+    fn is_signed(&self, ty: u32) -> Option<bool> {
+        match ty {
+            // https://github.com/Microsoft/microsoft-pdb/blob/082c5290e5aff028ae84e43affa8be717aa7af73/include/cvinfo.h#L326-L750
+            0x0010 | 0x0011 | 0x0012 | 0x0013 | 0x0068 | 0x0070 | 0x0072 | 0x0074 | 0x0076 => {
+                Some(true)
+            }
+            0x0020 | 0x0021 | 0x0022 | 0x0023 | 0x0030 | 0x0069 | 0x0073 | 0x0075 | 0x0077 => {
+                Some(false)
+            }
+            _ => self
+                .enum_underlying_types
+                .get(&ty)
+                .and_then(|u| self.is_signed(*u)),
+        }
+    }
+
     fn for_each_member<F: FnMut(&MemberType)>(&self, fl: u32, mut f: F) {
         let mut fields = self.fields.get(&fl).unwrap();
         loop {
@@ -64,10 +81,10 @@ impl<'a> Convert for Converter<'a> {
     fn extract_type(&self, ty: &ast::Type) -> Result<TypeLayout> {
         use ast::TypeVariant::*;
         let name = match &ty.variant {
-            Name(n, _) => n,
+            Name(n, ..) => n,
             Record(_) | Typedef(_) | Array(_) | Enum(_) => self.ids.get(&ty.id).unwrap(),
             Builtin(bi) => return Ok(builtin_type_layout(self.target, *bi)),
-            Opaque(_) => unreachable!(),
+            Opaque(_) | RustStruct(_) | RustEnum(_) => unreachable!(),
         };
         let alignment = self.get_b_offset(name, "alignment");
         let size_bits = self.get_b_offset(name, "size") - 8;
@@ -123,6 +140,25 @@ impl<'a> Convert for Converter<'a> {
             _ => panic!(),
         }
     }
+
+    fn extract_signedness(&self, field: &ast::RecordField, fpos: usize) -> Result<Option<bool>> {
+        let name = self.ids.get(&field.parent_id).unwrap();
+        let fields = self.records.get(name).unwrap();
+        let mut pos = 0;
+        let mut signed = None;
+        self.for_each_member(*fields, |m| {
+            if pos == fpos {
+                let bf = self.bitfields.get(&m.field_type.0).unwrap();
+                signed = self.is_signed(bf.underlying_type.0);
+            }
+            pos += 1;
+        });
+        Ok(signed)
+    }
+
+    fn target(&self) -> Option<Target> {
+        Some(self.target)
+    }
 }
 
 pub(crate) fn convert(
@@ -134,6 +170,7 @@ pub(crate) fn convert(
 ) -> Result<ConversionResult> {
     let mut records = HashMap::new();
     let mut bitfields = HashMap::new();
+    let mut enum_underlying_types = HashMap::new();
     let mut fields = HashMap::new();
     let mut sizes = HashMap::new();
     let mut pdb_index_names = HashMap::new();
@@ -172,7 +209,9 @@ pub(crate) fn convert(
                 pdb_index_names.insert(idx, name.clone());
                 sizes.insert(name, a.dimensions.last().copied().unwrap() as u64);
             }
-            TypeData::Enumeration(_) => {}
+            TypeData::Enumeration(e) => {
+                enum_underlying_types.insert(idx, e.underlying_type.0);
+            }
             _ => bail!("unexpected type info {:?}", ti),
         }
     }
@@ -181,6 +220,7 @@ pub(crate) fn convert(
         target,
         records,
         bitfields,
+        enum_underlying_types,
         fields,
         ids,
         pdb_index_names,
@@ -5,7 +5,7 @@ use cly_impl::ast::Declaration;
 use isnt::std_1::vec::IsntVecExt;
 use rayon::iter::IntoParallelRefIterator;
 use rayon::iter::ParallelIterator;
-use repc_impl::layout::{FieldLayout, Layout, Record, Type, TypeLayout};
+use repc_impl::layout::{FieldLayout, Layout, Record, Type, TypeLayout, TypeVariant};
 use repc_impl::target::{Target, TARGETS};
 use repc_impl::visitor::{visit_record, visit_type, Visitor};
 use std::path::Path;
@@ -77,6 +77,16 @@ fn process_target(
     if !global_config.test_target(target) {
         return Ok(true);
     }
+    let output_dir = dir.join("output");
+    let expected_file = output_dir.join(format!("{}.expected.txt", target.name()));
+    let expected = std::fs::read_to_string(&expected_file)
+        .with_context(|| anyhow!("cannot read {}", expected_file.display()))?;
+    // Outputs that were generated before the signedness of bit-fields was extracted do not
+    // contain it.
+    let strip_signedness = !expected.contains("signedness:");
+
+    let declarations =
+        &cly_impl::evaluate_conditions(input, declarations, target, Default::default())?;
     let mut actual_conversion_result = cly_impl::compute_layouts(input, declarations, target)?;
     for ty in actual_conversion_result.types.values() {
         TypeValidator.visit_type(ty);
@@ -84,18 +94,21 @@ fn process_target(
     actual_conversion_result.types = actual_conversion_result
         .types
         .into_iter()
-        .map(|(l, r)| {
+        .map(|(l, mut r)| {
+            strip_computed_properties(&mut r, strip_signedness);
             let r: Type<TypeLayoutWithoutPointerAlignment> = r.into();
             (l, r.into())
         })
         .collect();
 
-    let output_dir = dir.join("output");
-    let expected_file = output_dir.join(format!("{}.expected.txt", target.name()));
-    let expected = std::fs::read_to_string(&expected_file)
-        .with_context(|| anyhow!("cannot read {}", expected_file.display()))?;
     let expected_declarations = cly_impl::parse(&expected)
         .with_context(|| anyhow!("Parsing {} failed", expected_file.display()))?;
+    let expected_declarations = cly_impl::evaluate_conditions(
+        &expected,
+        &expected_declarations,
+        target,
+        Default::default(),
+    )?;
     let expected_conversion_result = cly_impl::extract_layouts(&expected, &expected_declarations)?;
 
     if actual_conversion_result == expected_conversion_result {
@@ -108,6 +121,32 @@ fn process_target(
     Ok(false)
 }
 
+/// The expected output does not contain the underlying types of enums. It contains the
+/// signedness of bit-fields only if it was generated from debug info that describes it.
+fn strip_computed_properties(ty: &mut Type<TypeLayout>, signedness: bool) {
+    match &mut ty.variant {
+        TypeVariant::Enum(e) => e.underlying_type = None,
+        TypeVariant::Typedef(dst) => strip_computed_properties(dst, signedness),
+        TypeVariant::Array(a) => strip_computed_properties(&mut a.element_type, signedness),
+        TypeVariant::Record(r) => r.fields.iter_mut().for_each(|f| {
+            if signedness {
+                f.signed = None;
+            }
+            strip_computed_properties(&mut f.ty, signedness)
+        }),
+        TypeVariant::RustStruct(s) => s
+            .fields
+            .iter_mut()
+            .for_each(|f| strip_computed_properties(&mut f.ty, signedness)),
+        TypeVariant::RustEnum(e) => e
+            .variants
+            .iter_mut()
+            .flat_map(|v| &mut v.fields)
+            .for_each(|f| strip_computed_properties(&mut f.ty, signedness)),
+        TypeVariant::Builtin(_) | TypeVariant::Opaque(_) => {}
+    }
+}
+
 struct TypeValidator;
 
 impl Visitor<TypeLayout> for TypeValidator {